@@ -0,0 +1,4 @@
+//! C library for the SVG loader, see `include/svgload.h`. The entry points live in
+//! `svg_load::ffi`; this crate only links them into a shared and a static library.
+
+pub use svg_load::ffi::*;
@@ -0,0 +1,70 @@
+/* Exercises the C API through include/svgload.h only, so a header that disagrees with the
+ * library in a signature or struct layout fails to compile or reads back wrong values. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "svgload.h"
+
+#define CHECK(cond)                                                      \
+    do {                                                                 \
+        if (!(cond)) {                                                   \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            return 1;                                                    \
+        }                                                                \
+    } while (0)
+
+static const char SCENE[] =
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\">"
+    "<linearGradient id=\"g\" x1=\"0\" x2=\"1\">"
+    "<stop offset=\"0\" stop-color=\"#0000ff\"/><stop offset=\"1\" stop-color=\"#00ff00\"/>"
+    "</linearGradient>"
+    "<rect width=\"10\" height=\"10\" fill=\"#ff0000\"/>"
+    "<rect width=\"10\" height=\"5\" fill=\"url(#g)\" opacity=\"0.5\"/>"
+    "</svg>";
+
+int main(void) {
+    SvgScene *scene = NULL;
+    CHECK(svgload_load_data((const uint8_t *)SCENE, strlen(SCENE), &scene) == SVGLOAD_OK);
+    CHECK(svgload_scene_primitive_count(scene) == 2);
+
+    for (size_t p = 0; p < 2; p++) {
+        const SvgVertex *vertices = NULL;
+        const uint32_t *indices = NULL;
+        size_t vertex_count = 0, index_count = 0;
+        CHECK(svgload_primitive_vertices(scene, p, &vertices, &vertex_count) == SVGLOAD_OK);
+        CHECK(svgload_primitive_indices(scene, p, &indices, &index_count) == SVGLOAD_OK);
+        CHECK(vertex_count >= 4 && index_count >= 6 && index_count % 3 == 0);
+        for (size_t i = 0; i < vertex_count; i++) {
+            CHECK(vertices[i].position[0] >= 0.0f && vertices[i].position[0] <= 10.0f);
+            CHECK(vertices[i].position[1] >= 0.0f && vertices[i].position[1] <= 10.0f);
+            CHECK(vertices[i].prim_id == p);
+        }
+        for (size_t i = 0; i < index_count; i++) {
+            CHECK(indices[i] < vertex_count);
+        }
+    }
+
+    SvgPaint paint;
+    CHECK(svgload_primitive_paint(scene, 0, &paint) == SVGLOAD_OK);
+    CHECK(paint.color[0] == 1.0f && paint.color[1] == 0.0f && paint.color[2] == 0.0f && paint.color[3] == 1.0f);
+    CHECK(paint.stop_count == 0 && paint.stop_positions == NULL && paint.stop_colors == NULL);
+    CHECK(paint.opacity == 1.0f);
+
+    CHECK(svgload_primitive_paint(scene, 1, &paint) == SVGLOAD_OK);
+    CHECK(paint.stop_count == 2);
+    CHECK(paint.stop_positions[0] == 0.0f && paint.stop_positions[1] == 1.0f);
+    CHECK(paint.stop_colors[2] == 1.0f && paint.stop_colors[5] == 1.0f && paint.stop_colors[7] == 1.0f);
+    CHECK(paint.gradient_start[0] < paint.gradient_end[0]);
+    CHECK(paint.opacity == 0.5f);
+
+    CHECK(svgload_primitive_paint(scene, 2, &paint) == SVGLOAD_ERR_OUT_OF_RANGE);
+    svgload_scene_free(scene);
+
+    scene = NULL;
+    CHECK(svgload_load_data((const uint8_t *)"not an svg", 10, &scene) == SVGLOAD_ERR_PARSE);
+    CHECK(scene == NULL);
+    CHECK(strlen(svgload_last_error_message()) > 0);
+    CHECK(svgload_load_data(NULL, 0, &scene) == SVGLOAD_ERR_NULL_ARGUMENT);
+    return 0;
+}
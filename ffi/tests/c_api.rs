@@ -0,0 +1,32 @@
+//! Builds `c_api.c` against `include/svgload.h` and the shared library of this crate, then runs
+//! it. Needs a C compiler: `cc`, or the one named by `CC`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[cfg(unix)]
+#[test]
+fn c_program_builds_against_the_header_and_runs() {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Integration tests run from target/<profile>/deps; the library is in target/<profile>.
+    let lib_dir = std::env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+    let exe = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("svgload_c_api");
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(&compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&exe)
+        .arg(manifest.join("tests/c_api.c"))
+        .arg("-I")
+        .arg(manifest.join("../include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lsvgload")
+        .status()
+        .unwrap_or_else(|e| panic!("cannot run the C compiler {}: {}", compiler, e));
+    assert!(status.success(), "c_api.c does not build against svgload.h");
+
+    let output = Command::new(&exe).output().unwrap();
+    assert!(output.status.success(), "c_api.c failed:\n{}", String::from_utf8_lossy(&output.stderr));
+}
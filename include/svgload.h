@@ -0,0 +1,50 @@
+#ifndef SVGLOAD_H
+#define SVGLOAD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define SVGLOAD_OK 0
+#define SVGLOAD_ERR_NULL_ARGUMENT 1
+#define SVGLOAD_ERR_PARSE 2
+#define SVGLOAD_ERR_OUT_OF_RANGE 3
+#define SVGLOAD_ERR_PANIC 4
+
+typedef struct SvgScene SvgScene;
+
+typedef struct SvgVertex {
+    float position[2];
+    uint32_t prim_id;
+} SvgVertex;
+
+/* Stop pointers borrow from the scene; stop_colors holds 4 * stop_count floats (RGBA). */
+typedef struct SvgPaint {
+    float color[4];
+    float gradient_start[2];
+    float gradient_end[2];
+    size_t stop_count;
+    const float *stop_positions;
+    const float *stop_colors;
+    float opacity; /* multiplied into the alpha of color or of the stops */
+} SvgPaint;
+
+int svgload_load_data(const uint8_t *data, size_t len, SvgScene **out);
+void svgload_scene_free(SvgScene *scene);
+
+size_t svgload_scene_primitive_count(const SvgScene *scene);
+int svgload_primitive_vertices(const SvgScene *scene, size_t index, const SvgVertex **vertices, size_t *len);
+int svgload_primitive_indices(const SvgScene *scene, size_t index, const uint32_t **indices, size_t *len);
+int svgload_primitive_paint(const SvgScene *scene, size_t index, SvgPaint *paint);
+
+/* Message of the last error on the calling thread, valid until the next failing call. */
+const char *svgload_last_error_message(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,114 @@
+use crate::path::{GpuVertex, PaintSpec, RenderablePath};
+
+/// Consecutive paths sharing one paint and opacity, merged so they can be drawn with a single
+/// call. Vertices and the gradient endpoints of `paint` are in mesh space, with the
+/// `local_transform` of each path applied, so paths with different transforms can share one.
+#[derive(Clone, Debug)]
+pub struct DrawBatch {
+    pub merged_vertices: Vec<GpuVertex>,
+    pub merged_indices: Vec<u32>,
+    pub paint: PaintSpec,
+    /// `RenderablePath::path_opacity` of every path in the batch.
+    pub opacity: f32,
+    /// `(start, end)` range in `merged_indices` of each path in the batch, in input order.
+    pub path_ranges: Vec<(usize, usize)>,
+}
+
+/// Groups `paths` greedily in draw order, starting a new batch whenever the paint or the
+/// opacity changes, so the painter's order of the scene is preserved.
+pub fn batch_paths(paths: &[RenderablePath]) -> Vec<DrawBatch> {
+    let mut batches: Vec<DrawBatch> = Vec::new();
+    for path in paths {
+        let path = path.flattened();
+        let paint = path.paint();
+        let batch = match batches.last_mut() {
+            Some(b) if b.paint == paint && b.opacity == path.path_opacity => b,
+            _ => {
+                batches.push(DrawBatch {
+                    merged_vertices: Vec::new(),
+                    merged_indices: Vec::new(),
+                    paint,
+                    opacity: path.path_opacity,
+                    path_ranges: Vec::new(),
+                });
+                batches.last_mut().unwrap()
+            }
+        };
+
+        let base = batch.merged_vertices.len() as u32;
+        let start = batch.merged_indices.len();
+        batch.merged_vertices.extend_from_slice(&path.vertices.vertices);
+        batch.merged_indices.extend(path.vertices.indices.iter().map(|i| i + base));
+        batch.path_ranges.push((start, batch.merged_indices.len()));
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::tessellation::VertexBuffers;
+
+    fn square(prim_id: u32, color: [f32; 4]) -> RenderablePath {
+        let mut mesh = VertexBuffers::new();
+        for position in [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            mesh.vertices.push(GpuVertex { position, prim_id });
+        }
+        mesh.indices.extend([0, 1, 2, 0, 2, 3]);
+        RenderablePath { bgcolor: color, ..RenderablePath::new((1, 1), mesh) }
+    }
+
+    #[test]
+    fn paths_of_one_color_share_a_batch() {
+        let paths: Vec<RenderablePath> = (0..10).map(|i| square(i, [1.0, 0.0, 0.0, 1.0])).collect();
+        let batches = batch_paths(&paths);
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.merged_vertices.len(), 40);
+        assert_eq!(batch.path_ranges.len(), 10);
+        assert_eq!(batch.path_ranges[9], (54, 60));
+        assert_eq!(&batch.merged_indices[54..60], [36, 37, 38, 36, 38, 39]);
+    }
+
+    #[test]
+    fn a_paint_change_starts_a_new_batch() {
+        let (red, blue) = ([1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]);
+        let paths = [square(0, red), square(1, red), square(2, blue), square(3, red)];
+        let sizes: Vec<usize> = batch_paths(&paths).iter().map(|b| b.path_ranges.len()).collect();
+        assert_eq!(sizes, [2, 1, 1]);
+    }
+
+    #[test]
+    fn local_transforms_are_applied_to_the_batches() {
+        use crate::svgload::{load_svg_data, LoadOptions};
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="8" y2="0">
+                <stop offset="0" stop-color="#f00"/><stop offset="1" stop-color="#00f"/>
+            </linearGradient>
+            <g transform="translate(16 8)"><rect width="8" height="8" fill="url(#g)"/></g>
+        </svg>"##;
+        let flat = batch_paths(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths());
+        let local = batch_paths(&load_svg_data(svg, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap().into_paths());
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        for (a, b) in flat[0].merged_vertices.iter().zip(&local[0].merged_vertices) {
+            assert!(close(a.position[0], b.position[0]) && close(a.position[1], b.position[1]));
+        }
+        assert_eq!(local[0].merged_vertices[0].position, [0.5, 0.75]);
+        match (&flat[0].paint, &local[0].paint) {
+            (PaintSpec::LinearGradient(a), PaintSpec::LinearGradient(b)) => {
+                assert!(close(a.start.0, b.start.0) && close(a.end.0, b.end.0) && close(a.end.1, b.end.1));
+                assert_eq!(b.end, (0.75, 0.75));
+            }
+            paints => panic!("expected gradients, got {:?}", paints),
+        }
+    }
+
+    #[test]
+    fn an_opacity_change_starts_a_new_batch() {
+        let red = [1.0, 0.0, 0.0, 1.0];
+        let paths = [square(0, red), square(1, red).with_opacity(0.5), square(2, red).with_opacity(0.5)];
+        let batches = batch_paths(&paths);
+        let sizes: Vec<(usize, f32)> = batches.iter().map(|b| (b.path_ranges.len(), b.opacity)).collect();
+        assert_eq!(sizes, [(1, 1.0), (2, 0.5)]);
+    }
+}
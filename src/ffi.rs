@@ -0,0 +1,288 @@
+//! C bindings for the SVG loader, see `include/svgload.h`.
+//!
+//! Every entry point catches panics so they never unwind into the caller; failures are
+//! reported through the returned status code and `svgload_last_error_message`.
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::path::{GpuVertex, RenderablePath};
+use crate::svgload::load_svg_from_bytes;
+
+pub const SVGLOAD_OK: c_int = 0;
+pub const SVGLOAD_ERR_NULL_ARGUMENT: c_int = 1;
+pub const SVGLOAD_ERR_PARSE: c_int = 2;
+pub const SVGLOAD_ERR_OUT_OF_RANGE: c_int = 3;
+pub const SVGLOAD_ERR_PANIC: c_int = 4;
+
+/// Opaque scene handle owned by the caller until `svgload_scene_free`.
+pub struct SvgScene {
+    primitives: Vec<RenderablePath>,
+}
+
+/// Paint of one primitive. Stop pointers borrow from the scene and are null when
+/// `stop_count` is 0; `stop_colors` holds `4 * stop_count` floats (RGBA per stop).
+#[repr(C)]
+pub struct SvgPaint {
+    pub color: [f32; 4],
+    pub gradient_start: [f32; 2],
+    pub gradient_end: [f32; 2],
+    pub stop_count: usize,
+    pub stop_positions: *const f32,
+    pub stop_colors: *const f32,
+    /// Path-level opacity, multiplied into the alpha of `color` or of the stops.
+    pub opacity: f32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(msg: &str) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+}
+
+fn guard<F: FnOnce() -> c_int>(f: F) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(code) => code,
+        Err(panic) => {
+            let msg = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panic in svg-load".to_string());
+            set_last_error(&msg);
+            SVGLOAD_ERR_PANIC
+        }
+    }
+}
+
+unsafe fn primitive<'a>(scene: *const SvgScene, index: usize) -> Result<&'a RenderablePath, c_int> {
+    if scene.is_null() {
+        set_last_error("scene is null");
+        return Err(SVGLOAD_ERR_NULL_ARGUMENT);
+    }
+    let scene = &*scene;
+    match scene.primitives.get(index) {
+        Some(p) => Ok(p),
+        None => {
+            set_last_error(&format!("primitive index {} out of range", index));
+            Err(SVGLOAD_ERR_OUT_OF_RANGE)
+        }
+    }
+}
+
+/// # Safety
+/// `data` must point to `len` readable bytes and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_load_data(data: *const u8, len: usize, out: *mut *mut SvgScene) -> c_int {
+    guard(|| {
+        if data.is_null() || out.is_null() {
+            set_last_error("data or out is null");
+            return SVGLOAD_ERR_NULL_ARGUMENT;
+        }
+        *out = ptr::null_mut();
+        let bytes = std::slice::from_raw_parts(data, len);
+        match load_svg_from_bytes(bytes) {
+            Ok(loaded) => {
+                *out = Box::into_raw(Box::new(SvgScene { primitives: loaded.into_paths() }));
+                SVGLOAD_OK
+            }
+            Err(e) => {
+                set_last_error(&e.to_string());
+                SVGLOAD_ERR_PARSE
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `scene` must be null or a pointer returned by `svgload_load_data` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_scene_free(scene: *mut SvgScene) {
+    if !scene.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(scene))));
+    }
+}
+
+/// # Safety
+/// `scene` must be null or a live scene handle.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_scene_primitive_count(scene: *const SvgScene) -> usize {
+    match scene.as_ref() {
+        Some(scene) => scene.primitives.len(),
+        None => 0,
+    }
+}
+
+/// # Safety
+/// `scene` must be a live scene handle, `vertices` and `len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_primitive_vertices(scene: *const SvgScene, index: usize,
+                                                    vertices: *mut *const GpuVertex, len: *mut usize) -> c_int {
+    guard(|| {
+        if vertices.is_null() || len.is_null() {
+            set_last_error("vertices or len is null");
+            return SVGLOAD_ERR_NULL_ARGUMENT;
+        }
+        match primitive(scene, index) {
+            Ok(p) => {
+                *vertices = p.vertices.vertices.as_ptr();
+                *len = p.vertices.vertices.len();
+                SVGLOAD_OK
+            }
+            Err(code) => code,
+        }
+    })
+}
+
+/// # Safety
+/// `scene` must be a live scene handle, `indices` and `len` valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_primitive_indices(scene: *const SvgScene, index: usize,
+                                                   indices: *mut *const u32, len: *mut usize) -> c_int {
+    guard(|| {
+        if indices.is_null() || len.is_null() {
+            set_last_error("indices or len is null");
+            return SVGLOAD_ERR_NULL_ARGUMENT;
+        }
+        match primitive(scene, index) {
+            Ok(p) => {
+                *indices = p.vertices.indices.as_ptr();
+                *len = p.vertices.indices.len();
+                SVGLOAD_OK
+            }
+            Err(code) => code,
+        }
+    })
+}
+
+/// # Safety
+/// `scene` must be a live scene handle and `paint` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn svgload_primitive_paint(scene: *const SvgScene, index: usize, paint: *mut SvgPaint) -> c_int {
+    guard(|| {
+        if paint.is_null() {
+            set_last_error("paint is null");
+            return SVGLOAD_ERR_NULL_ARGUMENT;
+        }
+        let p = match primitive(scene, index) {
+            Ok(p) => p,
+            Err(code) => return code,
+        };
+        let start = p.gradient_start.unwrap_or((0.0, 0.0));
+        let end = p.gradient_end.unwrap_or((0.0, 0.0));
+        let (stop_count, stop_positions, stop_colors) = match (&p.gradient_pos, &p.gradient_colors) {
+            (Some(pos), Some(colors)) if !pos.is_empty() => {
+                (pos.len().min(colors.len()), pos.as_ptr(), colors.as_ptr() as *const f32)
+            }
+            _ => (0, ptr::null(), ptr::null()),
+        };
+        *paint = SvgPaint {
+            color: p.bgcolor,
+            gradient_start: [start.0, start.1],
+            gradient_end: [end.0, end.1],
+            stop_count,
+            stop_positions,
+            stop_colors,
+            opacity: p.path_opacity,
+        };
+        SVGLOAD_OK
+    })
+}
+
+/// Message of the last error on the calling thread; valid until the next failing call.
+#[no_mangle]
+pub extern "C" fn svgload_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    const HEADER: &str = include_str!("../include/svgload.h");
+    const RED_SQUARE: &[u8] = br##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+        <rect width="10" height="10" fill="#ff0000"/></svg>"##;
+
+    #[test]
+    fn header_declares_every_entry_point() {
+        for decl in [
+            "int svgload_load_data(const uint8_t *data, size_t len, SvgScene **out);",
+            "void svgload_scene_free(SvgScene *scene);",
+            "size_t svgload_scene_primitive_count(const SvgScene *scene);",
+            "int svgload_primitive_vertices(const SvgScene *scene, size_t index, const SvgVertex **vertices, size_t *len);",
+            "int svgload_primitive_indices(const SvgScene *scene, size_t index, const uint32_t **indices, size_t *len);",
+            "int svgload_primitive_paint(const SvgScene *scene, size_t index, SvgPaint *paint);",
+            "const char *svgload_last_error_message(void);",
+        ] {
+            assert!(HEADER.contains(decl), "missing from svgload.h: {}", decl);
+        }
+        for (name, value) in [
+            ("SVGLOAD_OK", SVGLOAD_OK),
+            ("SVGLOAD_ERR_NULL_ARGUMENT", SVGLOAD_ERR_NULL_ARGUMENT),
+            ("SVGLOAD_ERR_PARSE", SVGLOAD_ERR_PARSE),
+            ("SVGLOAD_ERR_OUT_OF_RANGE", SVGLOAD_ERR_OUT_OF_RANGE),
+            ("SVGLOAD_ERR_PANIC", SVGLOAD_ERR_PANIC),
+        ] {
+            assert!(HEADER.contains(&format!("#define {} {}\n", name, value)), "{} differs from svgload.h", name);
+        }
+        // SvgVertex in the header mirrors GpuVertex.
+        assert_eq!(std::mem::size_of::<GpuVertex>(), 12);
+    }
+
+    #[test]
+    fn loads_and_reads_back_a_scene() {
+        unsafe {
+            let mut scene = ptr::null_mut();
+            assert_eq!(svgload_load_data(RED_SQUARE.as_ptr(), RED_SQUARE.len(), &mut scene), SVGLOAD_OK);
+            assert_eq!(svgload_scene_primitive_count(scene), 1);
+
+            let (mut vertices, mut vertex_count) = (ptr::null(), 0);
+            assert_eq!(svgload_primitive_vertices(scene, 0, &mut vertices, &mut vertex_count), SVGLOAD_OK);
+            let (mut indices, mut index_count) = (ptr::null(), 0);
+            assert_eq!(svgload_primitive_indices(scene, 0, &mut indices, &mut index_count), SVGLOAD_OK);
+            assert!(vertex_count >= 4 && index_count >= 6 && index_count % 3 == 0);
+            let indices = std::slice::from_raw_parts(indices, index_count);
+            assert!(indices.iter().all(|&i| (i as usize) < vertex_count));
+
+            let mut paint = std::mem::zeroed::<SvgPaint>();
+            assert_eq!(svgload_primitive_paint(scene, 0, &mut paint), SVGLOAD_OK);
+            assert_eq!(paint.color, [1.0, 0.0, 0.0, 1.0]);
+            assert_eq!(paint.stop_count, 0);
+            assert!(paint.stop_positions.is_null());
+
+            assert_eq!(svgload_primitive_paint(scene, 1, &mut paint), SVGLOAD_ERR_OUT_OF_RANGE);
+            svgload_scene_free(scene);
+        }
+    }
+
+    #[test]
+    fn reports_errors_through_codes_and_message() {
+        unsafe {
+            let mut scene = ptr::null_mut();
+            let junk = b"not an svg";
+            assert_eq!(svgload_load_data(junk.as_ptr(), junk.len(), &mut scene), SVGLOAD_ERR_PARSE);
+            assert!(scene.is_null());
+            assert!(!CStr::from_ptr(svgload_last_error_message()).to_bytes().is_empty());
+
+            assert_eq!(svgload_load_data(ptr::null(), 0, &mut scene), SVGLOAD_ERR_NULL_ARGUMENT);
+            let (mut vertices, mut len) = (ptr::null(), 0);
+            assert_eq!(svgload_primitive_vertices(ptr::null(), 0, &mut vertices, &mut len), SVGLOAD_ERR_NULL_ARGUMENT);
+            assert_eq!(svgload_scene_primitive_count(ptr::null()), 0);
+            svgload_scene_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_become_error_codes() {
+        assert_eq!(guard(|| panic!("boom")), SVGLOAD_ERR_PANIC);
+        let msg = unsafe { CStr::from_ptr(svgload_last_error_message()) };
+        assert_eq!(msg.to_str().unwrap(), "boom");
+    }
+}
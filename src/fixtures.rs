@@ -0,0 +1,72 @@
+//! Paths of the files in `tests/fixtures`, for unit tests.
+
+pub const DEJAVU_SANS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/DejaVuSans.ttf");
+
+/// Path of `name` relative to `tests/fixtures`.
+pub fn path(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+pub fn read(name: &str) -> Vec<u8> {
+    std::fs::read(path(name)).unwrap_or_else(|e| panic!("cannot read fixture {}: {}", name, e))
+}
+
+fn be32(bytes: &[u8]) -> usize {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
+
+/// Copy of the single-face sfnt `font` with `tables` added, replacing tables of the same tag,
+/// for building fonts with features no checked-in fixture has.
+pub fn with_tables(font: &[u8], tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let count = u16::from_be_bytes([font[4], font[5]]) as usize;
+    let mut all: Vec<([u8; 4], &[u8])> = (0..count)
+        .map(|i| {
+            let record = &font[12 + 16 * i..];
+            let (offset, len) = (be32(&record[8..]), be32(&record[12..]));
+            ([record[0], record[1], record[2], record[3]], &font[offset..offset + len])
+        })
+        .filter(|(tag, _)| !tables.iter().any(|(t, _)| *t == tag))
+        .collect();
+    all.extend(tables.iter().map(|&(tag, data)| (*tag, data)));
+    all.sort_by_key(|&(tag, _)| tag);
+
+    let mut out = font[..4].to_vec();
+    out.extend((all.len() as u16).to_be_bytes());
+    out.extend([0; 6]);
+    let mut offset = 12 + 16 * all.len();
+    for (tag, data) in &all {
+        out.extend(tag);
+        out.extend([0; 4]);
+        out.extend((offset as u32).to_be_bytes());
+        out.extend((data.len() as u32).to_be_bytes());
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in &all {
+        out.extend(*data);
+        out.resize((out.len() + 3) & !3, 0);
+    }
+    out
+}
+
+/// DejaVu Sans with the TrueType outline of `ch` truncated so that it cannot be parsed.
+pub fn corrupted_dejavu(ch: char) -> Vec<u8> {
+    let mut data = read("DejaVuSans.ttf");
+    let face = ttf_parser::Face::from_slice(&data, 0).unwrap();
+    let id = face.glyph_index(ch).unwrap().0 as usize;
+    let long = face.tables().head.index_to_location_format == ttf_parser::head::IndexToLocationFormat::Long;
+    let offset = |table: &[u8; 4]| {
+        let count = u16::from_be_bytes([data[4], data[5]]) as usize;
+        let record = (0..count).map(|i| 12 + 16 * i).find(|&r| &data[r..r + 4] == table).unwrap();
+        be32(&data[record + 8..])
+    };
+    let (loca, glyf) = (offset(b"loca"), offset(b"glyf"));
+    let start = if long {
+        be32(&data[loca + 4 * id..])
+    } else {
+        u16::from_be_bytes([data[loca + 2 * id], data[loca + 2 * id + 1]]) as usize * 2
+    };
+    // One contour whose last point index runs far past the glyph's data.
+    data[glyf + start..glyf + start + 2].copy_from_slice(&1u16.to_be_bytes());
+    data[glyf + start + 10..glyf + start + 12].copy_from_slice(&0xFFF0u16.to_be_bytes());
+    data
+}
@@ -1,20 +1,621 @@
 use std::collections::HashMap;
-use lyon::tessellation::VertexBuffers;
-use crate::path::GpuVertex;
+use std::fmt::Write;
+use std::sync::OnceLock;
+use lyon::path::PathEvent;
+use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, VertexBuffers};
+use usvg::Transform;
+use crate::path::{GpuVertex, RenderablePath};
+use crate::svgload::VertexCtor;
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Font {
+    /// File name of the font, with `#index` for later faces of a collection; the family name
+    /// for fonts loaded from bytes.
     pub name: String,
+    /// Family name from the name table (name ID 1), e.g. "Open Sans"; the file stem if the
+    /// font has none.
+    #[serde(default)]
+    pub family_name: String,
+    /// Subfamily from the name table (name ID 2), e.g. "Bold Italic".
+    #[serde(default)]
+    pub style_name: String,
+    /// OS/2 weight class, 400 for regular and 700 for bold.
+    #[serde(default)]
+    pub weight: u16,
+    /// Italic or oblique according to OS/2.
+    #[serde(default)]
+    pub italic: bool,
+    /// Fixed pitch according to the post table, the OS/2 PANOSE or the face's advances, and
+    /// confirmed by the loaded glyphs; see `Font::is_monospace`.
+    #[serde(default)]
+    pub monospace: bool,
     pub ascender: f32,
     pub descender: f32,
     pub line_gap: f32,
+    /// Height of lowercase letters, from OS/2 or else the 'x' glyph.
+    #[serde(default)]
+    pub x_height: f32,
+    /// Height of uppercase letters, from OS/2 or else the 'H' glyph.
+    #[serde(default)]
+    pub cap_height: f32,
+    /// Top of the underline relative to the baseline; negative below it.
+    #[serde(default)]
+    pub underline_position: f32,
+    #[serde(default)]
+    pub underline_thickness: f32,
+    /// Top of the strikeout line relative to the baseline.
+    #[serde(default)]
+    pub strikeout_position: f32,
+    #[serde(default)]
+    pub strikeout_thickness: f32,
+    /// Vertical layout metrics from the vhea table; `None` if the font has none.
+    #[serde(default)]
+    pub vertical_ascender: Option<f32>,
+    #[serde(default)]
+    pub vertical_descender: Option<f32>,
+    #[serde(default)]
+    pub vertical_line_gap: Option<f32>,
+    /// `FontOptions::em_size` the glyphs were built with.
+    #[serde(default)]
+    pub em_size: f32,
+    /// Flattening tolerance the glyphs were built with as a fraction of the em, including the
+    /// default of `FontOptions::tolerance: None`; part of any mesh cache key. `None` only for
+    /// fonts serialized before it was recorded.
+    #[serde(default)]
+    pub tolerance: Option<f32>,
+    /// Hash of the font data, the requested codepoints and the `FontOptions` the glyphs were
+    /// loaded from, to tell a cached copy from a fresh load; see `Font::is_stale`. 0 for fonts
+    /// not loaded from font data.
+    #[serde(default)]
+    pub source_hash: u64,
     pub glyph_map: HashMap<u32, Glyph>,
+    /// Advance adjustment of codepoint pairs `(left, right)` in em units; pairs without
+    /// kerning are absent.
+    #[serde(default, with = "pair_list")]
+    pub kerning: HashMap<(u32, u32), f32>,
+    /// Position of a combining mark's origin relative to the origin of the base character it
+    /// attaches to, for codepoint pairs `(base, mark)` covered by the GPOS `mark` feature;
+    /// in em units.
+    #[serde(default, with = "pair_list")]
+    pub mark_anchors: HashMap<(u32, u32), (f32, f32)>,
+    /// Glyphs keyed by glyph id, filled on demand for shaped text (see `Font::add_glyph_ids`).
+    #[serde(default)]
+    pub glyph_id_map: HashMap<u16, Glyph>,
+    /// Ligatures between loaded characters, filled when `FontOptions::ligatures` is set.
+    #[serde(default)]
+    pub ligatures: Vec<Ligature>,
+    /// Requested codepoints the font has no single glyph for but draws from several, e.g. "é"
+    /// as "e" plus a combining acute. Codepoints with a glyph in `glyph_map` are never here.
+    #[serde(default)]
+    pub clusters: HashMap<u32, GlyphCluster>,
+    /// Tags of the OpenType features of the GSUB and GPOS tables, e.g. `"liga"` or `"kern"`,
+    /// sorted; see `Font::supports_opentype_feature`.
+    #[serde(default)]
+    pub opentype_features: Vec<String>,
+    /// Glyph 0 (.notdef, usually a "tofu" box) with its real advance, to draw characters the
+    /// font lacks. A box is synthesized when the font's .notdef is empty.
+    #[serde(default)]
+    pub notdef: Glyph,
+    /// Requested codepoints the font has no glyph for, in request order.
+    #[serde(default)]
+    pub unresolved: Vec<u32>,
+    #[serde(default)]
+    pub warnings: Vec<FontWarning>,
+}
+
+/// Fonts tried in order for every character, e.g. UI font, then emoji, then CJK.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FontChain {
+    pub fonts: Vec<Font>,
+}
+
+impl FontChain {
+    pub fn with_fallback(mut self, fallback: Font) -> FontChain {
+        self.fonts.push(fallback);
+        self
+    }
+
+    /// Glyph of `ch` from the first font that has it, or else the last font's `notdef`. An
+    /// empty chain has an empty glyph for every character.
+    pub fn glyph_for_char(&self, ch: char) -> &Glyph {
+        self.fonts.iter().find_map(|f| f.glyph(ch)).unwrap_or_else(|| self.notdef())
+    }
+
+    /// The last font's `notdef`, drawn for characters no font of the chain has.
+    pub fn notdef(&self) -> &Glyph {
+        static EMPTY: OnceLock<Glyph> = OnceLock::new();
+        self.fonts.last().map_or_else(|| EMPTY.get_or_init(Glyph::default), |f| &f.notdef)
+    }
+
+    /// Like [`Font::glyph_run`] over the chain: each run of characters the same font is picked
+    /// for is laid out by that font, so kerning and ligatures apply within it. A combining mark
+    /// stays with the font of its base when that font has it. Characters no font has get
+    /// [`FontChain::notdef`].
+    pub fn glyph_run(&self, text: &str, ligatures: bool) -> Vec<RunGlyph<'_>> {
+        let font_of = |ch: char| self.fonts.iter().position(|f| f.glyph(ch).is_some());
+        let mut segments: Vec<(usize, usize, Option<usize>)> = Vec::new();
+        for (i, ch) in text.char_indices() {
+            let end = i + ch.len_utf8();
+            match segments.last_mut() {
+                Some((_, seg_end, Some(font)))
+                    if unicode_normalization::char::is_combining_mark(ch) && self.fonts[*font].glyph(ch).is_some() =>
+                {
+                    *seg_end = end;
+                }
+                Some((_, seg_end, font)) if *font == font_of(ch) => *seg_end = end,
+                _ => segments.push((i, end, font_of(ch))),
+            }
+        }
+
+        let mut run = Vec::new();
+        let mut pen = 0.0;
+        for (start, end, font) in segments {
+            let glyphs = match font {
+                Some(font) => self.fonts[font].glyph_run(&text[start..end], ligatures),
+                None => text[start..end].char_indices().scan(0.0, |x, (cluster, _)| {
+                    let glyph = self.notdef();
+                    let placed = RunGlyph { glyph, x: *x, y: 0.0, cluster, attached: false };
+                    *x += glyph.advance;
+                    Some(placed)
+                }).collect(),
+            };
+            let width = glyphs.iter().rev().find(|g| !g.attached).map_or(0.0, |g| g.x + g.glyph.advance);
+            run.extend(glyphs.into_iter().map(|g| RunGlyph { x: g.x + pen, cluster: g.cluster + start, ..g }));
+            pen += width;
+        }
+        run
+    }
+
+    /// Metrics come from the primary font.
+    pub fn primary(&self) -> Option<&Font> {
+        self.fonts.first()
+    }
+}
+
+/// Face-level information read without tessellating any glyph. Lengths are in em units
+/// like the fields of [`Font`]; `units_per_em` is the raw design grid size.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FontMetrics {
+    pub name: String,
+    pub style_name: String,
+    pub ascender: f32,
+    pub descender: f32,
+    pub line_gap: f32,
+    pub cap_height: Option<f32>,
+    pub x_height: Option<f32>,
+    pub units_per_em: u16,
+}
+
+/// Non-fatal problem found while loading a font.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum FontWarning {
+    /// The glyph has no horizontal metrics; its advance was set to 0.
+    MissingAdvance { codepoint: u32 },
+    /// Same as `MissingAdvance`, for a glyph loaded by id.
+    MissingGlyphAdvance { glyph_id: u16 },
+    /// The glyph's outline data could not be parsed or tessellated; it was loaded with an
+    /// empty mesh. With `MissingGlyph::Error` the load fails with `OutlineFailed` instead.
+    InvalidOutline { codepoint: u32 },
+    /// Same as `InvalidOutline`, for a glyph loaded by id.
+    InvalidGlyphOutline { glyph_id: u16 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Glyph {
     pub advance: f32,
+    /// `(x_min, y_min, x_max, y_max)` of the outline in the same space as `outline`; always
+    /// `x_min <= x_max` and `y_min <= y_max`, all zero for glyphs without an outline.
     pub bbox: (f32, f32, f32, f32),
+    /// Filled outline with the glyph origin at (0, 0): y points up and the baseline is y = 0.
+    /// Vertex `prim_id`s are the glyph id plus `FontOptions::prim_id_base`, so a table indexed
+    /// by them can hold per-glyph data such as a paint.
     pub outline: VertexBuffers<GpuVertex, u32>,
-}
\ No newline at end of file
+    /// Outline in the same space as `outline`, kept when `FontOptions::retain_path_events` is set.
+    #[serde(default)]
+    pub path_events: Option<Vec<PathEvent>>,
+    /// Index of the face in the `FaceChain` this glyph was loaded from; 0 for single-face loads.
+    #[serde(default)]
+    pub face: usize,
+    /// Colored layers of glyphs from the OpenType `SVG ` table, in the same space as `outline`
+    /// and in paint order; empty for plain outline glyphs.
+    #[serde(default)]
+    pub svg_layers: Vec<RenderablePath>,
+    /// Embedded bitmap, only loaded for glyphs that have neither an outline nor SVG layers.
+    #[serde(default)]
+    pub image: Option<GlyphImage>,
+    /// The font defines no outline for this glyph, e.g. for whitespace. Bitmap and SVG glyphs
+    /// may still have an `image` or `svg_layers`.
+    #[serde(default)]
+    pub is_empty: bool,
+    /// Advance in vertical layout, from the vmtx table.
+    #[serde(default)]
+    pub v_advance: Option<f32>,
+    /// Y of the vertical origin (top center of the glyph's em box) relative to the baseline,
+    /// from the VORG table or else the vmtx top side bearing.
+    #[serde(default)]
+    pub v_origin_y: Option<f32>,
+    /// Left side bearing from the hmtx table, in output units: from the origin to where the
+    /// outline was designed to start.
+    #[serde(default)]
+    pub lsb: f32,
+    /// Right side bearing, `advance - lsb - (bbox.2 - bbox.0)`; `advance - lsb` for glyphs
+    /// without an outline.
+    #[serde(default)]
+    pub rsb: f32,
+    /// Outline stroked with `FontOptions::outline_stroke`, in the same space as `outline`;
+    /// `None` when no stroke was requested or the glyph has no outline.
+    #[serde(default)]
+    pub stroke_outline: Option<VertexBuffers<GpuVertex, u32>>,
+    /// `(x_min, y_min, x_max, y_max)` of `stroke_outline`.
+    #[serde(default)]
+    pub stroke_bbox: Option<(f32, f32, f32, f32)>,
+}
+
+/// Embedded bitmap of a glyph (CBDT/CBLC or sbix table). Metrics are in pixels of the strike.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GlyphImage {
+    /// PNG encoded image.
+    pub png: Vec<u8>,
+    pub width: u16,
+    pub height: u16,
+    /// Offset of the image's bottom-left corner from the glyph origin.
+    pub x: i16,
+    pub y: i16,
+    /// Size of the strike the image was taken from.
+    pub pixels_per_em: u16,
+}
+
+impl Glyph {
+    /// Tessellates the retained outline again with `tolerance` (in output units), e.g. for a
+    /// different level of detail. Returns `None` if the outline was not retained.
+    pub fn retessellate(&self, tolerance: f32) -> Option<VertexBuffers<GpuVertex, u32>> {
+        let events = self.path_events.as_ref()?;
+        let prim_id = self.outline.vertices.first().map(|v| v.prim_id).unwrap_or(0);
+        let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+        FillTessellator::new()
+            .tessellate(
+                events.iter().copied(),
+                &FillOptions::tolerance(tolerance),
+                &mut BuffersBuilder::new(&mut mesh, VertexCtor { prim_id, transform: Transform::default() }),
+            )
+            .expect("Error during tesselation!");
+        Some(mesh)
+    }
+
+    /// Copy moved up by `shift` em units (down if negative), e.g. for superscripts and
+    /// subscripts. The advance is unchanged.
+    pub fn with_baseline_shift(&self, shift: f32) -> Glyph {
+        let mut glyph = self.clone();
+        for v in &mut glyph.outline.vertices {
+            *v = v.offset_position(0.0, shift);
+        }
+        glyph.bbox.1 += shift;
+        glyph.bbox.3 += shift;
+        if let Some(stroke) = &mut glyph.stroke_outline {
+            for v in &mut stroke.vertices {
+                *v = v.offset_position(0.0, shift);
+            }
+        }
+        if let Some(bbox) = &mut glyph.stroke_bbox {
+            bbox.1 += shift;
+            bbox.3 += shift;
+        }
+        if let Some(events) = &mut glyph.path_events {
+            let t = lyon::math::Transform::translation(0.0, shift);
+            for e in events.iter_mut() {
+                *e = e.transformed(&t);
+            }
+        }
+        glyph
+    }
+}
+
+impl Font {
+    /// Family name for display, see `Font::family_name`.
+    pub fn family_name(&self) -> &str {
+        &self.family_name
+    }
+
+    /// Whether the GSUB or GPOS table of the face defines the feature `tag`, a 4-letter tag
+    /// like `"liga"`, `"kern"` or `"smcp"`, for any script. `false` for fonts without those
+    /// tables.
+    pub fn supports_opentype_feature(&self, tag: &str) -> bool {
+        self.opentype_features.binary_search_by(|t| t.as_str().cmp(tag)).is_ok()
+    }
+
+    /// Whether the face is fixed pitch, before any `FontOptions::force_advance`.
+    pub fn is_monospace(&self) -> bool {
+        self.monospace
+    }
+
+    pub fn glyph(&self, ch: char) -> Option<&Glyph> {
+        self.glyph_map.get(&u32::from(ch))
+    }
+
+    pub fn glyph_by_id(&self, glyph_id: u16) -> Option<&Glyph> {
+        self.glyph_id_map.get(&glyph_id)
+    }
+
+    /// Glyph of `ch`, or `notdef` if the font has none.
+    pub fn glyph_or_notdef(&self, ch: char) -> &Glyph {
+        self.glyph(ch).unwrap_or(&self.notdef)
+    }
+
+    pub fn cluster(&self, ch: char) -> Option<&GlyphCluster> {
+        self.clusters.get(&u32::from(ch))
+    }
+
+    /// Glyphs drawing `ch` with their offsets from the pen position: its glyph at `(0, 0)`, or
+    /// the parts of its cluster. Empty if the font has neither.
+    pub fn glyph_parts(&self, ch: char) -> Vec<(&Glyph, (f32, f32))> {
+        if let Some(glyph) = self.glyph(ch) {
+            return vec![(glyph, (0.0, 0.0))];
+        }
+        self.cluster(ch).map_or_else(Vec::new, |cluster| {
+            cluster.parts.iter()
+                .filter_map(|part| Some((self.glyph_by_id(part.glyph_id)?, part.offset)))
+                .collect()
+        })
+    }
+
+    /// Adjustment to add to the advance of `left` when it is followed by `right`; negative
+    /// values move the glyphs closer, e.g. for "AV".
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        self.kerning.get(&(u32::from(left), u32::from(right))).copied().unwrap_or(0.0)
+    }
+
+    /// Offset of the origin of `mark` from the origin of `base` when the mark is attached to
+    /// it, see `Font::mark_anchors`.
+    pub fn mark_offset(&self, base: char, mark: char) -> Option<(f32, f32)> {
+        self.mark_anchors.get(&(u32::from(base), u32::from(mark))).copied()
+    }
+
+    /// Advance of `ch` in vertical layout; `None` if the character is missing or the font has
+    /// no vertical metrics.
+    pub fn vertical_advance(&self, ch: char) -> Option<f32> {
+        self.glyph(ch)?.v_advance
+    }
+
+    /// Starts a fallback chain: characters missing from `self` are looked up in `fallback`.
+    pub fn with_fallback(self, fallback: Font) -> FontChain {
+        FontChain {
+            fonts: vec![self, fallback],
+        }
+    }
+
+    /// Lays `text` out on a baseline starting at `(x, y)` and returns a standalone SVG document
+    /// with one `<path>` per glyph. Glyph meshes are y-up, so they are flipped into SVG's y-down
+    /// space; each triangle of the mesh becomes a closed subpath. Characters missing from the
+    /// font are skipped.
+    pub fn to_svg_glyphs(&self, text: &str, x: f32, y: f32) -> String {
+        let mut paths = String::new();
+        let mut end = x;
+        for placed in self.glyph_run(text, true) {
+            let glyph = placed.glyph;
+            let pen = x + placed.x;
+            let baseline = y - placed.y;
+            let verts = &glyph.outline.vertices;
+            if !glyph.outline.indices.is_empty() {
+                let mut d = String::new();
+                for tri in glyph.outline.indices.chunks_exact(3) {
+                    for (i, idx) in tri.iter().enumerate() {
+                        let p = verts[*idx as usize].position;
+                        let cmd = if i == 0 { 'M' } else { 'L' };
+                        let _ = write!(d, "{}{} {} ", cmd, pen + p[0], baseline - p[1]);
+                    }
+                    d.push_str("Z ");
+                }
+                let _ = writeln!(paths, "  <path d=\"{}\"/>", d.trim_end());
+            }
+            end = pen + glyph.advance;
+        }
+
+        let top = y - self.ascender;
+        let height = self.ascender - self.descender;
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n{}</svg>\n",
+            x, top, (end - x).max(0.0), height.max(0.0), paths
+        )
+    }
+
+    /// Glyphs of `text` on a single line starting at x = 0, with kerning applied. With
+    /// `ligatures`, runs of characters listed in `Font::ligatures` are replaced by the
+    /// ligature glyph. Combining marks with an entry in `Font::mark_anchors` are attached to
+    /// the preceding base without advancing the pen. Characters missing from the font are
+    /// skipped.
+    pub fn glyph_run(&self, text: &str, ligatures: bool) -> Vec<RunGlyph<'_>> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let mut run = Vec::new();
+        let mut pen = 0.0;
+        let mut prev = None;
+        let mut base: Option<(char, f32)> = None;
+        let mut i = 0;
+        while i < chars.len() {
+            let (cluster, ch) = chars[i];
+            let attached = base.and_then(|(b, x)| Some((self.glyph(ch)?, self.mark_offset(b, ch)?, x)));
+            if let Some((glyph, (dx, dy), x)) = attached {
+                run.push(RunGlyph { glyph, x: x + dx, y: dy, cluster, attached: true });
+                i += 1;
+                continue;
+            }
+            if let Some(p) = prev {
+                pen += self.kerning(p, ch);
+            }
+            let ligature = if ligatures { self.ligature_at(&chars[i..]) } else { None };
+            let (glyph, len) = match ligature {
+                Some((glyph, len)) => (glyph, len),
+                None => match self.glyph(ch) {
+                    Some(glyph) => (glyph, 1),
+                    None => {
+                        i += 1;
+                        continue;
+                    }
+                },
+            };
+            run.push(RunGlyph { glyph, x: pen, y: 0.0, cluster, attached: false });
+            base = (len == 1).then_some((ch, pen));
+            pen += glyph.advance;
+            prev = Some(chars[i + len - 1].1);
+            i += len;
+        }
+        run
+    }
+
+    /// Longest ligature whose components start `chars`, with its component count.
+    fn ligature_at(&self, chars: &[(usize, char)]) -> Option<(&Glyph, usize)> {
+        self.ligatures.iter()
+            .filter(|l| l.components.len() <= chars.len()
+                && l.components.iter().zip(chars).all(|(&cp, &(_, ch))| cp == u32::from(ch)))
+            .max_by_key(|l| l.components.len())
+            .and_then(|l| Some((self.glyph_by_id(l.glyph_id)?, l.components.len())))
+    }
+}
+
+/// A glyph placed by [`Font::glyph_run`].
+#[derive(Clone, Copy, Debug)]
+pub struct RunGlyph<'a> {
+    pub glyph: &'a Glyph,
+    /// Pen position of the glyph origin, in em units.
+    pub x: f32,
+    /// Height of the glyph origin above the baseline; non-zero only for attached marks.
+    pub y: f32,
+    /// Byte offset in the text of the first character the glyph stands for.
+    pub cluster: usize,
+    /// A combining mark placed on the preceding glyph, which did not advance the pen.
+    pub attached: bool,
+}
+
+/// Glyphs drawn together for one codepoint, see `Font::clusters`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GlyphCluster {
+    /// Parts in drawing order, the base glyph first.
+    pub parts: Vec<ClusterPart>,
+    /// Advance of the whole cluster.
+    pub advance: f32,
+}
+
+impl GlyphCluster {
+    /// The glyph id when the cluster consists of one glyph.
+    pub fn single(&self) -> Option<u16> {
+        match self.parts.as_slice() {
+            [part] => Some(part.glyph_id),
+            _ => None,
+        }
+    }
+}
+
+/// One glyph of a [`GlyphCluster`], drawn `offset` from the cluster origin.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClusterPart {
+    /// Key of the glyph in `Font::glyph_id_map`.
+    pub glyph_id: u16,
+    pub offset: (f32, f32),
+}
+
+/// A ligature substitution from the font's GSUB `liga` feature.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Ligature {
+    /// Codepoints replaced by the ligature, in text order.
+    pub components: Vec<u32>,
+    /// Key of the ligature glyph in `Font::glyph_id_map`.
+    pub glyph_id: u16,
+}
+
+/// Serializes a pair-keyed map as a list of `(left, right, value)` so formats with string-only
+/// map keys, like JSON, can store it.
+mod pair_list {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, V: Serialize + Copy>(map: &HashMap<(u32, u32), V>, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut list: Vec<(u32, u32, V)> = map.iter().map(|(&(l, r), &v)| (l, r, v)).collect();
+        list.sort_by_key(|&(l, r, _)| (l, r));
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(deserializer: D) -> Result<HashMap<(u32, u32), V>, D::Error> {
+        let list = Vec::<(u32, u32, V)>::deserialize(deserializer)?;
+        Ok(list.into_iter().map(|(l, r, v)| ((l, r), v)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DEJAVU_SANS;
+    use crate::ttfload::{load_font, load_font_with_options, FontOptions};
+
+    #[test]
+    fn fonts_serialized_without_load_reports_still_deserialize() {
+        let font = load_font(DEJAVU_SANS, "a").unwrap();
+        let mut json = serde_json::to_value(&font).unwrap();
+        for field in ["kerning", "glyph_id_map", "ligatures", "unresolved", "warnings"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let font: Font = serde_json::from_value(json).unwrap();
+        assert!(font.kerning.is_empty() && font.ligatures.is_empty());
+        assert!(font.unresolved.is_empty() && font.warnings.is_empty());
+    }
+
+    #[test]
+    fn chain_takes_each_glyph_from_the_first_font_that_has_it() {
+        let primary = load_font(DEJAVU_SANS, "ab").unwrap();
+        let options = FontOptions { em_size: 2.0 * primary.em_size, ..FontOptions::default() };
+        let fallback = load_font_with_options(DEJAVU_SANS, "bc", &options).unwrap();
+        let chain = primary.with_fallback(fallback);
+        let (primary, fallback) = (&chain.fonts[0], &chain.fonts[1]);
+
+        assert_eq!(chain.glyph_for_char('b').advance, primary.glyph('b').unwrap().advance);
+        assert_eq!(chain.glyph_for_char('c').advance, fallback.glyph('c').unwrap().advance);
+        assert!(std::ptr::eq(chain.glyph_for_char('z'), &fallback.notdef));
+        assert_eq!(FontChain::default().glyph_for_char('a').advance, 0.0);
+    }
+
+    #[test]
+    fn chain_glyph_run_advances_across_fonts() {
+        let primary = load_font(DEJAVU_SANS, "ab").unwrap();
+        let options = FontOptions { em_size: 2.0 * primary.em_size, ..FontOptions::default() };
+        let chain = primary.with_fallback(load_font_with_options(DEJAVU_SANS, "c", &options).unwrap());
+        let run = chain.glyph_run("abcz", true);
+
+        let clusters: Vec<usize> = run.iter().map(|g| g.cluster).collect();
+        assert_eq!(clusters, [0, 1, 2, 3]);
+        let (a, b, c) = (chain.glyph_for_char('a'), chain.glyph_for_char('b'), chain.glyph_for_char('c'));
+        let kern = chain.fonts[0].kerning('a', 'b');
+        assert!((run[2].x - (a.advance + kern + b.advance)).abs() < 1e-5);
+        assert!((run[3].x - (run[2].x + c.advance)).abs() < 1e-5);
+        assert!(std::ptr::eq(run[3].glyph, chain.notdef()));
+    }
+
+    #[test]
+    fn glyphs_serialized_without_later_fields_still_deserialize() {
+        let options = FontOptions { retain_path_events: true, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "a", &options).unwrap();
+        let mut json = serde_json::to_value(font.glyph('a').unwrap()).unwrap();
+        for field in ["path_events", "face"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let glyph: Glyph = serde_json::from_value(json).unwrap();
+        assert!(glyph.path_events.is_none());
+        assert_eq!(glyph.face, 0);
+        assert!(!glyph.outline.indices.is_empty());
+    }
+
+    #[test]
+    fn fi_is_drawn_as_a_ligature_unless_disabled() {
+        let options = FontOptions { ligatures: true, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "fin", &options).unwrap();
+        let fi = font.ligatures.iter().find(|l| l.components == [u32::from('f'), u32::from('i')]).unwrap();
+        let ligature = font.glyph_by_id(fi.glyph_id).unwrap();
+        assert!(!ligature.outline.indices.is_empty());
+
+        let run = font.glyph_run("fin", true);
+        assert_eq!(run.len(), 2);
+        assert!(std::ptr::eq(run[0].glyph, ligature));
+        assert_eq!(run[1].x, ligature.advance + font.kerning('i', 'n'));
+        assert_eq!(run[1].cluster, 2);
+        assert_eq!(font.glyph_run("fin", false).len(), 3);
+    }
+}
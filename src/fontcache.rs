@@ -0,0 +1,647 @@
+//! Binary cache of a tessellated [`Font`], so large fonts can be tessellated once (e.g. in CI)
+//! and shipped. All numbers are little endian.
+//!
+//! Layout: magic, format version, em size, tolerance and source hash, names, weight and style, metrics,
+//! kerning, mark anchors, ligatures, clusters, OpenType feature tags, unresolved codepoints, load warnings, the .notdef glyph,
+//! then the glyphs by codepoint and by glyph id with their fill and stroke meshes, bitmaps, SVG layers and retained path events.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use lyon::math::Point;
+use lyon::path::PathEvent;
+use lyon::tessellation::VertexBuffers;
+
+use crate::font::{ClusterPart, Font, FontWarning, Glyph, GlyphCluster, GlyphImage, Ligature};
+use crate::path::{GpuVertex, RenderablePath};
+
+const MAGIC: &[u8; 8] = b"SVGLFONT";
+/// Bumped on every layout change; files of other versions are rejected.
+pub const FONT_CACHE_VERSION: u32 = 12;
+
+impl Font {
+    /// Writes the font to `path` in the cache format described in [`crate::fontcache`].
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(MAGIC)?;
+        write_u32(&mut w, FONT_CACHE_VERSION)?;
+        write_f32(&mut w, self.em_size)?;
+        write_opt_f32(&mut w, self.tolerance)?;
+        w.write_all(&self.source_hash.to_le_bytes())?;
+        write_bytes(&mut w, self.name.as_bytes())?;
+        write_bytes(&mut w, self.family_name.as_bytes())?;
+        write_bytes(&mut w, self.style_name.as_bytes())?;
+        w.write_all(&self.weight.to_le_bytes())?;
+        w.write_all(&[self.italic as u8, self.monospace as u8])?;
+        for v in [self.ascender, self.descender, self.line_gap, self.x_height, self.cap_height,
+                  self.underline_position, self.underline_thickness, self.strikeout_position, self.strikeout_thickness] {
+            write_f32(&mut w, v)?;
+        }
+        for v in [self.vertical_ascender, self.vertical_descender, self.vertical_line_gap] {
+            write_opt_f32(&mut w, v)?;
+        }
+
+        let mut kerning: Vec<_> = self.kerning.iter().collect();
+        kerning.sort_by_key(|(&pair, _)| pair);
+        write_u32(&mut w, kerning.len() as u32)?;
+        for (&(left, right), &value) in kerning {
+            write_u32(&mut w, left)?;
+            write_u32(&mut w, right)?;
+            write_f32(&mut w, value)?;
+        }
+
+        let mut mark_anchors: Vec<_> = self.mark_anchors.iter().collect();
+        mark_anchors.sort_by_key(|(&pair, _)| pair);
+        write_u32(&mut w, mark_anchors.len() as u32)?;
+        for (&(base, mark), &(dx, dy)) in mark_anchors {
+            write_u32(&mut w, base)?;
+            write_u32(&mut w, mark)?;
+            write_f32(&mut w, dx)?;
+            write_f32(&mut w, dy)?;
+        }
+
+        write_u32(&mut w, self.ligatures.len() as u32)?;
+        for ligature in &self.ligatures {
+            write_u32(&mut w, ligature.components.len() as u32)?;
+            for &cp in &ligature.components {
+                write_u32(&mut w, cp)?;
+            }
+            write_u32(&mut w, ligature.glyph_id as u32)?;
+        }
+
+        let mut clusters: Vec<_> = self.clusters.iter().collect();
+        clusters.sort_by_key(|(&cp, _)| cp);
+        write_u32(&mut w, clusters.len() as u32)?;
+        for (&cp, cluster) in clusters {
+            write_u32(&mut w, cp)?;
+            write_f32(&mut w, cluster.advance)?;
+            write_u32(&mut w, cluster.parts.len() as u32)?;
+            for part in &cluster.parts {
+                write_u32(&mut w, part.glyph_id as u32)?;
+                write_f32(&mut w, part.offset.0)?;
+                write_f32(&mut w, part.offset.1)?;
+            }
+        }
+
+        write_u32(&mut w, self.opentype_features.len() as u32)?;
+        for tag in &self.opentype_features {
+            write_bytes(&mut w, tag.as_bytes())?;
+        }
+
+        write_u32(&mut w, self.unresolved.len() as u32)?;
+        for &cp in &self.unresolved {
+            write_u32(&mut w, cp)?;
+        }
+
+        write_u32(&mut w, self.warnings.len() as u32)?;
+        for warning in &self.warnings {
+            let (kind, value) = match *warning {
+                FontWarning::MissingAdvance { codepoint } => (0, codepoint),
+                FontWarning::MissingGlyphAdvance { glyph_id } => (1, glyph_id as u32),
+                FontWarning::InvalidOutline { codepoint } => (2, codepoint),
+                FontWarning::InvalidGlyphOutline { glyph_id } => (3, glyph_id as u32),
+            };
+            w.write_all(&[kind])?;
+            write_u32(&mut w, value)?;
+        }
+
+        write_glyph(&mut w, &self.notdef)?;
+
+        let mut glyphs: Vec<_> = self.glyph_map.iter().collect();
+        glyphs.sort_by_key(|(&cp, _)| cp);
+        write_u32(&mut w, glyphs.len() as u32)?;
+        for (&cp, glyph) in glyphs {
+            write_u32(&mut w, cp)?;
+            write_glyph(&mut w, glyph)?;
+        }
+        let mut glyphs: Vec<_> = self.glyph_id_map.iter().collect();
+        glyphs.sort_by_key(|(&id, _)| id);
+        write_u32(&mut w, glyphs.len() as u32)?;
+        for (&id, glyph) in glyphs {
+            write_u32(&mut w, id as u32)?;
+            write_glyph(&mut w, glyph)?;
+        }
+        w.flush()
+    }
+
+    /// Reads a font written by [`Font::save`]. Fails with `InvalidData` for other files and
+    /// for caches written by a different format version.
+    pub fn load(path: &str) -> io::Result<Font> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic).map_err(|_| invalid("not a font mesh cache"))?;
+        if &magic != MAGIC {
+            return Err(invalid("not a font mesh cache"));
+        }
+        let version = read_u32(&mut r)?;
+        if version != FONT_CACHE_VERSION {
+            return Err(invalid(&format!("font cache version {} is not supported, expected {}", version, FONT_CACHE_VERSION)));
+        }
+
+        let mut font = Font {
+            em_size: read_f32(&mut r)?,
+            tolerance: read_opt_f32(&mut r)?,
+            source_hash: read_u64(&mut r)?,
+            name: String::from_utf8(read_bytes(&mut r)?).map_err(|_| invalid("font name is not utf-8"))?,
+            family_name: String::from_utf8(read_bytes(&mut r)?).map_err(|_| invalid("font name is not utf-8"))?,
+            style_name: String::from_utf8(read_bytes(&mut r)?).map_err(|_| invalid("font name is not utf-8"))?,
+            weight: read_u16(&mut r)?,
+            italic: read_u8(&mut r)? != 0,
+            monospace: read_u8(&mut r)? != 0,
+            ..Font::default()
+        };
+        for v in [&mut font.ascender, &mut font.descender, &mut font.line_gap, &mut font.x_height, &mut font.cap_height,
+                  &mut font.underline_position, &mut font.underline_thickness, &mut font.strikeout_position, &mut font.strikeout_thickness] {
+            *v = read_f32(&mut r)?;
+        }
+        for v in [&mut font.vertical_ascender, &mut font.vertical_descender, &mut font.vertical_line_gap] {
+            *v = read_opt_f32(&mut r)?;
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let pair = (read_u32(&mut r)?, read_u32(&mut r)?);
+            font.kerning.insert(pair, read_f32(&mut r)?);
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let pair = (read_u32(&mut r)?, read_u32(&mut r)?);
+            font.mark_anchors.insert(pair, (read_f32(&mut r)?, read_f32(&mut r)?));
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let components = (0..read_u32(&mut r)?).map(|_| read_u32(&mut r)).collect::<io::Result<_>>()?;
+            font.ligatures.push(Ligature { components, glyph_id: read_u32(&mut r)? as u16 });
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let cp = read_u32(&mut r)?;
+            let advance = read_f32(&mut r)?;
+            let parts = (0..read_u32(&mut r)?).map(|_| Ok(ClusterPart {
+                glyph_id: read_u32(&mut r)? as u16,
+                offset: (read_f32(&mut r)?, read_f32(&mut r)?),
+            })).collect::<io::Result<_>>()?;
+            font.clusters.insert(cp, GlyphCluster { parts, advance });
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let tag = String::from_utf8(read_bytes(&mut r)?).map_err(|_| invalid("feature tag is not utf-8"))?;
+            font.opentype_features.push(tag);
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            font.unresolved.push(read_u32(&mut r)?);
+        }
+
+        for _ in 0..read_u32(&mut r)? {
+            let kind = read_u8(&mut r)?;
+            let value = read_u32(&mut r)?;
+            font.warnings.push(match kind {
+                0 => FontWarning::MissingAdvance { codepoint: value },
+                1 => FontWarning::MissingGlyphAdvance { glyph_id: value as u16 },
+                2 => FontWarning::InvalidOutline { codepoint: value },
+                3 => FontWarning::InvalidGlyphOutline { glyph_id: value as u16 },
+                _ => return Err(invalid("unknown font warning")),
+            });
+        }
+
+        font.notdef = read_glyph(&mut r)?;
+
+        for _ in 0..read_u32(&mut r)? {
+            let cp = read_u32(&mut r)?;
+            font.glyph_map.insert(cp, read_glyph(&mut r)?);
+        }
+        for _ in 0..read_u32(&mut r)? {
+            let id = read_u32(&mut r)? as u16;
+            font.glyph_id_map.insert(id, read_glyph(&mut r)?);
+        }
+        Ok(font)
+    }
+}
+
+fn write_glyph(w: &mut impl Write, glyph: &Glyph) -> io::Result<()> {
+    write_f32(w, glyph.advance)?;
+    write_f32(w, glyph.lsb)?;
+    write_f32(w, glyph.rsb)?;
+    for v in [glyph.bbox.0, glyph.bbox.1, glyph.bbox.2, glyph.bbox.3] {
+        write_f32(w, v)?;
+    }
+    write_u32(w, glyph.face as u32)?;
+    w.write_all(&[glyph.is_empty as u8])?;
+    write_opt_f32(w, glyph.v_advance)?;
+    write_opt_f32(w, glyph.v_origin_y)?;
+
+    write_mesh(w, &glyph.outline)?;
+    match (&glyph.stroke_outline, glyph.stroke_bbox) {
+        (Some(stroke), Some(bbox)) => {
+            w.write_all(&[1])?;
+            write_mesh(w, stroke)?;
+            for v in [bbox.0, bbox.1, bbox.2, bbox.3] {
+                write_f32(w, v)?;
+            }
+        }
+        _ => w.write_all(&[0])?,
+    }
+
+    match &glyph.image {
+        Some(image) => {
+            w.write_all(&[1])?;
+            write_bytes(w, &image.png)?;
+            for v in [image.width, image.height, image.x as u16, image.y as u16, image.pixels_per_em] {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        None => w.write_all(&[0])?,
+    }
+
+    write_u32(w, glyph.svg_layers.len() as u32)?;
+    for layer in &glyph.svg_layers {
+        write_layer(w, layer)?;
+    }
+
+    match &glyph.path_events {
+        Some(events) => {
+            w.write_all(&[1])?;
+            write_u32(w, events.len() as u32)?;
+            for event in events {
+                write_event(w, event)?;
+            }
+            Ok(())
+        }
+        None => w.write_all(&[0]),
+    }
+}
+
+fn read_glyph(r: &mut impl Read) -> io::Result<Glyph> {
+    let advance = read_f32(r)?;
+    let (lsb, rsb) = (read_f32(r)?, read_f32(r)?);
+    let bbox = (read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?);
+    let face = read_u32(r)? as usize;
+    let is_empty = read_u8(r)? != 0;
+    let v_advance = read_opt_f32(r)?;
+    let v_origin_y = read_opt_f32(r)?;
+
+    let outline = read_mesh(r)?;
+    let (stroke_outline, stroke_bbox) = match read_u8(r)? {
+        0 => (None, None),
+        _ => {
+            let mesh = read_mesh(r)?;
+            (Some(mesh), Some((read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?)))
+        }
+    };
+
+    let image = match read_u8(r)? {
+        0 => None,
+        _ => {
+            let png = read_bytes(r)?;
+            let mut v = [0u16; 5];
+            for x in &mut v {
+                *x = read_u16(r)?;
+            }
+            Some(GlyphImage { png, width: v[0], height: v[1], x: v[2] as i16, y: v[3] as i16, pixels_per_em: v[4] })
+        }
+    };
+
+    let svg_layers = (0..read_u32(r)?).map(|_| read_layer(r)).collect::<io::Result<_>>()?;
+
+    let path_events = match read_u8(r)? {
+        0 => None,
+        _ => Some((0..read_u32(r)?).map(|_| read_event(r)).collect::<io::Result<_>>()?),
+    };
+
+    Ok(Glyph {
+        advance, bbox, outline, path_events, face, svg_layers, image, is_empty, v_advance, v_origin_y, lsb, rsb,
+        stroke_outline, stroke_bbox,
+    })
+}
+
+fn write_layer(w: &mut impl Write, layer: &RenderablePath) -> io::Result<()> {
+    write_u32(w, layer.size.0)?;
+    write_u32(w, layer.size.1)?;
+    for v in layer.bgcolor {
+        write_f32(w, v)?;
+    }
+    w.write_all(&[layer.gradient_stops])?;
+    match &layer.gradient_pos {
+        Some(pos) => {
+            w.write_all(&[1])?;
+            write_u32(w, pos.len() as u32)?;
+            for &v in pos {
+                write_f32(w, v)?;
+            }
+        }
+        None => w.write_all(&[0])?,
+    }
+    match &layer.gradient_colors {
+        Some(colors) => {
+            w.write_all(&[1])?;
+            write_u32(w, colors.len() as u32)?;
+            for color in colors {
+                for &v in color {
+                    write_f32(w, v)?;
+                }
+            }
+        }
+        None => w.write_all(&[0])?,
+    }
+    for point in [layer.gradient_start, layer.gradient_end] {
+        match point {
+            Some((x, y)) => {
+                w.write_all(&[1])?;
+                write_f32(w, x)?;
+                write_f32(w, y)?;
+            }
+            None => w.write_all(&[0])?,
+        }
+    }
+    write_mesh(w, &layer.vertices)?;
+    w.write_all(&layer.z_index.to_le_bytes())?;
+    write_f32(w, layer.path_opacity)?;
+    match &layer.local_transform {
+        Some(matrix) => {
+            w.write_all(&[1])?;
+            for &v in matrix.iter().flatten() {
+                write_f32(w, v)?;
+            }
+            Ok(())
+        }
+        None => w.write_all(&[0]),
+    }
+}
+
+fn read_layer(r: &mut impl Read) -> io::Result<RenderablePath> {
+    let size = (read_u32(r)?, read_u32(r)?);
+    let bgcolor = [read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?];
+    let gradient_stops = read_u8(r)?;
+    let gradient_pos = match read_u8(r)? {
+        0 => None,
+        _ => Some((0..read_u32(r)?).map(|_| read_f32(r)).collect::<io::Result<_>>()?),
+    };
+    let gradient_colors = match read_u8(r)? {
+        0 => None,
+        _ => Some((0..read_u32(r)?).map(|_| Ok([read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?])).collect::<io::Result<_>>()?),
+    };
+    let mut endpoints = [None; 2];
+    for point in &mut endpoints {
+        if read_u8(r)? != 0 {
+            *point = Some((read_f32(r)?, read_f32(r)?));
+        }
+    }
+    let vertices = read_mesh(r)?;
+    let z_index = read_u32(r)? as i32;
+    let path_opacity = read_f32(r)?;
+    let local_transform = match read_u8(r)? {
+        0 => None,
+        _ => {
+            let mut matrix = [[0.0; 3]; 3];
+            for v in matrix.iter_mut().flatten() {
+                *v = read_f32(r)?;
+            }
+            Some(matrix)
+        }
+    };
+    Ok(RenderablePath {
+        size, bgcolor, gradient_stops, gradient_pos, gradient_colors,
+        gradient_start: endpoints[0], gradient_end: endpoints[1],
+        vertices, z_index, path_opacity, local_transform,
+    })
+}
+
+fn write_event(w: &mut impl Write, event: &PathEvent) -> io::Result<()> {
+    let (kind, points): (u8, &[Point]) = match event {
+        PathEvent::Begin { at } => (0, std::slice::from_ref(at)),
+        PathEvent::Line { from, to } => (1, &[*from, *to]),
+        PathEvent::Quadratic { from, ctrl, to } => (2, &[*from, *ctrl, *to]),
+        PathEvent::Cubic { from, ctrl1, ctrl2, to } => (3, &[*from, *ctrl1, *ctrl2, *to]),
+        PathEvent::End { last, first, close } => (if *close { 5 } else { 4 }, &[*last, *first]),
+    };
+    w.write_all(&[kind])?;
+    for p in points {
+        write_f32(w, p.x)?;
+        write_f32(w, p.y)?;
+    }
+    Ok(())
+}
+
+fn read_event(r: &mut impl Read) -> io::Result<PathEvent> {
+    let kind = read_u8(r)?;
+    let mut point = || -> io::Result<Point> { Ok(Point::new(read_f32(r)?, read_f32(r)?)) };
+    Ok(match kind {
+        0 => PathEvent::Begin { at: point()? },
+        1 => PathEvent::Line { from: point()?, to: point()? },
+        2 => PathEvent::Quadratic { from: point()?, ctrl: point()?, to: point()? },
+        3 => PathEvent::Cubic { from: point()?, ctrl1: point()?, ctrl2: point()?, to: point()? },
+        4 | 5 => PathEvent::End { last: point()?, first: point()?, close: kind == 5 },
+        _ => return Err(invalid("unknown path event")),
+    })
+}
+
+fn write_mesh(w: &mut impl Write, mesh: &VertexBuffers<GpuVertex, u32>) -> io::Result<()> {
+    write_u32(w, mesh.vertices.len() as u32)?;
+    for v in &mesh.vertices {
+        write_f32(w, v.position[0])?;
+        write_f32(w, v.position[1])?;
+        write_u32(w, v.prim_id)?;
+    }
+    write_u32(w, mesh.indices.len() as u32)?;
+    for &i in &mesh.indices {
+        write_u32(w, i)?;
+    }
+    Ok(())
+}
+
+fn read_mesh(r: &mut impl Read) -> io::Result<VertexBuffers<GpuVertex, u32>> {
+    let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+    for _ in 0..read_u32(r)? {
+        let position = [read_f32(r)?, read_f32(r)?];
+        mesh.vertices.push(GpuVertex { position, prim_id: read_u32(r)? });
+    }
+    for _ in 0..read_u32(r)? {
+        mesh.indices.push(read_u32(r)?);
+    }
+    if mesh.indices.iter().any(|&i| i as usize >= mesh.vertices.len()) {
+        return Err(invalid("glyph mesh index out of range"));
+    }
+    Ok(mesh)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_u32(w: &mut impl Write, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_f32(w: &mut impl Write, v: f32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_opt_f32(w: &mut impl Write, v: Option<f32>) -> io::Result<()> {
+    match v {
+        Some(v) => {
+            w.write_all(&[1])?;
+            write_f32(w, v)
+        }
+        None => w.write_all(&[0]),
+    }
+}
+
+fn write_bytes(w: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_u32(w, bytes.len() as u32)?;
+    w.write_all(bytes)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut b = [0; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut b = [0; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(f32::from_le_bytes(b))
+}
+
+fn read_opt_f32(r: &mut impl Read) -> io::Result<Option<f32>> {
+    match read_u8(r)? {
+        0 => Ok(None),
+        _ => read_f32(r).map(Some),
+    }
+}
+
+fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(r)? as usize;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, DEJAVU_SANS};
+    use crate::ttfload::{load_font_with_options, FontOptions};
+
+    const FIXTURE: &str = "font_cache_v12.bin";
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("svg-load-{}-{}.bin", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    /// "Il" plus an uncovered codepoint with retained path events, a warning and an SVG layer
+    /// on 'I', so every optional part of the format is present.
+    fn sample_font() -> Font {
+        let options = FontOptions { em_size: 1.0, retain_path_events: true, ..FontOptions::default() };
+        let mut font = load_font_with_options(DEJAVU_SANS, "Il\u{10FFFD}", &options).unwrap();
+        font.warnings.push(FontWarning::InvalidGlyphOutline { glyph_id: 7 });
+        let glyph = font.glyph_map.get_mut(&u32::from('I')).unwrap();
+        let mut layer = RenderablePath {
+            size: (1, 1),
+            bgcolor: [1.0, 0.0, 0.0, 1.0],
+            gradient_stops: 2,
+            gradient_pos: Some(vec![0.0, 1.0]),
+            gradient_colors: Some(vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]]),
+            gradient_start: Some((0.0, 0.0)),
+            gradient_end: Some((0.5, 0.7)),
+            vertices: glyph.outline.clone(),
+            z_index: -2,
+            path_opacity: 0.5,
+            local_transform: Some([[1.0, 0.0, 0.1], [0.0, 1.0, 0.2], [0.0, 0.0, 1.0]]),
+        };
+        glyph.svg_layers.push(layer.clone());
+        layer.local_transform = None;
+        layer.gradient_pos = None;
+        layer.gradient_colors = None;
+        glyph.svg_layers.push(layer);
+        font
+    }
+
+    fn assert_same(a: &Font, b: &Font) {
+        assert_eq!(a.kerning, b.kerning);
+        let json = |font: &Font| serde_json::to_value(Font { kerning: Default::default(), ..font.clone() }).unwrap();
+        assert_eq!(json(a), json(b));
+    }
+
+    #[test]
+    fn save_and_load_round_trip_every_field() {
+        let font = sample_font();
+        let path = temp_path("font-cache-round-trip");
+        font.save(&path).unwrap();
+        let loaded = Font::load(&path);
+        std::fs::remove_file(&path).ok();
+        let loaded = loaded.unwrap();
+
+        assert_same(&font, &loaded);
+        let i = loaded.glyph('I').unwrap();
+        assert_eq!(i.svg_layers.len(), 2);
+        assert!(i.path_events.as_ref().is_some_and(|e| !e.is_empty()));
+        assert_eq!(loaded.unresolved, vec![0x10FFFD]);
+        assert_eq!(loaded.warnings, vec![FontWarning::InvalidGlyphOutline { glyph_id: 7 }]);
+    }
+
+    #[test]
+    fn checked_in_cache_still_loads_and_saves_identically() {
+        // Regenerate with `sample_font().save(...)` when bumping FONT_CACHE_VERSION.
+        let bytes = fixtures::read(FIXTURE);
+        assert_eq!(bytes[8..12], FONT_CACHE_VERSION.to_le_bytes(), "fixture is for another format version");
+        let font = Font::load(&fixtures::path(FIXTURE)).unwrap();
+        assert_eq!((font.name.as_str(), font.family_name()), ("DejaVuSans.ttf", "DejaVu Sans"));
+        assert_eq!(font.em_size, 1.0);
+        assert!(font.glyph('I').is_some() && font.glyph('l').is_some());
+        assert_eq!(font.unresolved, vec![0x10FFFD]);
+        assert_eq!(font.glyph('I').unwrap().svg_layers[0].z_index, -2);
+
+        let path = temp_path("font-cache-resave");
+        font.save(&path).unwrap();
+        let resaved = std::fs::read(&path);
+        std::fs::remove_file(&path).ok();
+        assert!(resaved.unwrap() == bytes, "re-saving the fixture changed its bytes");
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = fixtures::read(FIXTURE);
+        bytes[8..12].copy_from_slice(&(FONT_CACHE_VERSION + 1).to_le_bytes());
+        let path = temp_path("font-cache-version");
+        std::fs::write(&path, &bytes).unwrap();
+        let err = Font::load(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&format!("version {}", FONT_CACHE_VERSION + 1)), "{}", err);
+
+        let path = temp_path("font-cache-magic");
+        std::fs::write(&path, b"not a cache").unwrap();
+        let err = Font::load(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[ignore]
+    fn write_fixture() {
+        sample_font().save(&fixtures::path(FIXTURE)).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use ttf_parser as ttf;
+
+use crate::font::{Font, Glyph};
+use crate::ttfload::{add_clusters, build_font, face_max_advance, fit_glyph, fitted_advance, load_glyphs, load_kerning, loaded_glyph_ids, parse_face, resolve_symbols, sfnt_data, source_hash, FontLoadError, FontOptions};
+
+/// A font whose glyphs are tessellated on first use instead of up front.
+///
+/// The face is parsed once, and the advance of `FontOptions::force_advance` or
+/// `FontOptions::tabular_figures` is fixed at construction: since the loaded glyphs keep
+/// changing, `Advance::MaxLoaded` and tabular figures use the largest advance of the whole
+/// face. Lookups take `&mut self`, so a `FontFace` is not shared between threads; wrap it in
+/// a `Mutex` if that is needed, or freeze it with [`FontFace::into_font`].
+pub struct FontFace {
+    // Borrows the heap buffer of `data`, which is never changed or reallocated; declared first
+    // so it is dropped before `data`.
+    face: ttf::Face<'static>,
+    data: Vec<u8>,
+    options: FontOptions,
+    font: Font,
+    advance: Option<f32>,
+    tessellated: usize,
+}
+
+impl FontFace {
+    pub fn from_bytes(data: Vec<u8>, options: FontOptions) -> Result<Self, FontLoadError> {
+        let sfnt = match sfnt_data(&data)? {
+            Cow::Owned(sfnt) => Some(sfnt),
+            Cow::Borrowed(_) => None,
+        };
+        let data = sfnt.unwrap_or(data);
+        let face = parse_face(&data, &options)?;
+        // SAFETY: the face only points into the heap buffer of `data`, which moves with the
+        // `FontFace` without being reallocated and outlives the face, see the field order.
+        let face = unsafe { std::mem::transmute::<ttf::Face<'_>, ttf::Face<'static>>(face) };
+        let mut font = build_font(&face, Default::default(), Vec::new(), &options)?;
+        let advance = fitted_advance(&options, || face_max_advance(&face, &options));
+        if let Some(width) = advance {
+            fit_glyph(&mut font.notdef, width, &options);
+        }
+        Ok(FontFace { face, data, options, font, advance, tessellated: 0 })
+    }
+
+    pub fn from_file(filename: &str) -> Result<Self, FontLoadError> {
+        Self::from_bytes(std::fs::read(filename)?, FontOptions::default())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.font.name
+    }
+
+    pub fn ascender(&self) -> f32 {
+        self.font.ascender
+    }
+
+    pub fn descender(&self) -> f32 {
+        self.font.descender
+    }
+
+    pub fn line_gap(&self) -> f32 {
+        self.font.line_gap
+    }
+
+    /// Glyph for codepoint `cp`, tessellating it on the first request. Returns `None` for
+    /// invalid codepoints, for characters the face draws as a cluster (see
+    /// `Font::clusters`), and for characters the face lacks when the missing-glyph policy
+    /// does not map them to .notdef. A glyph that fails to load leaves the cache unchanged.
+    pub fn glyph(&mut self, cp: u32) -> Result<Option<&Glyph>, FontLoadError> {
+        let Some(ch) = char::from_u32(cp) else {
+            return Ok(None);
+        };
+        if !self.font.glyph_map.contains_key(&cp) && !self.font.clusters.contains_key(&cp) && !self.font.unresolved.contains(&cp) {
+            let (face, options) = (&self.face, &self.options);
+            let (g_map, unresolved, clusters) = resolve_symbols(face, ch.encode_utf8(&mut [0; 4]), options)?;
+            let kerning = load_kerning(face, &g_map, &loaded_glyph_ids(face, &self.font), options);
+            let mut warnings = Vec::new();
+            let mut glyphs = load_glyphs(face, g_map, options, &mut warnings)?;
+            let mut part_ids: Vec<u16> = clusters.iter()
+                .flat_map(|(_, parts)| parts.iter().map(|&(_, id)| id.0))
+                .filter(|id| !self.font.glyph_id_map.contains_key(id))
+                .collect();
+            part_ids.sort_unstable();
+            part_ids.dedup();
+            // Loads every part before it changes the font.
+            add_clusters(&mut self.font, face, clusters, options)?;
+
+            if let Some(width) = self.advance {
+                let parts = self.font.glyph_id_map.iter_mut().filter(|(id, _)| part_ids.binary_search(id).is_ok());
+                for glyph in glyphs.values_mut().chain(parts.map(|(_, glyph)| glyph)) {
+                    fit_glyph(glyph, width, options);
+                }
+                if options.force_advance.is_some() {
+                    self.font.clusters.values_mut().for_each(|c| c.advance = width);
+                }
+            }
+            self.tessellated += glyphs.len() + part_ids.len();
+            self.font.kerning.extend(kerning);
+            self.font.glyph_map.extend(glyphs);
+            self.font.unresolved.extend(unresolved);
+            self.font.warnings.extend(warnings);
+        }
+        Ok(self.font.glyph_map.get(&cp))
+    }
+
+    /// Glyph with id `glyph_id`, tessellating it on the first request, e.g. for shaped text.
+    /// Returns `None` for ids beyond the face's glyph count.
+    pub fn glyph_by_id(&mut self, glyph_id: u16) -> Result<Option<&Glyph>, FontLoadError> {
+        if !self.font.glyph_id_map.contains_key(&glyph_id) {
+            let added = self.font.insert_glyph_ids(&self.face, &[glyph_id], &self.options)?;
+            if let Some(width) = self.advance {
+                for id in &added {
+                    fit_glyph(self.font.glyph_id_map.get_mut(id).unwrap(), width, &self.options);
+                }
+            }
+            self.tessellated += added.len();
+        }
+        Ok(self.font.glyph_id_map.get(&glyph_id))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn options(&self) -> &FontOptions {
+        &self.options
+    }
+
+    /// Number of glyphs tessellated so far; cache hits do not increase it.
+    pub fn tessellated_count(&self) -> usize {
+        self.tessellated
+    }
+
+    /// Freezes the glyphs tessellated so far into a serializable [`Font`].
+    pub fn into_font(mut self) -> Font {
+        self.font.source_hash = source_hash(&self.data, &self.font, &self.options);
+        self.font
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, DEJAVU_SANS};
+    use crate::ttfload::{load_font_with_options, Advance, MissingGlyph};
+
+    #[test]
+    fn glyphs_are_tessellated_once_on_first_use() {
+        let mut face = FontFace::from_file(DEJAVU_SANS).unwrap();
+        assert_eq!(face.tessellated_count(), 0);
+        let advance = face.glyph(u32::from('a')).unwrap().unwrap().advance;
+        assert_eq!(face.tessellated_count(), 1);
+        assert_eq!(face.glyph(u32::from('a')).unwrap().unwrap().advance, advance);
+        assert_eq!(face.tessellated_count(), 1);
+
+        let font = face.into_font();
+        assert_eq!(font.glyph_map.len(), 1);
+        assert!(!font.glyph('a').unwrap().outline.indices.is_empty());
+    }
+
+    #[test]
+    fn fitted_advances_stay_put_as_glyphs_load() {
+        let options = FontOptions { force_advance: Some(Advance::MaxLoaded), center_in_cell: true, ..FontOptions::default() };
+        let mut face = FontFace::from_bytes(fixtures::read("DejaVuSans.ttf"), options.clone()).unwrap();
+        let i = face.glyph(u32::from('i')).unwrap().unwrap().clone();
+        for ch in "W\u{2014}mi".chars() {
+            face.glyph(u32::from(ch)).unwrap();
+        }
+        let again = face.glyph(u32::from('i')).unwrap().unwrap();
+        assert_eq!((again.advance, again.bbox), (i.advance, i.bbox));
+        assert_eq!(face.tessellated_count(), 4);
+
+        // The cell is the widest glyph of the face, which an eager load of the widest
+        // character agrees with.
+        let font = face.into_font();
+        assert!(font.glyph_map.values().chain([&font.notdef]).all(|g| g.advance == i.advance));
+        let eager = load_font_with_options(DEJAVU_SANS, "\u{2014}", &options).unwrap();
+        assert!(i.advance >= eager.glyph('\u{2014}').unwrap().advance);
+    }
+
+    #[test]
+    fn failed_loads_are_reported_and_leave_the_cache_alone() {
+        let options = FontOptions { missing_glyph: MissingGlyph::Error, ..FontOptions::default() };
+        let mut face = FontFace::from_bytes(fixtures::corrupted_dejavu('a'), options).unwrap();
+        assert!(matches!(face.glyph(u32::from('a')), Err(FontLoadError::OutlineFailed { .. })));
+        assert!(face.glyph(u32::from('a')).is_err(), "a failed glyph must not be cached");
+        assert!(face.glyph(u32::from('b')).unwrap().is_some());
+        let font = face.into_font();
+        assert_eq!(font.glyph_map.len(), 1);
+        assert!(font.warnings.is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+//! Golden-image checks of the loader against resvg's reference rendering.
+
+use std::path::PathBuf;
+
+use tiny_skia::{FillRule, Pixmap, Transform};
+
+use crate::path::RenderablePath;
+use crate::skia::{mesh_to_skia_path, paint_to_skia};
+use crate::svgload::{load_svg_data, LoadOptions};
+
+/// Rasterizes the crate's own tessellation of `svg` into a `width` x `height` pixmap.
+pub fn render_tessellated(svg: &[u8], width: u32, height: u32) -> Pixmap {
+    // resvg resolves a bare currentColor to black.
+    let options = LoadOptions { current_color: [0.0, 0.0, 0.0, 1.0], ..LoadOptions::default() };
+    render_paths(&load_svg_data(svg, &options).expect("failed to load svg").into_paths(), width, height)
+}
+
+/// Rasterizes loaded `paths`, flattened or not, into a `width` x `height` pixmap.
+pub fn render_paths(paths: &[RenderablePath], width: u32, height: u32) -> Pixmap {
+    let mut pixmap = Pixmap::new(width, height).expect("invalid pixmap size");
+    let to_pixels = Transform::from_row(width as f32, 0.0, 0.0, -(height as f32), 0.0, height as f32);
+    for path in paths {
+        if let Some(skia_path) = mesh_to_skia_path(path, to_pixels) {
+            let paint = paint_to_skia(path, to_pixels);
+            pixmap.fill_path(&skia_path, &paint, FillRule::Winding, Transform::identity(), None);
+        }
+    }
+    pixmap
+}
+
+/// Renders `svg` with resvg into a `width` x `height` pixmap.
+pub fn render_reference(svg: &[u8], width: u32, height: u32) -> Pixmap {
+    let tree = usvg::Tree::from_data(svg, &usvg::Options::default().to_ref()).expect("failed to parse svg");
+    let mut pixmap = Pixmap::new(width, height).expect("invalid pixmap size");
+    resvg::render(&tree, usvg::FitTo::Size(width, height), Transform::identity(), pixmap.as_mut());
+    pixmap
+}
+
+/// Mean over all pixels of the largest per-channel difference, in `0.0..=1.0`.
+pub fn difference(a: &Pixmap, b: &Pixmap) -> f32 {
+    assert_eq!((a.width(), a.height()), (b.width(), b.height()), "pixmap sizes differ");
+    let total: u64 = a.data().chunks_exact(4).zip(b.data().chunks_exact(4))
+        .map(|(pa, pb)| pa.iter().zip(pb).map(|(x, y)| x.abs_diff(*y)).max().unwrap_or(0) as u64)
+        .sum();
+    let pixels = (a.width() * a.height()).max(1) as f32;
+    total as f32 / (pixels * 255.0)
+}
+
+/// Renders `svg` both through the tessellator and through resvg and panics if their
+/// [`difference`] exceeds `max_diff`, after writing both images to the temp directory.
+/// `width`/`height` should keep the document's aspect ratio: resvg letterboxes, while
+/// tessellated meshes are stretched over the whole target. Returns the score.
+pub fn verify_against_reference(svg: &[u8], width: u32, height: u32, max_diff: f32) -> f32 {
+    let actual = render_tessellated(svg, width, height);
+    let expected = render_reference(svg, width, height);
+    let score = difference(&actual, &expected);
+    if score > max_diff {
+        let dir = std::env::temp_dir();
+        let stem = format!("svg-load-golden-{:016x}", fingerprint(svg));
+        let actual_path: PathBuf = dir.join(format!("{}-actual.png", stem));
+        let expected_path: PathBuf = dir.join(format!("{}-expected.png", stem));
+        let _ = actual.save_png(&actual_path);
+        let _ = expected.save_png(&expected_path);
+        panic!("rendering differs from reference: score {} > {} (see {} and {})",
+               score, max_diff, actual_path.display(), expected_path.display());
+    }
+    score
+}
+
+fn fingerprint(data: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/golden");
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(format!("{}/{}", FIXTURES, name)).unwrap()
+    }
+
+    #[test]
+    fn gradients_match_reference() {
+        verify_against_reference(&fixture("gradient.svg"), 64, 64, 0.02);
+    }
+
+    #[test]
+    fn strokes_match_reference() {
+        verify_against_reference(&fixture("stroke.svg"), 64, 64, 0.02);
+    }
+
+    #[test]
+    fn transforms_match_reference() {
+        verify_against_reference(&fixture("transform.svg"), 64, 64, 0.02);
+    }
+
+    #[test]
+    fn difference_is_zero_for_equal_and_one_for_opposite_images() {
+        let mut black = Pixmap::new(4, 4).unwrap();
+        black.fill(tiny_skia::Color::BLACK);
+        let mut white = Pixmap::new(4, 4).unwrap();
+        white.fill(tiny_skia::Color::WHITE);
+        assert_eq!(difference(&black, &black), 0.0);
+        assert_eq!(difference(&black, &white), 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "rendering differs from reference")]
+    fn mismatch_fails_the_check() {
+        // Negative thresholds cannot be met, so even a perfect match is reported.
+        verify_against_reference(&fixture("gradient.svg"), 16, 16, -1.0);
+    }
+
+    #[test]
+    fn unflattened_transforms_match_reference() {
+        let svg = fixture("transform.svg");
+        let options = LoadOptions { current_color: [0.0, 0.0, 0.0, 1.0], flatten_transforms: false, ..LoadOptions::default() };
+        let paths = load_svg_data(&svg, &options).unwrap().into_paths();
+        assert!(paths.iter().all(|p| p.local_transform.is_some()));
+        let score = difference(&render_paths(&paths, 64, 64), &render_reference(&svg, 64, 64));
+        assert!(score <= 0.02, "score {}", score);
+    }
+}
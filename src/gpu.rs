@@ -0,0 +1,236 @@
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::path::{GpuVertex, RenderablePath};
+
+/// Gradient stops beyond this count are dropped when packing paints.
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+/// Paint of one primitive as laid out in the paint storage buffer (std430 compatible).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuPaint {
+    pub color: [f32; 4],
+    pub gradient_start: [f32; 2],
+    pub gradient_end: [f32; 2],
+    pub stop_count: u32,
+    /// `RenderablePath::path_opacity`, multiplied into the alpha of `color` or the stops.
+    pub opacity: f32,
+    pub _padding: [u32; 2],
+    pub stop_positions: [f32; MAX_GRADIENT_STOPS],
+    pub stop_colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+}
+
+impl GpuPaint {
+    pub fn from_path(path: &RenderablePath) -> Self {
+        let mut paint = GpuPaint {
+            color: path.bgcolor,
+            opacity: path.path_opacity,
+            ..Default::default()
+        };
+        if let Some(stops) = path.gradient_color_stops() {
+            let n = stops.len().min(MAX_GRADIENT_STOPS);
+            paint.stop_count = n as u32;
+            for (i, (pos, color)) in stops.into_iter().take(n).enumerate() {
+                paint.stop_positions[i] = pos;
+                paint.stop_colors[i] = color;
+            }
+            let start = path.gradient_start.unwrap_or((0.0, 0.0));
+            let end = path.gradient_end.unwrap_or((0.0, 0.0));
+            paint.gradient_start = [start.0, start.1];
+            paint.gradient_end = [end.0, end.1];
+        }
+        paint
+    }
+}
+
+impl GpuVertex {
+    pub const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Uint32];
+
+    pub const fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GpuVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// CPU-side contents of a [`GpuScene`]: all meshes merged into one vertex/index pair.
+///
+/// Vertex `prim_id`s are rewritten to the index of their path, so `paints[prim_id]`
+/// is the paint of the vertex. `draw_ranges[i]` is the index range of `paths[i]`. Positions
+/// and gradient endpoints are in mesh space, with any `local_transform` applied.
+#[derive(Clone, Debug, Default)]
+pub struct SceneData {
+    pub vertices: Vec<GpuVertex>,
+    pub indices: Vec<u32>,
+    pub paints: Vec<GpuPaint>,
+    pub draw_ranges: Vec<Range<u32>>,
+}
+
+impl SceneData {
+    pub fn pack(paths: &[RenderablePath]) -> Self {
+        let mut data = SceneData::default();
+        for (i, path) in paths.iter().enumerate() {
+            let path = path.flattened();
+            let base = data.vertices.len() as u32;
+            let first = data.indices.len() as u32;
+            data.vertices.extend(path.vertices.vertices.iter().map(|v| GpuVertex {
+                position: v.position,
+                prim_id: i as u32,
+            }));
+            data.indices.extend(path.vertices.indices.iter().map(|idx| idx + base));
+            data.paints.push(GpuPaint::from_path(&path));
+            data.draw_ranges.push(first..data.indices.len() as u32);
+        }
+        data
+    }
+
+    pub fn vertex_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+
+    pub fn index_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.indices)
+    }
+
+    pub fn paint_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.paints)
+    }
+}
+
+pub struct GpuScene {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    /// Storage buffer of [`GpuPaint`] indexed by `prim_id`; never empty so it can always be bound.
+    pub paint_buffer: wgpu::Buffer,
+    pub draw_ranges: Vec<Range<u32>>,
+    pub index_count: u32,
+}
+
+pub fn upload(device: &wgpu::Device, paths: &[RenderablePath]) -> GpuScene {
+    let mut data = SceneData::pack(paths);
+    if data.paints.is_empty() {
+        data.paints.push(GpuPaint::default());
+    }
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("svg-load vertices"),
+        contents: data.vertex_bytes(),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("svg-load indices"),
+        contents: data.index_bytes(),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+    let paint_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("svg-load paints"),
+        contents: data.paint_bytes(),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    GpuScene {
+        vertex_buffer,
+        index_buffer,
+        paint_buffer,
+        index_count: data.indices.len() as u32,
+        draw_ranges: data.draw_ranges,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::PaintSpec;
+    use lyon::tessellation::VertexBuffers;
+
+    fn triangle(x: f32, paint: PaintSpec) -> RenderablePath {
+        let mut mesh = VertexBuffers::new();
+        mesh.vertices = vec![[x, 0.0], [x + 1.0, 0.0], [x, 1.0]].into_iter()
+            .map(|position| GpuVertex { position, prim_id: 7 })
+            .collect();
+        mesh.indices = vec![0, 1, 2];
+        RenderablePath::from_paint((1, 1), &paint, mesh)
+    }
+
+    #[test]
+    fn pack_merges_meshes_and_rewrites_prim_ids() {
+        let red = PaintSpec::Color([1.0, 0.0, 0.0, 1.0]);
+        let blue = PaintSpec::Color([0.0, 0.0, 1.0, 0.5]);
+        let data = SceneData::pack(&[triangle(0.0, red), triangle(2.0, blue)]);
+
+        assert_eq!(data.vertices.len(), 6);
+        assert_eq!(data.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(data.draw_ranges, vec![0..3, 3..6]);
+        assert!(data.vertices[..3].iter().all(|v| v.prim_id == 0));
+        assert!(data.vertices[3..].iter().all(|v| v.prim_id == 1));
+        assert_eq!(data.vertices[3].position, [2.0, 0.0]);
+        assert_eq!(data.paints[1].color, [0.0, 0.0, 1.0, 0.5]);
+        assert_eq!(data.paints[1].stop_count, 0);
+    }
+
+    #[test]
+    fn byte_views_match_element_layout() {
+        let data = SceneData::pack(&[triangle(0.0, PaintSpec::Color([1.0; 4]))]);
+        assert_eq!(data.vertex_bytes().len(), 3 * 12);
+        assert_eq!(&data.vertex_bytes()[..8], bytemuck::cast_slice::<f32, u8>(&[0.0, 0.0]));
+        assert_eq!(data.index_bytes().len(), 3 * 4);
+        assert_eq!(data.paint_bytes().len(), std::mem::size_of::<GpuPaint>());
+        // std430 arrays of structs need a 16 byte multiple.
+        assert_eq!(std::mem::size_of::<GpuPaint>() % 16, 0);
+        assert_eq!(GpuVertex::vertex_buffer_layout().array_stride, 12);
+    }
+
+    #[test]
+    fn gradient_paint_is_truncated_to_max_stops() {
+        let n = MAX_GRADIENT_STOPS + 2;
+        let gradient = PaintSpec::LinearGradient(crate::path::GradientSpec {
+            start: (0.0, 0.0),
+            end: (1.0, 0.0),
+            positions: (0..n).map(|i| i as f32 / (n - 1) as f32).collect(),
+            colors: vec![[0.5; 4]; n],
+        });
+        let paint = GpuPaint::from_path(&triangle(0.0, gradient));
+        assert_eq!(paint.stop_count as usize, MAX_GRADIENT_STOPS);
+        assert_eq!(paint.gradient_end, [1.0, 0.0]);
+        assert_eq!(paint.stop_positions[0], 0.0);
+    }
+
+    #[test]
+    fn pack_applies_local_transforms() {
+        use crate::svgload::{load_svg_data, LoadOptions};
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="8" y2="0">
+                <stop offset="0" stop-color="#f00"/><stop offset="1" stop-color="#00f"/>
+            </linearGradient>
+            <g transform="translate(16 8)"><rect width="8" height="8" fill="url(#g)"/></g>
+        </svg>"##;
+        let flat = SceneData::pack(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths());
+        let local = SceneData::pack(&load_svg_data(svg, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap().into_paths());
+        let close = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).abs() < 1e-5 && (a[1] - b[1]).abs() < 1e-5;
+        assert_eq!(flat.vertices.len(), local.vertices.len());
+        for (a, b) in flat.vertices.iter().zip(&local.vertices) {
+            assert!(close(a.position, b.position), "{:?} vs {:?}", a.position, b.position);
+        }
+        assert!(close(local.paints[0].gradient_start, [0.5, 0.75]));
+        assert!(close(local.paints[0].gradient_end, flat.paints[0].gradient_end));
+    }
+
+    #[test]
+    fn same_color_paths_with_different_opacities_keep_their_own_paints() {
+        let red = PaintSpec::Color([1.0, 0.0, 0.0, 1.0]);
+        let paths = [triangle(0.0, red.clone()), triangle(2.0, red).with_opacity(0.25)];
+        let data = SceneData::pack(&paths);
+        assert_eq!(data.paints.len(), 2);
+        assert_eq!((data.paints[0].color, data.paints[1].color), ([1.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]));
+        assert_eq!((data.paints[0].opacity, data.paints[1].opacity), (1.0, 0.25));
+        assert!(data.vertices[3..].iter().all(|v| data.paints[v.prim_id as usize].opacity == 0.25));
+
+        let batches = crate::batch::batch_paths(&paths);
+        let opacities: Vec<f32> = batches.iter().map(|b| b.opacity).collect();
+        assert_eq!(opacities, [1.0, 0.25]);
+    }
+}
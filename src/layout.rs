@@ -0,0 +1,850 @@
+//! Placement of glyphs for blocks of text. Positions are in the font's output units, y-up like
+//! the glyph meshes.
+
+use std::ops::Range;
+
+use unicode_bidi::{BidiInfo, Level};
+
+use crate::font::{Font, FontChain, Glyph, RunGlyph};
+use crate::path::RenderablePath;
+
+/// A glyph placed by [`LayoutEngine`]; draw its mesh translated by `(x, y)`.
+#[derive(Clone, Copy, Debug)]
+pub struct PositionedGlyph<'a> {
+    pub glyph: &'a Glyph,
+    pub x: f32,
+    pub y: f32,
+    /// Byte offset in the text of the first character the glyph stands for.
+    pub cluster: usize,
+}
+
+/// What [`LayoutEngine::layout_paragraph`] does with a word wider than a whole line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineOverflow {
+    /// Drop the glyphs that end past the line width.
+    Truncate,
+    /// Let the word stick out past the line width.
+    #[default]
+    Overflow,
+}
+
+pub struct LayoutEngine<'a> {
+    pub font: &'a Font,
+}
+
+impl<'a> LayoutEngine<'a> {
+    pub fn new(font: &'a Font) -> Self {
+        LayoutEngine { font }
+    }
+
+    /// Lays `text` out top to bottom as a vertical (CJK) column centered on `x`, with the top
+    /// of the first glyph's em box at `y`. Glyphs without vertical metrics advance by the
+    /// font's line height and hang from its ascender. Characters missing from the font are
+    /// skipped.
+    pub fn layout_vertical_column(&self, text: &str, x: f32, y: f32) -> Vec<PositionedGlyph<'a>> {
+        let font = self.font;
+        let mut pen = y;
+        let mut placed = Vec::new();
+        for (cluster, ch) in text.char_indices() {
+            let glyph = match font.glyph(ch) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            let origin_y = glyph.v_origin_y.unwrap_or(font.ascender);
+            placed.push(PositionedGlyph {
+                glyph,
+                x: x - glyph.advance / 2.0,
+                y: pen - origin_y,
+                cluster,
+            });
+            pen -= font.vertical_advance(ch).unwrap_or(font.ascender - font.descender);
+        }
+        placed
+    }
+
+    /// Breaks `text` into lines at most `max_width` wide like [`layout_text_wrapped`], except
+    /// that a word wider than a whole line is kept whole and handled by `overflow`, and
+    /// returns the glyph meshes of each line. Line `i` starts at x = 0 with its baseline at
+    /// `y = -i * line_height`. Prim_ids number the meshes of all lines in order, as in
+    /// [`layout_text`].
+    pub fn layout_paragraph(&self, text: &str, max_width: f32, line_height: f32, overflow: LineOverflow) -> Vec<Vec<RenderablePath>> {
+        let options = TextOptions::default();
+        let mut paths = Vec::new();
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let line = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+            let bidi = BidiInfo::new(line, None);
+            for range in wrap_line(line, max_width, false, |range| visual_line(self.font, &bidi, range, &options).width) {
+                let y = -(lines.len() as f32) * line_height;
+                let mut glyphs = visual_line(self.font, &bidi, range, &options).glyphs;
+                if overflow == LineOverflow::Truncate {
+                    glyphs.retain(|g| g.x + g.glyph.advance <= max_width);
+                }
+                let first = paths.len();
+                push_run(&mut paths, &glyphs, (0.0, y));
+                lines.push(first..paths.len());
+            }
+        }
+        let mut paths = paths.into_iter();
+        lines.into_iter().map(|line| paths.by_ref().take(line.len()).collect()).collect()
+    }
+}
+
+/// What [`layout_text`] and its siblings lay text out with: a single [`Font`], or a
+/// [`FontChain`] that falls back between fonts per character.
+pub trait GlyphSource {
+    /// Glyphs of `text` on a single line starting at x = 0, see [`Font::glyph_run`].
+    fn glyph_run(&self, text: &str, ligatures: bool) -> Vec<RunGlyph<'_>>;
+    /// `(ascender, descender, line_gap)` used to stack lines.
+    fn line_metrics(&self) -> (f32, f32, f32);
+}
+
+impl GlyphSource for Font {
+    fn glyph_run(&self, text: &str, ligatures: bool) -> Vec<RunGlyph<'_>> {
+        Font::glyph_run(self, text, ligatures)
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        (self.ascender, self.descender, self.line_gap)
+    }
+}
+
+/// Lines are stacked with the primary font's metrics; an empty chain has zero metrics.
+impl GlyphSource for FontChain {
+    fn glyph_run(&self, text: &str, ligatures: bool) -> Vec<RunGlyph<'_>> {
+        FontChain::glyph_run(self, text, ligatures)
+    }
+
+    fn line_metrics(&self) -> (f32, f32, f32) {
+        self.primary().map_or((0.0, 0.0, 0.0), Font::line_metrics)
+    }
+}
+
+/// Size of a block of text as laid out by [`layout_text`], relative to the origin of its
+/// first line. Y points up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    /// Width of the widest line, trailing spaces included.
+    pub advance_width: f32,
+    /// `((x_min, y_min), (x_max, y_max))` of the drawn outlines; `None` if nothing is drawn.
+    pub ink_bounds: Option<((f32, f32), (f32, f32))>,
+    /// Top of the first line, the font's ascender.
+    pub ascent: f32,
+    /// Bottom of the last line: the font's descender, lowered by the line height for every
+    /// line after the first. Negative.
+    pub descent: f32,
+    pub line_count: usize,
+}
+
+/// Base direction of a paragraph, which orders its mixed-direction runs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// From the first strong character, left to right if there is none.
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+/// How [`layout_text_with_options`], [`measure_text_with_options`] and
+/// [`layout_text_wrapped`] place glyphs.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextOptions {
+    /// Only used by [`layout_text_wrapped`], which has a box to align lines in.
+    pub align: Align,
+    pub direction: Direction,
+    /// Space added after every glyph but the last of a line, on top of kerning; in output
+    /// units like `Glyph::advance`, so `0.02 * font.em_size` is 2% tracking. May be negative,
+    /// but no glyph's advance shrinks below zero.
+    pub letter_spacing: f32,
+    /// Space added to the advance of whitespace characters, in the same units and with the
+    /// same limit as `letter_spacing`.
+    pub word_spacing: f32,
+}
+
+/// Meshes of `text` with the baseline of its first line starting at `origin`, kerned and with
+/// ligatures, ready to draw like loaded SVG paths. Every `\n` starts a new line one
+/// `ascender - descender + line_gap` lower. Lines are ordered with the Unicode bidi algorithm,
+/// each paragraph's direction detected from its text, and right-to-left runs are laid out
+/// with the pen moving leftwards. Glyph `i` of the result gets prim_id `i`; glyphs without
+/// an outline, such as spaces, only advance the pen. Characters requested at load time but
+/// missing from the face follow `FontOptions::missing_glyph`; characters never loaded are
+/// skipped. With a [`FontChain`], each character comes from the first font that has it.
+pub fn layout_text(font: &impl GlyphSource, text: &str, origin: (f32, f32)) -> Vec<RenderablePath> {
+    layout_text_with_options(font, text, origin, &TextOptions::default())
+}
+
+/// Like [`layout_text`], with the base direction and spacing of `options`.
+pub fn layout_text_with_options(font: &impl GlyphSource, text: &str, origin: (f32, f32), options: &TextOptions) -> Vec<RenderablePath> {
+    let mut paths = Vec::new();
+    for (line, y) in text_lines(font, text, options) {
+        push_run(&mut paths, &line.glyphs, (origin.0, origin.1 + y));
+    }
+    paths
+}
+
+/// Measures `text` as [`layout_text`] places it, without building meshes.
+pub fn measure_text(font: &impl GlyphSource, text: &str) -> TextMetrics {
+    measure_text_with_options(font, text, &TextOptions::default())
+}
+
+/// Measures `text` as [`layout_text_with_options`] places it with `options`.
+pub fn measure_text_with_options(font: &impl GlyphSource, text: &str, options: &TextOptions) -> TextMetrics {
+    let (ascender, descender, _) = font.line_metrics();
+    let mut metrics = TextMetrics { ascent: ascender, ..TextMetrics::default() };
+    let mut bottom = 0.0;
+    for (line, y) in text_lines(font, text, options) {
+        metrics.line_count += 1;
+        bottom = y;
+        metrics.advance_width = metrics.advance_width.max(line.width);
+        for g in line.glyphs.iter().filter(|g| is_drawn(g)) {
+            let (x0, y0, x1, y1) = g.glyph.bbox;
+            let (min, max) = ((g.x + x0, y + g.y + y0), (g.x + x1, y + g.y + y1));
+            metrics.ink_bounds = Some(match metrics.ink_bounds {
+                Some((lo, hi)) => ((lo.0.min(min.0), lo.1.min(min.1)), (hi.0.max(max.0), hi.1.max(max.1))),
+                None => (min, max),
+            });
+        }
+    }
+    metrics.descent = bottom + descender;
+    metrics
+}
+
+/// A line of [`WrappedText`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLine {
+    /// Byte range of the line in the laid out text, without the whitespace at its break.
+    pub text: Range<usize>,
+    /// Range of the line's meshes in `WrappedText::paths`.
+    pub glyphs: Range<usize>,
+    /// Advance width of the line from its origin, including any justification.
+    pub width: f32,
+    /// Origin of the line on its baseline.
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Horizontal placement of the lines of [`layout_text_wrapped`] in the box `max_width` wide
+/// starting at the origin.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    /// With `ink_edge`, the rightmost outline of each line touches the right edge of the box;
+    /// otherwise the end of its last advance does.
+    Right { ink_edge: bool },
+    /// Widens the word gaps so lines fill the box, except the last line of each paragraph
+    /// and lines without a gap, which stay left aligned.
+    Justify,
+}
+
+/// Glyph meshes of wrapped text with the lines they form, see [`layout_text_wrapped`].
+#[derive(Clone, Debug, Default)]
+pub struct WrappedText {
+    pub paths: Vec<RenderablePath>,
+    /// Byte offset in the text of the first character each mesh of `paths` stands for, e.g.
+    /// to map a caret position in logical order to the glyphs around it.
+    pub clusters: Vec<usize>,
+    pub lines: Vec<TextLine>,
+}
+
+/// Like [`layout_text`], also breaking lines wider than `max_width`. Lines break after the
+/// last word that fits, at whitespace, which is dropped and not counted in the line width.
+/// A word wider than a whole line is broken between characters, keeping combining marks with
+/// their base; only a line of a single such character can exceed `max_width`. Lines are
+/// broken in logical order, then reordered for display following `options.direction` and
+/// placed in the box according to `options.align`. Letter and word spacing count towards the
+/// line widths.
+pub fn layout_text_wrapped(font: &impl GlyphSource, text: &str, origin: (f32, f32), max_width: f32, options: &TextOptions) -> WrappedText {
+    let (ascender, descender, line_gap) = font.line_metrics();
+    let line_height = ascender - descender + line_gap;
+    let mut wrapped = WrappedText::default();
+    let mut offset = 0;
+    for paragraph in text.split('\n') {
+        let line = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+        let bidi = BidiInfo::new(line, base_level(options.direction));
+        let ranges = wrap_line(line, max_width, true, |range| visual_line(font, &bidi, range, options).width);
+        let last = ranges.len() - 1;
+        for (i, range) in ranges.into_iter().enumerate() {
+            let y = origin.1 - wrapped.lines.len() as f32 * line_height;
+            let mut visual = visual_line(font, &bidi, range.clone(), options);
+            let shift = match options.align {
+                Align::Left | Align::Justify => 0.0,
+                Align::Center => (max_width - visual.width) / 2.0,
+                Align::Right { ink_edge: false } => max_width - visual.width,
+                Align::Right { ink_edge: true } => max_width - ink_right(&visual.glyphs).unwrap_or(visual.width),
+            };
+            if options.align == Align::Justify && i != last {
+                justify(&mut visual, line, max_width);
+            }
+            let first = wrapped.paths.len();
+            push_run(&mut wrapped.paths, &visual.glyphs, (origin.0 + shift, y));
+            wrapped.clusters.extend(visual.glyphs.iter().filter(|g| is_drawn(g)).map(|g| offset + g.cluster));
+            wrapped.lines.push(TextLine {
+                text: offset + range.start..offset + range.end,
+                glyphs: first..wrapped.paths.len(),
+                width: visual.width,
+                x: origin.0 + shift,
+                y,
+            });
+        }
+        offset += paragraph.len() + 1;
+    }
+    wrapped
+}
+
+/// Glyphs of a line in display order, see [`visual_line`].
+struct VisualLine<'a> {
+    /// Glyphs with pen positions from the left end of the line and `cluster` as byte offsets
+    /// in the paragraph.
+    glyphs: Vec<RunGlyph<'a>>,
+    /// Index of the bidi run of each glyph.
+    runs: Vec<usize>,
+    width: f32,
+}
+
+/// Lays out `line`, a byte range of the text `bidi` was built for: its runs of one direction
+/// are spaced and reordered for display, and right-to-left runs are mirrored so their pen
+/// moves leftwards while marks keep their offsets from their bases. A line spanning several
+/// bidi paragraphs, split by separators such as CR or U+2029, lays them out one after another.
+fn visual_line<'a>(font: &'a impl GlyphSource, bidi: &BidiInfo, line: Range<usize>, options: &TextOptions) -> VisualLine<'a> {
+    let mut visual = VisualLine { glyphs: Vec::new(), runs: Vec::new(), width: 0.0 };
+    let line_end = line.end.min(bidi.text.len());
+    let paragraphs = bidi.paragraphs.iter()
+        .map(|para| (para, line.start.max(para.range.start)..line_end.min(para.range.end)))
+        .filter(|(_, range)| !range.is_empty());
+    let mut run_index = 0;
+    for (para, range) in paragraphs {
+        let (levels, runs) = bidi.visual_runs(para, range);
+        for run in runs {
+            let text = &bidi.text[run.clone()];
+            let mut glyphs = font.glyph_run(text, true);
+            let (advances, width) = apply_spacing(&mut glyphs, text, options, run.end == line_end);
+            if levels[run.start].is_rtl() {
+                let mut base = (0.0, 0.0);
+                for (g, advance) in glyphs.iter_mut().zip(advances) {
+                    if g.attached {
+                        g.x = base.1 + (g.x - base.0);
+                    } else {
+                        base = (g.x, width - g.x - advance);
+                        g.x = base.1;
+                    }
+                }
+            }
+            for mut g in glyphs {
+                g.x += visual.width;
+                g.cluster += run.start;
+                visual.glyphs.push(g);
+                visual.runs.push(run_index);
+            }
+            visual.width += width;
+            run_index += 1;
+        }
+    }
+    visual
+}
+
+/// Moves the glyphs of `run`, the glyph run of `text`, apart by the letter and word spacing of
+/// `options` in logical order. With `ends_line` the last glyph is the last of its line and
+/// gets no letter spacing. Returns each glyph's advance with its spacing, 0 for attached
+/// marks, and the width of the run.
+fn apply_spacing(run: &mut [RunGlyph], text: &str, options: &TextOptions, ends_line: bool) -> (Vec<f32>, f32) {
+    let last = run.iter().rposition(|g| !g.attached);
+    let mut advances = Vec::with_capacity(run.len());
+    let (mut shift, mut base_shift) = (0.0, 0.0);
+    for (i, g) in run.iter_mut().enumerate() {
+        if g.attached {
+            g.x += base_shift;
+            advances.push(0.0);
+            continue;
+        }
+        g.x += shift;
+        base_shift = shift;
+        let mut extra = 0.0;
+        if !(ends_line && Some(i) == last) {
+            extra += options.letter_spacing;
+        }
+        if text[g.cluster..].starts_with(char::is_whitespace) {
+            extra += options.word_spacing;
+        }
+        let extra = extra.max(-g.glyph.advance);
+        advances.push(g.glyph.advance + extra);
+        shift += extra;
+    }
+    let width = last.map_or(0.0, |i| run[i].x + advances[i]);
+    (advances, width)
+}
+
+fn base_level(direction: Direction) -> Option<Level> {
+    match direction {
+        Direction::Auto => None,
+        Direction::Ltr => Some(Level::ltr()),
+        Direction::Rtl => Some(Level::rtl()),
+    }
+}
+
+/// Right edge of the outlines of `run`; `None` if no glyph has one.
+fn ink_right(run: &[RunGlyph]) -> Option<f32> {
+    run.iter()
+        .filter(|g| is_drawn(g))
+        .map(|g| g.x + g.glyph.bbox.2)
+        .reduce(f32::max)
+}
+
+/// Widens `line`, a line of the paragraph `text`, to `max_width` by spreading the missing
+/// width evenly over its word gaps: every glyph moves right by a share for each gap left of
+/// it. Gaps are whitespace glyphs, or whitespace skipped between neighbouring glyphs of one
+/// bidi run.
+fn justify(line: &mut VisualLine, text: &str, max_width: f32) {
+    let extra = max_width - line.width;
+    if extra <= 0.0 {
+        return;
+    }
+    let glyphs = &line.glyphs;
+    let mut order: Vec<usize> = (0..glyphs.len()).filter(|&i| !glyphs[i].attached).collect();
+    order.sort_by(|&a, &b| glyphs[a].x.total_cmp(&glyphs[b].x));
+
+    let is_space = |cluster: usize| text[cluster..].chars().next().is_some_and(char::is_whitespace);
+    let mut gaps_left = vec![0; glyphs.len()];
+    let (mut gaps, mut seen_word, mut in_gap) = (0, false, false);
+    let mut prev: Option<usize> = None;
+    for &i in &order {
+        let skipped = prev.is_some_and(|p| {
+            let (lo, hi) = (glyphs[p].cluster.min(glyphs[i].cluster), glyphs[p].cluster.max(glyphs[i].cluster));
+            line.runs[p] == line.runs[i] && text[lo..hi].chars().skip(1).any(char::is_whitespace)
+        });
+        let space = is_space(glyphs[i].cluster);
+        if (space || skipped) && seen_word {
+            in_gap = true;
+        }
+        if !space {
+            if in_gap {
+                gaps += 1;
+                in_gap = false;
+            }
+            seen_word = true;
+        }
+        gaps_left[i] = gaps;
+        prev = Some(i);
+    }
+    if gaps == 0 {
+        return;
+    }
+
+    let per_gap = extra / gaps as f32;
+    let mut base_gaps = 0;
+    for (g, &left) in line.glyphs.iter_mut().zip(&gaps_left) {
+        if !g.attached {
+            base_gaps = left;
+        }
+        g.x += base_gaps as f32 * per_gap;
+    }
+    line.width += extra;
+}
+
+/// Byte ranges of the lines `line` wraps into at `max_width`, with `width` measuring a range;
+/// see [`layout_text_wrapped`]. Without `break_words`, a word wider than a line gets a line of
+/// its own instead of being broken between characters.
+fn wrap_line(line: &str, max_width: f32, break_words: bool, width: impl Fn(Range<usize>) -> f32) -> Vec<Range<usize>> {
+    let fits = |range: Range<usize>| width(range) <= max_width;
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut end = None;
+    for word in words(line) {
+        if fits(start..word.end) {
+            end = Some(word.end);
+            continue;
+        }
+        if let Some(end) = end {
+            lines.push(start..end);
+            start = word.start;
+        }
+        // Break a word that does not fit on a line of its own between characters.
+        while break_words && !fits(start..word.end) {
+            let breaks: Vec<usize> = line[start..word.end].char_indices()
+                .filter(|&(i, ch)| i > 0 && !unicode_normalization::char::is_combining_mark(ch))
+                .map(|(i, _)| start + i)
+                .collect();
+            let last_fitting = breaks.iter().rev().find(|&&b| fits(start..b));
+            match last_fitting.or(breaks.first()) {
+                Some(&at) => {
+                    lines.push(start..at);
+                    start = at;
+                }
+                None => break,
+            }
+        }
+        end = Some(word.end);
+    }
+    lines.push(start..end.unwrap_or(start));
+    lines
+}
+
+/// Byte ranges of the whitespace-separated words of `line`.
+fn words(line: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    line.split(char::is_whitespace)
+        .filter(|word| !word.is_empty())
+        .map(move |word| {
+            let start = word.as_ptr() as usize - line.as_ptr() as usize;
+            start..start + word.len()
+        })
+}
+
+/// Whether `g` has an outline and so a mesh in the layout.
+fn is_drawn(g: &RunGlyph) -> bool {
+    !g.glyph.outline.indices.is_empty()
+}
+
+/// Appends the meshes of the glyphs of `run` with outlines, placed at `origin`, with prim_ids
+/// continuing from the meshes already in `paths`.
+fn push_run(paths: &mut Vec<RenderablePath>, run: &[RunGlyph], origin: (f32, f32)) {
+    for g in run.iter().filter(|g| is_drawn(g)) {
+        let prim_id = paths.len() as u32;
+        let mut mesh = g.glyph.outline.clone();
+        for v in &mut mesh.vertices {
+            *v = v.offset_position(origin.0 + g.x, origin.1 + g.y).with_prim_id(prim_id);
+        }
+        paths.push(RenderablePath::new((1, 1), mesh));
+    }
+}
+
+/// The `\n`-separated lines of `text` in display order with the baseline y of each, relative
+/// to the first.
+fn text_lines<'a>(font: &'a impl GlyphSource, text: &'a str, options: &'a TextOptions) -> impl Iterator<Item = (VisualLine<'a>, f32)> {
+    let (ascender, descender, line_gap) = font.line_metrics();
+    let line_height = ascender - descender + line_gap;
+    text.split('\n').enumerate().map(move |(i, line)| {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let bidi = BidiInfo::new(line, base_level(options.direction));
+        (visual_line(font, &bidi, 0..line.len(), options), -(i as f32) * line_height)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::fixtures::DEJAVU_SANS;
+    use crate::ttfload::{load_font, load_font_with_options, FontOptions};
+
+    fn chain() -> FontChain {
+        let primary = load_font(DEJAVU_SANS, "ab ").unwrap();
+        let options = FontOptions { em_size: 2.0 * primary.em_size, ..FontOptions::default() };
+        primary.with_fallback(load_font_with_options(DEJAVU_SANS, "c", &options).unwrap())
+    }
+
+    #[test]
+    fn chains_are_measured_and_laid_out_with_their_fallbacks() {
+        let chain = chain();
+        let metrics = measure_text(&chain, "ab c");
+        let alone = measure_text(&chain.fonts[0], "ab ");
+        assert!((metrics.advance_width - (alone.advance_width + chain.glyph_for_char('c').advance)).abs() < 1e-4);
+        assert_eq!(metrics.ascent, chain.fonts[0].ascender);
+
+        let paths = layout_text(&chain, "ab c", (0.0, 0.0));
+        assert_eq!(paths.len(), 3);
+        let ids: Vec<u32> = paths.iter().map(|p| p.vertices.vertices[0].prim_id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn chain_lines_are_stacked_with_the_primary_metrics() {
+        let chain = chain();
+        let font = &chain.fonts[0];
+        let metrics = measure_text(&chain, "a\nc");
+        let line_height = font.ascender - font.descender + font.line_gap;
+        assert_eq!(metrics.line_count, 2);
+        assert!((metrics.descent - (font.descender - line_height)).abs() < 1e-5);
+        assert_eq!(measure_text(&FontChain::default(), "ab").advance_width, 0.0);
+    }
+
+    fn line_bounds(line: &[RenderablePath]) -> (f32, f32, f32, f32) {
+        line.iter().flat_map(|p| &p.vertices.vertices).fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |b, v| {
+            (b.0.min(v.position[0]), b.1.min(v.position[1]), b.2.max(v.position[0]), b.3.max(v.position[1]))
+        })
+    }
+
+    #[test]
+    fn paragraphs_wrap_like_wrapped_text() {
+        let font = load_font(DEJAVU_SANS, "abcdefghijklmnopqrstuvwxyz \t").unwrap();
+        let text = "the quick brown fox\tjumps over the lazy dog";
+        let max_width = measure_text(&font, "the quick brown").advance_width + 0.01;
+        let lines = LayoutEngine::new(&font).layout_paragraph(text, max_width, 2.0, LineOverflow::Overflow);
+        let wrapped = layout_text_wrapped(&font, text, (0.0, 0.0), max_width, &TextOptions::default());
+        assert_eq!(lines.len(), wrapped.lines.len());
+        for (line, wrapped_line) in lines.iter().zip(&wrapped.lines) {
+            assert_eq!(line.len(), wrapped_line.glyphs.len());
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let (x_min, y_min, x_max, _) = line_bounds(line);
+            assert!(x_min >= 0.0 && x_max <= max_width, "line {} spans {}..{}", i, x_min, x_max);
+            assert!(y_min > -(i as f32) * 2.0 - 0.5 && y_min < -(i as f32) * 2.0, "line {} at {}", i, y_min);
+        }
+        let ids: Vec<u32> = lines.iter().flatten().map(|p| p.vertices.vertices[0].prim_id).collect();
+        assert_eq!(ids, (0..ids.len() as u32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn newlines_always_break_and_empty_lines_are_kept() {
+        let font = load_font(DEJAVU_SANS, "ab").unwrap();
+        let lines = LayoutEngine::new(&font).layout_paragraph("a\r\n\nb", 100.0, 1.5, LineOverflow::Overflow);
+        assert_eq!(lines.iter().map(Vec::len).collect::<Vec<_>>(), [1, 0, 1]);
+        assert!((line_bounds(&lines[2]).1 + 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn long_words_overflow_or_are_truncated() {
+        let font = load_font(DEJAVU_SANS, "w ").unwrap();
+        let engine = LayoutEngine::new(&font);
+        let width = 2.5 * font.glyph('w').unwrap().advance;
+        let overflow = engine.layout_paragraph("w wwwww w", width, 1.0, LineOverflow::Overflow);
+        assert_eq!(overflow.iter().map(Vec::len).collect::<Vec<_>>(), [1, 5, 1]);
+        assert!(line_bounds(&overflow[1]).2 > width);
+
+        let truncated = engine.layout_paragraph("w wwwww w", width, 1.0, LineOverflow::Truncate);
+        assert_eq!(truncated.iter().map(Vec::len).collect::<Vec<_>>(), [1, 2, 1]);
+        assert!(line_bounds(&truncated[1]).2 <= width);
+    }
+
+    #[test]
+    fn glyphs_are_placed_at_their_kerned_pen_positions() {
+        let font = load_font(DEJAVU_SANS, "AV ").unwrap();
+        let advance = |ch| font.glyph(ch).unwrap().advance;
+        assert!(font.kerning('A', 'V') < 0.0);
+        let kerned = advance('A') + advance('V') + font.kerning('A', 'V') + font.kerning('V', 'A');
+        assert!((measure_text(&font, "AVA").advance_width - (kerned + advance('A'))).abs() < 1e-5);
+
+        let origin = (10.0, -3.0);
+        let paths = layout_text(&font, "AVA", origin);
+        assert_eq!(paths.len(), 3);
+        let outline = &font.glyph('A').unwrap().outline;
+        for (path, x) in [(&paths[0], origin.0), (&paths[2], origin.0 + kerned)] {
+            assert_eq!(path.vertices.indices, outline.indices);
+            for (v, expected) in path.vertices.vertices.iter().zip(&outline.vertices) {
+                assert!((v.position[0] - (expected.position[0] + x)).abs() < 1e-4);
+                assert!((v.position[1] - (expected.position[1] + origin.1)).abs() < 1e-4);
+            }
+        }
+        let ids: Vec<u32> = paths.iter().map(|p| p.vertices.vertices[0].prim_id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn spaces_and_unloaded_characters_only_advance_or_are_skipped() {
+        let font = load_font(DEJAVU_SANS, "A ").unwrap();
+        let paths = layout_text(&font, "A Aq", (0.0, 0.0));
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[1].vertices.vertices[0].prim_id, 1);
+        let shift = paths[1].vertices.vertices[0].position[0] - paths[0].vertices.vertices[0].position[0];
+        let expected = font.glyph('A').unwrap().advance + font.glyph(' ').unwrap().advance;
+        assert!((shift - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn measurements_match_the_layout() {
+        // Straight-edged letters, so the outline bboxes are exactly the mesh bounds.
+        let font = load_font(DEJAVU_SANS, "EHILT ").unwrap();
+        let text = "HIT  \nLE\n \nTI ";
+        let metrics = measure_text(&font, text);
+        let paths = layout_text(&font, text, (0.0, 0.0));
+        let (x_min, y_min, x_max, y_max) = line_bounds(&paths);
+        let ((ink_x0, ink_y0), (ink_x1, ink_y1)) = metrics.ink_bounds.unwrap();
+        for (measured, laid_out) in [(ink_x0, x_min), (ink_y0, y_min), (ink_x1, x_max), (ink_y1, y_max)] {
+            assert!((measured - laid_out).abs() < 1e-4, "{} vs {}", measured, laid_out);
+        }
+
+        let line_height = font.ascender - font.descender + font.line_gap;
+        assert_eq!(metrics.line_count, 4);
+        assert_eq!(metrics.ascent, font.ascender);
+        assert!((metrics.descent - (font.descender - 3.0 * line_height)).abs() < 1e-4);
+
+        // Trailing spaces count: a glyph appended to the widest line starts at its width.
+        let widest = measure_text(&font, "HIT  ").advance_width;
+        assert_eq!(metrics.advance_width, widest);
+        let appended = layout_text(&font, "HIT  I", (0.0, 0.0));
+        let i = &font.glyph('I').unwrap().outline.vertices[0];
+        assert!((appended[3].vertices.vertices[0].position[0] - i.position[0] - widest).abs() < 1e-5);
+
+        assert_eq!(measure_text(&font, " ").ink_bounds, None);
+        assert_eq!(measure_text(&font, "").line_count, 1);
+    }
+
+    fn wrap(font: &Font, text: &str, max_width: f32) -> WrappedText {
+        layout_text_wrapped(font, text, (0.0, 0.0), max_width, &TextOptions::default())
+    }
+
+    #[test]
+    fn words_wrap_at_whitespace_without_counting_it() {
+        let font = load_font(DEJAVU_SANS, "a ").unwrap();
+        let pair = measure_text(&font, "aa aa").advance_width;
+        let wrapped = wrap(&font, "aa aa   aa aa aa", pair + 1e-3);
+        let texts: Vec<_> = wrapped.lines.iter().map(|l| l.text.clone()).collect();
+        assert_eq!(texts, [0..5, 8..13, 14..16]);
+        let glyphs: Vec<_> = wrapped.lines.iter().map(|l| l.glyphs.clone()).collect();
+        assert_eq!(glyphs, [0..4, 4..8, 8..10]);
+        assert!((wrapped.lines[0].width - pair).abs() < 1e-5);
+        assert!((wrapped.lines[1].width - pair).abs() < 1e-5);
+        let line_height = font.ascender - font.descender + font.line_gap;
+        for (i, line) in wrapped.lines.iter().enumerate() {
+            assert!(line.width <= pair + 1e-3);
+            assert!((line.y + i as f32 * line_height).abs() < 1e-5);
+            assert_eq!(line.x, 0.0);
+        }
+        assert_eq!(wrapped.paths.len(), 10);
+        assert_eq!(wrapped.clusters[4], 8);
+    }
+
+    #[test]
+    fn long_words_break_between_characters() {
+        let font = load_font(DEJAVU_SANS, "aW e\u{301}").unwrap();
+        let a = font.glyph('a').unwrap().advance;
+        let wrapped = wrap(&font, "aaaaaaa", 2.5 * a);
+        assert_eq!(wrapped.lines.iter().map(|l| l.glyphs.len()).collect::<Vec<_>>(), [2, 2, 2, 1]);
+        assert!(wrapped.lines.iter().all(|l| l.width <= 2.5 * a));
+
+        // A single character wider than the line is the only overflow allowed.
+        let w = font.glyph('W').unwrap().advance;
+        let wrapped = wrap(&font, "WW", w / 2.0);
+        assert_eq!(wrapped.lines.len(), 2);
+        assert!(wrapped.lines.iter().all(|l| (l.width - w).abs() < 1e-5));
+
+        // Combining marks stay with their base.
+        let e = font.glyph('e').unwrap().advance;
+        let wrapped = wrap(&font, "e\u{301}e\u{301}", 1.5 * e);
+        let texts: Vec<_> = wrapped.lines.iter().map(|l| l.text.clone()).collect();
+        assert_eq!(texts, [0..3, 3..6]);
+    }
+
+    #[test]
+    fn lines_are_aligned_in_the_box() {
+        let font = load_font(DEJAVU_SANS, "a ").unwrap();
+        let a = font.glyph('a').unwrap();
+        let max_width = measure_text(&font, "aa aa").advance_width + 0.1 * a.advance;
+        let text = "aa aa a aa a";
+        let widths = ["aa aa", "a aa", "a"].map(|line| measure_text(&font, line).advance_width);
+        let aligned = |align| {
+            let wrapped = layout_text_wrapped(&font, text, (0.0, 0.0), max_width, &TextOptions { align, ..TextOptions::default() });
+            assert_eq!(wrapped.lines.len(), 3);
+            wrapped
+        };
+        let offsets = |align| aligned(align).lines.iter().map(|l| l.x).collect::<Vec<_>>();
+        let expect = |actual: Vec<f32>, expected: [f32; 3]| {
+            for (x, e) in actual.iter().zip(expected) {
+                assert!((x - e).abs() < 1e-5, "{:?} vs {:?}", actual, expected);
+            }
+        };
+
+        expect(offsets(Align::Left), [0.0; 3]);
+        expect(offsets(Align::Center), widths.map(|w| (max_width - w) / 2.0));
+        expect(offsets(Align::Right { ink_edge: false }), widths.map(|w| max_width - w));
+        // The last glyph of every line is an 'a', so its ink ends short of the advance.
+        expect(offsets(Align::Right { ink_edge: true }), widths.map(|w| max_width - (w - a.advance + a.bbox.2)));
+
+        let justified = aligned(Align::Justify);
+        expect(justified.lines.iter().map(|l| l.x).collect(), [0.0; 3]);
+        expect(justified.lines.iter().map(|l| l.width).collect(), [max_width, max_width, widths[2]]);
+        let left = aligned(Align::Left);
+        let first_x = |wrapped: &WrappedText, i: usize| wrapped.paths[i].vertices.vertices[0].position[0];
+        // Glyphs after the gap of the first two lines move by the whole extra width.
+        assert!((first_x(&justified, 0) - first_x(&left, 0)).abs() < 1e-5);
+        assert!((first_x(&justified, 3) - first_x(&left, 3) - (max_width - widths[0])).abs() < 1e-5);
+        assert!((first_x(&justified, 6) - first_x(&left, 6) - (max_width - widths[1])).abs() < 1e-5);
+        assert!((first_x(&justified, 7) - first_x(&left, 7)).abs() < 1e-5);
+    }
+
+    /// Left edge of the mesh of each character of `text` laid out with `options`, keyed by
+    /// its byte offset.
+    fn glyph_x(font: &Font, text: &str, options: &TextOptions) -> HashMap<usize, f32> {
+        let wrapped = layout_text_wrapped(font, text, (0.0, 0.0), f32::MAX, options);
+        wrapped.paths.iter().zip(&wrapped.clusters)
+            .map(|(path, &cluster)| (cluster, path.vertices.vertices.iter().map(|v| v.position[0]).fold(f32::MAX, f32::min)))
+            .collect()
+    }
+
+    #[test]
+    fn right_to_left_runs_are_laid_out_leftwards() {
+        let font = load_font(DEJAVU_SANS, "שלוםworld ").unwrap();
+        let hebrew = "שלום";
+        let starts: Vec<usize> = hebrew.char_indices().map(|(i, _)| i).collect();
+        let x = glyph_x(&font, hebrew, &TextOptions::default());
+        assert!(starts.windows(2).all(|w| x[&w[0]] > x[&w[1]]), "{:?}", x);
+
+        // An auto-detected right-to-left paragraph puts the English word on the left.
+        let mixed = "שלום world";
+        let x = glyph_x(&font, mixed, &TextOptions::default());
+        let world = mixed.find('w').unwrap();
+        assert!(x[&world] < x[&(world + 1)]);
+        assert!(starts.iter().all(|i| x[i] > x[&(world + 4)]));
+        assert!(x[&0] > x[&starts[3]]);
+
+        let ltr = TextOptions { direction: Direction::Ltr, ..TextOptions::default() };
+        let x = glyph_x(&font, mixed, &ltr);
+        assert!(starts.iter().all(|i| x[i] < x[&world]));
+        assert!(x[&0] > x[&starts[3]]);
+    }
+
+    #[test]
+    fn text_after_paragraph_separators_is_kept() {
+        let font = load_font(DEJAVU_SANS, "abcd").unwrap();
+        let expected = measure_text(&font, "ab").advance_width + measure_text(&font, "cd").advance_width;
+        for text in ["ab\rcd", "ab\u{85}cd", "ab\u{2029}cd"] {
+            let paths = layout_text(&font, text, (0.0, 0.0));
+            assert_eq!(paths.len(), 4, "{:?}", text);
+            let metrics = measure_text(&font, text);
+            assert_eq!(metrics.line_count, 1);
+            assert!((metrics.advance_width - expected).abs() < 1e-5, "{:?}", text);
+            let x = glyph_x(&font, text, &TextOptions::default());
+            assert!(x[&(text.len() - 1)] > x[&0]);
+        }
+    }
+
+    #[test]
+    fn spacing_widens_measurements_and_layout_alike() {
+        let font = load_font(DEJAVU_SANS, "AV c").unwrap();
+        let spaced = TextOptions { letter_spacing: 0.02 * font.em_size, word_spacing: 0.1 * font.em_size, ..TextOptions::default() };
+        let width = |text, options| measure_text_with_options(&font, text, options).advance_width;
+
+        // Kerning is kept, and the last glyph of the line gets no letter spacing.
+        let plain = TextOptions::default();
+        assert!((width("AVA", &spaced) - (width("AVA", &plain) + 2.0 * spaced.letter_spacing)).abs() < 1e-5);
+        let extra = 4.0 * spaced.letter_spacing + spaced.word_spacing;
+        assert!((width("AV cA", &spaced) - (width("AV cA", &plain) + extra)).abs() < 1e-5);
+        let multi_line = measure_text_with_options(&font, "AV cA\nAVA", &spaced);
+        assert!((multi_line.advance_width - width("AV cA", &spaced)).abs() < 1e-6);
+
+        let first_x = |paths: &[RenderablePath], i: usize| paths[i].vertices.vertices[0].position[0];
+        let plain_paths = layout_text(&font, "AV cA", (0.0, 0.0));
+        let spaced_paths = layout_text_with_options(&font, "AV cA", (0.0, 0.0), &spaced);
+        let shifts: Vec<f32> = (0..4).map(|i| first_x(&spaced_paths, i) - first_x(&plain_paths, i)).collect();
+        let l = spaced.letter_spacing;
+        for (shift, expected) in shifts.iter().zip([0.0, l, 3.0 * l + spaced.word_spacing, extra]) {
+            assert!((shift - expected).abs() < 1e-5, "{:?}", shifts);
+        }
+
+        // Wrapped and justified lines count the spacing too.
+        let max_width = width("AV cA", &spaced) + 0.05;
+        let wrapped = layout_text_wrapped(&font, "AV cA AVA", (0.0, 0.0), max_width, &spaced);
+        assert!((wrapped.lines[0].width - width("AV cA", &spaced)).abs() < 1e-5);
+        assert!((wrapped.lines[1].width - width("AVA", &spaced)).abs() < 1e-5);
+        let justified = TextOptions { align: Align::Justify, ..spaced };
+        let wrapped = layout_text_wrapped(&font, "AV cA AVA", (0.0, 0.0), max_width, &justified);
+        assert!((wrapped.lines[0].width - max_width).abs() < 1e-5);
+    }
+
+    #[test]
+    fn negative_spacing_never_makes_advances_negative() {
+        let font = load_font(DEJAVU_SANS, "AV c").unwrap();
+        let squeezed = TextOptions { letter_spacing: -10.0 * font.em_size, word_spacing: -10.0 * font.em_size, ..TextOptions::default() };
+        // Every advance but the last shrinks to zero, leaving the kerning and the last advance.
+        let metrics = measure_text_with_options(&font, "AV cA", &squeezed);
+        let collapsed: f32 = "AV c".chars().map(|ch| font.glyph(ch).unwrap().advance).sum();
+        let plain = measure_text(&font, "AV cA").advance_width;
+        assert!((metrics.advance_width - (plain - collapsed)).abs() < 1e-5);
+        assert!(metrics.advance_width > 0.0);
+        let wrapped = layout_text_wrapped(&font, "AV cA", (0.0, 0.0), f32::MAX, &squeezed);
+        assert!((wrapped.lines[0].width - metrics.advance_width).abs() < 1e-5);
+        assert_eq!(measure_text_with_options(&font, " ", &squeezed).advance_width, 0.0);
+    }
+}
@@ -1,4 +1,32 @@
 pub mod path;
 pub mod svgload;
 pub mod ttfload;
-pub mod font;
\ No newline at end of file
+pub mod font;
+pub mod fontface;
+pub mod fontcache;
+pub mod primitives;
+pub mod batch;
+pub mod layout;
+pub mod sdf;
+#[cfg(feature = "image")]
+pub mod raster;
+#[cfg(feature = "woff")]
+pub mod woff;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "tiny-skia")]
+pub mod skia;
+#[cfg(feature = "test-utils")]
+pub mod golden;
+
+#[cfg(feature = "fontdb")]
+pub mod sysfont;
+#[cfg(feature = "shaping")]
+pub mod shaping;
+
+#[cfg(test)]
+mod fixtures;
\ No newline at end of file
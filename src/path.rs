@@ -1,7 +1,31 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
 use lyon::tessellation::VertexBuffers;
 use usvg::{Color, LinearGradient, Transform};
 use serde::{Serialize, Deserialize};
 
+/// `col` as RGBA components in `[0, 1]`, with `opacity` as alpha.
+pub fn color_to_rgba(col: &Color, opacity: f32) -> [f32; 4] {
+    [col.red as f32 / 255.0, col.green as f32 / 255.0, col.blue as f32 / 255.0, opacity]
+}
+
+/// Paint of a primitive built from code rather than loaded from an SVG.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PaintSpec {
+    Color([f32; 4]),
+    LinearGradient(GradientSpec),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GradientSpec {
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+    pub positions: Vec<f32>,
+    pub colors: Vec<[f32; 4]>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct RenderablePath {
     pub size: (u32, u32),
@@ -12,25 +36,52 @@ pub struct RenderablePath {
     pub gradient_start: Option<(f32, f32)>,
     pub gradient_end: Option<(f32, f32)>,
     pub vertices: VertexBuffers<GpuVertex, u32>,
+    /// Draw order across paths, higher on top; see [`sort_by_z_index`]. usvg does not keep
+    /// CSS `z-index`, so loaded paths start at 0 and rely on document order.
+    #[serde(default)]
+    pub z_index: i32,
+    /// Opacity of the whole path, from the `opacity` of its SVG element and ancestors, applied
+    /// on top of the fill or stroke alpha in `bgcolor[3]` and the gradient stops.
+    #[serde(default = "full_opacity")]
+    pub path_opacity: f32,
+    /// Matrix (row-major, as for `transform_matrix`) from the space of `vertices` and the
+    /// gradient endpoints to the loader's mesh space, for paths loaded with
+    /// `LoadOptions::flatten_transforms` off; `None` when they are already in mesh space.
+    #[serde(default)]
+    pub local_transform: Option<[[f32; 3]; 3]>,
+}
+
+fn full_opacity() -> f32 {
+    1.0
+}
+
+/// `(x, y)` transformed by the affine matrix `m`, row-major with bottom row `[0, 0, 1]`.
+fn apply_matrix(m: &[[f32; 3]; 3], x: f32, y: f32) -> (f32, f32) {
+    (m[0][0] * x + m[0][1] * y + m[0][2], m[1][0] * x + m[1][1] * y + m[1][2])
 }
 
 impl RenderablePath {
+    #[must_use = "constructing a RenderablePath without using it is a bug"]
     pub fn from_color(size: (u32, u32), col: &Color, opacity: f32, mesh: VertexBuffers<GpuVertex, u32>) -> Self {
         RenderablePath {
             size,
-            bgcolor: [col.red as f32 / 256.0, col.green as f32 / 256.0, col.blue as f32 / 256.0, opacity],
+            bgcolor: color_to_rgba(col, opacity),
             gradient_stops: 0,
             gradient_colors: None,
             gradient_pos: None,
             gradient_start: None,
             gradient_end: None,
             vertices: mesh,
+            z_index: 0,
+            path_opacity: 1.0,
+            local_transform: None,
         }
     }
 
+    #[must_use = "constructing a RenderablePath without using it is a bug"]
     pub fn from_gradient(size: (u32, u32), g: &LinearGradient, mesh: VertexBuffers<GpuVertex, u32>, transform: &Transform) -> Self {
         let n = g.stops.len();
-        let mut t = g.transform.clone();
+        let mut t = g.transform;
         t.append(transform);
         let start = t.apply(g.x1, g.y1);
         let end = t.apply(g.x2, g.y2);
@@ -38,14 +89,36 @@ impl RenderablePath {
             size,
             bgcolor: [1.0, 1.0, 1.0, 1.0],
             gradient_stops: n as u8,
-            gradient_colors: Some(g.stops.iter().map(|s| [s.color.red as f32 / 256.0, s.color.green as f32 / 256.0, s.color.blue as f32 / 256.0, s.opacity.value() as f32]).collect()),
+            gradient_colors: Some(g.stops.iter().map(|s| color_to_rgba(&s.color, s.opacity.value() as f32)).collect()),
             gradient_pos: Some(g.stops.iter().map(|s| s.offset.value() as f32).collect()),
             gradient_start: Some((start.0 as f32, start.1 as f32)),
             gradient_end: Some((end.0 as f32, end.1 as f32)),
             vertices: mesh,
+            z_index: 0,
+            path_opacity: 1.0,
+            local_transform: None,
         }
     }
 
+    #[must_use = "constructing a RenderablePath without using it is a bug"]
+    pub fn from_paint(size: (u32, u32), paint: &PaintSpec, mesh: VertexBuffers<GpuVertex, u32>) -> Self {
+        match paint {
+            PaintSpec::Color(color) => RenderablePath {
+                bgcolor: *color,
+                ..RenderablePath::new(size, mesh)
+            },
+            PaintSpec::LinearGradient(g) => RenderablePath {
+                gradient_stops: g.positions.len() as u8,
+                gradient_pos: Some(g.positions.clone()),
+                gradient_colors: Some(g.colors.clone()),
+                gradient_start: Some(g.start),
+                gradient_end: Some(g.end),
+                ..RenderablePath::new(size, mesh)
+            },
+        }
+    }
+
+    #[must_use = "constructing a RenderablePath without using it is a bug"]
     pub fn new(size: (u32, u32), mesh: VertexBuffers<GpuVertex, u32>) -> Self {
         RenderablePath {
             size,
@@ -56,12 +129,583 @@ impl RenderablePath {
             gradient_start: None,
             gradient_end: None,
             vertices: mesh,
+            z_index: 0,
+            path_opacity: 1.0,
+            local_transform: None,
+        }
+    }
+
+    /// Paint of this path as a [`PaintSpec`]; paths with equal paints can share a draw call.
+    pub fn paint(&self) -> PaintSpec {
+        match (&self.gradient_pos, &self.gradient_colors) {
+            (Some(positions), Some(colors)) => PaintSpec::LinearGradient(GradientSpec {
+                start: self.gradient_start.unwrap_or((0.0, 0.0)),
+                end: self.gradient_end.unwrap_or((0.0, 0.0)),
+                positions: positions.clone(),
+                colors: colors.clone(),
+            }),
+            _ => PaintSpec::Color(self.bgcolor),
+        }
+    }
+
+    /// Gradient stops as `(position, color)` pairs, cut to the shorter of `gradient_pos` and
+    /// `gradient_colors`; `None` for solid-color paths.
+    pub fn gradient_color_stops(&self) -> Option<Vec<(f32, [f32; 4])>> {
+        match (&self.gradient_pos, &self.gradient_colors) {
+            (Some(positions), Some(colors)) => Some(positions.iter().copied().zip(colors.iter().copied()).collect()),
+            _ => None,
+        }
+    }
+
+    /// Same gradient running the other way: stops are reversed with positions mapped to
+    /// `1.0 - pos`. The endpoints are kept, since also swapping them would undo the reversal.
+    /// Paths without a gradient are returned unchanged.
+    pub fn flip_gradient(&self) -> Self {
+        let mut flipped = self.clone();
+        if let Some(pos) = &mut flipped.gradient_pos {
+            pos.reverse();
+            for p in pos.iter_mut() {
+                *p = 1.0 - *p;
+            }
+        }
+        if let Some(colors) = &mut flipped.gradient_colors {
+            colors.reverse();
         }
+        flipped
+    }
+
+    /// Copy with the RGB components of the color and of every gradient stop multiplied by
+    /// `factor` and clamped to `[0, 1]`, e.g. to darken a pressed button. Alpha is unchanged.
+    pub fn scale_colors(&self, factor: f32) -> Self {
+        let scale = |c: &mut [f32; 4]| {
+            for v in &mut c[..3] {
+                *v = (*v * factor).clamp(0.0, 1.0);
+            }
+        };
+        let mut scaled = self.clone();
+        scale(&mut scaled.bgcolor);
+        if let Some(colors) = &mut scaled.gradient_colors {
+            colors.iter_mut().for_each(scale);
+        }
+        scaled
+    }
+
+    /// Copy with `path_opacity` set to `opacity`; the fill alpha is left alone.
+    pub fn with_opacity(&self, opacity: f32) -> Self {
+        RenderablePath { path_opacity: opacity, ..self.clone() }
+    }
+
+    /// Copy painted with `gradient` instead of its current gradient, e.g. to animate the
+    /// direction or stops without tessellating again. `None` for solid-color paths.
+    pub fn with_gradient_replaced(&self, gradient: GradientSpec) -> Option<Self> {
+        self.gradient_pos.as_ref()?;
+        Some(RenderablePath {
+            gradient_stops: gradient.positions.len() as u8,
+            gradient_pos: Some(gradient.positions),
+            gradient_colors: Some(gradient.colors),
+            gradient_start: Some(gradient.start),
+            gradient_end: Some(gradient.end),
+            ..self.clone()
+        })
+    }
+
+    /// Applies the affine matrix `m` (row-major, bottom row `[0, 0, 1]`) to every vertex and
+    /// to the gradient endpoints, which live in the same space.
+    pub fn transform_matrix(&self, m: [[f32; 3]; 3]) -> Self {
+        let apply = |x: f32, y: f32| apply_matrix(&m, x, y);
+        let mut transformed = self.clone();
+        for v in &mut transformed.vertices.vertices {
+            let (x, y) = apply(v.position[0], v.position[1]);
+            v.position = [x, y];
+        }
+        transformed.gradient_start = self.gradient_start.map(|(x, y)| apply(x, y));
+        transformed.gradient_end = self.gradient_end.map(|(x, y)| apply(x, y));
+        transformed
+    }
+
+    /// This path with `local_transform` baked into its vertices and gradient endpoints, as if
+    /// loaded with `LoadOptions::flatten_transforms`; borrowed if they are in mesh space already.
+    pub fn flattened(&self) -> Cow<'_, RenderablePath> {
+        match &self.local_transform {
+            Some(m) => Cow::Owned(RenderablePath { local_transform: None, ..self.transform_matrix(*m) }),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// `(min, max)` corners of the vertices in mesh space, that is after `local_transform`;
+    /// `None` for an empty mesh.
+    pub fn bounding_box(&self) -> Option<((f32, f32), (f32, f32))> {
+        let mut positions = self.vertex_positions().map(|p| self.to_mesh_space(*p));
+        let first = positions.next()?;
+        let init = (first, first);
+        Some(positions.fold(init, |(min, max), p| {
+            ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1)))
+        }))
+    }
+
+    /// Whether the bounding box of the path overlaps the box `min`..`max` in mesh space, e.g.
+    /// the viewport. Conservatively `true` when either box is empty.
+    pub fn intersects_aabb(&self, min: (f32, f32), max: (f32, f32)) -> bool {
+        let (own_min, own_max) = match self.bounding_box() {
+            Some(b) => b,
+            None => return true,
+        };
+        if min.0 > max.0 || min.1 > max.1 {
+            return true;
+        }
+        own_min.0 <= max.0 && min.0 <= own_max.0 && own_min.1 <= max.1 && min.1 <= own_max.1
+    }
+
+    /// Mesh space position of the point `p` of the space of `vertices`.
+    pub fn to_mesh_space(&self, p: [f32; 2]) -> (f32, f32) {
+        match &self.local_transform {
+            Some(m) => apply_matrix(m, p[0], p[1]),
+            None => (p[0], p[1]),
+        }
+    }
+
+    /// Position in the space of `vertices` of the mesh space point `p`; `None` if
+    /// `local_transform` collapses the path to a line or a point.
+    pub fn to_vertex_space(&self, p: (f32, f32)) -> Option<[f32; 2]> {
+        let m = match &self.local_transform {
+            Some(m) => m,
+            None => return Some([p.0, p.1]),
+        };
+        let det = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (x, y) = (p.0 - m[0][2], p.1 - m[1][2]);
+        Some([(m[1][1] * x - m[0][1] * y) / det, (m[0][0] * y - m[1][0] * x) / det])
+    }
+
+    /// Positions of the vertices, in the space of `vertices`; see [`Self::to_mesh_space`].
+    pub fn vertex_positions(&self) -> impl Iterator<Item = &[f32; 2]> {
+        self.vertices.vertices.iter().map(|v| &v.position)
+    }
+
+    /// Vertices as `[x, y, prim_id, x, y, prim_id, ...]`, for `FLOAT` vertex attributes in
+    /// OpenGL ES 2. `prim_id` is exact only up to 2^24; on GLES 3+ prefer a `uint` attribute
+    /// fed from [`GpuVertex::to_ne_bytes`].
+    pub fn to_vertex_flat_f32(&self) -> Vec<f32> {
+        self.vertices.vertices.iter()
+            .flat_map(|v| [v.position[0], v.position[1], v.prim_id as f32])
+            .collect()
+    }
+
+    /// Corner positions of each triangle of the mesh, in index order.
+    ///
+    /// # Panics
+    ///
+    /// If the index count is not a multiple of 3, or an index is out of range.
+    pub fn triangles(&self) -> impl Iterator<Item = ([f32; 2], [f32; 2], [f32; 2])> + '_ {
+        let indices = &self.vertices.indices;
+        assert!(indices.len().is_multiple_of(3), "mesh has {} indices, which is not a multiple of 3", indices.len());
+        let position = move |i: u32| self.vertices.vertices[i as usize].position;
+        indices.chunks_exact(3).map(move |tri| (position(tri[0]), position(tri[1]), position(tri[2])))
+    }
+
+    /// Releases capacity the tessellator over-allocated in the vertex and index buffers.
+    pub fn shrink_to_fit(&mut self) {
+        VertexBuffersExt::shrink_to_fit(&mut self.vertices);
+    }
+
+    /// Merges vertices closer than `epsilon` (per axis) that share a `prim_id`, remaps the
+    /// indices and drops triangles that collapse as a result. `epsilon <= 0.0` merges only
+    /// bit-identical positions.
+    pub fn dedup_vertices(&self, epsilon: f32) -> Self {
+        let exact = epsilon <= 0.0;
+        let cell = |v: f32| if exact { v.to_bits() as i64 } else { (v / epsilon).floor() as i64 };
+
+        let mut grid: HashMap<(i64, i64, u32), Vec<u32>> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.vertices.vertices.len());
+        let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+        for v in &self.vertices.vertices {
+            let (cx, cy) = (cell(v.position[0]), cell(v.position[1]));
+            let reach = if exact { 0 } else { 1 };
+            let mut found = None;
+            'search: for dx in -reach..=reach {
+                for dy in -reach..=reach {
+                    if let Some(candidates) = grid.get(&(cx + dx, cy + dy, v.prim_id)) {
+                        for &c in candidates {
+                            let p = mesh.vertices[c as usize].position;
+                            if (p[0] - v.position[0]).abs() <= epsilon.max(0.0)
+                                && (p[1] - v.position[1]).abs() <= epsilon.max(0.0) {
+                                found = Some(c);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            let index = found.unwrap_or_else(|| {
+                let index = mesh.vertices.len() as u32;
+                mesh.vertices.push(*v);
+                grid.entry((cx, cy, v.prim_id)).or_default().push(index);
+                index
+            });
+            remap.push(index);
+        }
+
+        for tri in self.vertices.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| remap[i as usize]);
+            if a != b && b != c && a != c {
+                mesh.indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        self.with_mesh(mesh)
+    }
+
+    /// Copy of this path's paint and size around a different mesh.
+    fn with_mesh(&self, mesh: VertexBuffers<GpuVertex, u32>) -> Self {
+        RenderablePath {
+            size: self.size,
+            bgcolor: self.bgcolor,
+            gradient_stops: self.gradient_stops,
+            gradient_pos: self.gradient_pos.clone(),
+            gradient_colors: self.gradient_colors.clone(),
+            gradient_start: self.gradient_start,
+            gradient_end: self.gradient_end,
+            vertices: mesh,
+            z_index: self.z_index,
+            path_opacity: self.path_opacity,
+            local_transform: self.local_transform,
+        }
+    }
+
+    /// Edges used by exactly one triangle, i.e. the silhouette of the mesh, as `(from, to)`
+    /// positions in triangle winding order, in mesh space like [`Self::bounding_box`].
+    pub fn outline_boundary_edges(&self) -> Vec<([f32; 2], [f32; 2])> {
+        let mut counts: HashMap<(u32, u32), usize> = HashMap::new();
+        let mut edges = Vec::new();
+        for tri in self.vertices.indices.chunks_exact(3) {
+            for (a, b) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])] {
+                let count = counts.entry((a.min(b), a.max(b))).or_insert(0);
+                if *count == 0 {
+                    edges.push((a, b));
+                }
+                *count += 1;
+            }
+        }
+
+        let verts = &self.vertices.vertices;
+        let position = |i: u32| {
+            let (x, y) = self.to_mesh_space(verts[i as usize].position);
+            [x, y]
+        };
+        edges.into_iter()
+            .filter(|(a, b)| counts[&(*a.min(b), *a.max(b))] == 1)
+            .map(|(a, b)| (position(a), position(b)))
+            .collect()
+    }
+
+    /// Distance in mesh space from `(x, y)` to the nearest of the [`outline_boundary_edges`],
+    /// inside or outside the shape, e.g. for hover glow or snapping. `f32::INFINITY` for an
+    /// empty path.
+    ///
+    /// [`outline_boundary_edges`]: RenderablePath::outline_boundary_edges
+    pub fn distance_to_edge(&self, x: f32, y: f32) -> f32 {
+        self.outline_boundary_edges().into_iter()
+            .map(|(a, b)| {
+                let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+                let len = dx * dx + dy * dy;
+                let t = if len > 0.0 { (((x - a[0]) * dx + (y - a[1]) * dy) / len).clamp(0.0, 1.0) } else { 0.0 };
+                (a[0] + dx * t - x).hypot(a[1] + dy * t - y)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/// Row-major matrix of `t` for [`RenderablePath::transform_matrix`].
+pub fn matrix_from_transform(t: &Transform) -> [[f32; 3]; 3] {
+    [
+        [t.a as f32, t.c as f32, t.e as f32],
+        [t.b as f32, t.d as f32, t.f as f32],
+        [0.0, 0.0, 1.0],
+    ]
+}
+
+/// Helpers for the meshes produced by this crate.
+pub trait VertexBuffersExt {
+    fn triangle_count(&self) -> usize;
+    /// Whether every index points at a vertex and no position is NaN.
+    fn is_valid(&self) -> bool;
+    /// Releases spare capacity of both the vertex and the index buffer.
+    fn shrink_to_fit(&mut self);
+}
+
+impl VertexBuffersExt for VertexBuffers<GpuVertex, u32> {
+    fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn is_valid(&self) -> bool {
+        let count = self.vertices.len();
+        self.indices.iter().all(|&i| (i as usize) < count)
+            && self.vertices.iter().all(|v| !v.position[0].is_nan() && !v.position[1].is_nan())
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.vertices.shrink_to_fit();
+        self.indices.shrink_to_fit();
     }
 }
 
+/// A raster image embedded in a document, e.g. a PNG in a data URI, left for the renderer to
+/// decode and draw as a textured quad.
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct RenderableImage {
+    /// Encoded image file.
+    pub bytes: Vec<u8>,
+    /// `image/png`, `image/jpeg` or `image/gif`.
+    pub mime_type: String,
+    /// Two triangles over the image's viewport. Vertices are the top-left, top-right,
+    /// bottom-right and bottom-left corners of the image, i.e. texture coordinates
+    /// `(0, 0)`, `(1, 0)`, `(1, 1)` and `(0, 1)`. Their `prim_id` is the index of the image
+    /// in `LoadedSvg::primitives`, which no path shares.
+    pub quad: VertexBuffers<GpuVertex, u32>,
+    /// Maps texture coordinates to the space of the quad (row-major, bottom row `[0, 0, 1]`);
+    /// invert it to find the texture coordinate of a point.
+    pub transform: [[f32; 3]; 3],
+    /// Matrix from the space of `quad` to the loader's mesh space, as for
+    /// [`RenderablePath::local_transform`]; `None` when the quad is already in mesh space.
+    #[serde(default)]
+    pub local_transform: Option<[[f32; 3]; 3]>,
+    /// Opacity of the whole image, from the `opacity` of its SVG element and ancestors.
+    #[serde(default = "full_opacity")]
+    pub opacity: f32,
+}
+
+/// One drawable of a loaded document, in document order.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ScenePrimitive {
+    Path(RenderablePath),
+    Image(RenderableImage),
+}
+
+impl ScenePrimitive {
+    pub fn as_path(&self) -> Option<&RenderablePath> {
+        match self {
+            ScenePrimitive::Path(path) => Some(path),
+            ScenePrimitive::Image(_) => None,
+        }
+    }
+
+    pub fn as_image(&self) -> Option<&RenderableImage> {
+        match self {
+            ScenePrimitive::Image(image) => Some(image),
+            ScenePrimitive::Path(_) => None,
+        }
+    }
+}
+
+/// Stable sort into draw order: lower `z_index` first, equal ones keep their order.
+pub fn sort_by_z_index(paths: &mut [RenderablePath]) {
+    paths.sort_by_key(|p| p.z_index);
+}
+
+pub fn shrink_scene_to_fit(paths: &mut [RenderablePath]) {
+    for path in paths {
+        path.shrink_to_fit();
+    }
+}
+
+#[repr(C)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
 pub struct GpuVertex {
     pub position: [f32; 2],
     pub prim_id: u32,
 }
+
+// Positions compare by bit pattern so that Eq and Hash agree: NaN equals itself and 0.0 differs
+// from -0.0, which is what vertex deduplication needs.
+impl PartialEq for GpuVertex {
+    fn eq(&self, other: &Self) -> bool {
+        self.position.map(f32::to_bits) == other.position.map(f32::to_bits) && self.prim_id == other.prim_id
+    }
+}
+
+impl Eq for GpuVertex {}
+
+impl Hash for GpuVertex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.position.map(f32::to_bits).hash(state);
+        self.prim_id.hash(state);
+    }
+}
+
+impl GpuVertex {
+    pub fn to_point(&self) -> lyon::math::Point {
+        lyon::math::point(self.position[0], self.position[1])
+    }
+
+    pub fn with_prim_id(self, new_id: u32) -> GpuVertex {
+        GpuVertex { prim_id: new_id, ..self }
+    }
+
+    pub fn offset_position(self, dx: f32, dy: f32) -> GpuVertex {
+        GpuVertex { position: [self.position[0] + dx, self.position[1] + dy], ..self }
+    }
+
+    /// The `#[repr(C)]` memory layout (x, y, prim_id; no padding) in native byte order, for
+    /// APIs that take vertex data as raw bytes with a 12 byte stride.
+    pub fn to_ne_bytes(self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[0..4].copy_from_slice(&self.position[0].to_ne_bytes());
+        bytes[4..8].copy_from_slice(&self.position[1].to_ne_bytes());
+        bytes[8..12].copy_from_slice(&self.prim_id.to_ne_bytes());
+        bytes
+    }
+
+    /// Inverse of [`GpuVertex::to_ne_bytes`].
+    pub fn from_ne_bytes(bytes: [u8; 12]) -> Self {
+        let word = |i: usize| [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+        GpuVertex {
+            position: [f32::from_ne_bytes(word(0)), f32::from_ne_bytes(word(4))],
+            prim_id: u32::from_ne_bytes(word(8)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::svgload::{load_svg_data, LoadOptions};
+
+    fn heap_bytes(paths: &[RenderablePath]) -> usize {
+        paths.iter().map(|p| {
+            p.vertices.vertices.capacity() * std::mem::size_of::<GpuVertex>() + p.vertices.indices.capacity() * 4
+        }).sum()
+    }
+
+    #[test]
+    fn shrink_scene_to_fit_releases_tessellator_slack() {
+        let mut paths = load_svg_data(&fixtures::read("golden/stroke.svg"), &LoadOptions::default()).unwrap().into_paths();
+        let before = heap_bytes(&paths);
+        shrink_scene_to_fit(&mut paths);
+        let after = heap_bytes(&paths);
+        assert!(after < before, "{} bytes before, {} after", before, after);
+        for p in &paths {
+            assert_eq!(p.vertices.vertices.capacity(), p.vertices.vertices.len());
+            assert_eq!(p.vertices.indices.capacity(), p.vertices.indices.len());
+        }
+    }
+
+    fn triangle_soup(corners: &[[f32; 2]], prim_ids: &[u32]) -> RenderablePath {
+        let mut mesh = VertexBuffers::new();
+        for (&position, &prim_id) in corners.iter().zip(prim_ids) {
+            mesh.indices.push(mesh.vertices.len() as u32);
+            mesh.vertices.push(GpuVertex { position, prim_id });
+        }
+        RenderablePath::new((1, 1), mesh)
+    }
+
+    #[test]
+    fn dedup_vertices_shares_the_corners_of_a_square() {
+        let corners = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let square = triangle_soup(&corners, &[0; 6]).dedup_vertices(0.0);
+        assert_eq!(square.vertices.vertices.len(), 4);
+        assert_eq!(square.vertices.indices, [0, 1, 2, 0, 2, 3]);
+
+        let jittered = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0005, 0.0], [1.0, 0.9995], [0.0, 1.0]];
+        assert_eq!(triangle_soup(&jittered, &[0; 6]).dedup_vertices(0.001).vertices.vertices.len(), 4);
+    }
+
+    #[test]
+    fn dedup_vertices_keeps_vertices_of_different_primitives() {
+        let corners = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let square = triangle_soup(&corners, &[0, 0, 0, 1, 1, 1]).dedup_vertices(0.0);
+        assert_eq!(square.vertices.vertices.len(), 6);
+        assert_eq!(square.vertices.indices.len(), 6);
+    }
+
+    #[test]
+    fn flip_gradient_reverses_the_stops() {
+        let path = RenderablePath {
+            gradient_stops: 3,
+            gradient_pos: Some(vec![0.1, 0.5, 0.8]),
+            gradient_colors: Some(vec![[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]]),
+            gradient_start: Some((0.0, 0.0)),
+            gradient_end: Some((10.0, 0.0)),
+            ..RenderablePath::new((1, 1), VertexBuffers::new())
+        };
+        let flipped = path.flip_gradient();
+        let (pos, colors) = (flipped.gradient_pos.as_ref().unwrap(), flipped.gradient_colors.as_ref().unwrap());
+        assert!((pos[2] - 0.9).abs() < 1e-6 && (pos[1] - 0.5).abs() < 1e-6 && (pos[0] - 0.2).abs() < 1e-6);
+        assert_eq!(colors[2], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(colors[0], [0.0, 0.0, 1.0, 1.0]);
+        assert_eq!((flipped.gradient_start, flipped.gradient_end), (path.gradient_start, path.gradient_end));
+        let twice = flipped.flip_gradient().gradient_pos.unwrap();
+        assert!(twice.iter().zip(path.gradient_pos.as_ref().unwrap()).all(|(a, b)| (a - b).abs() < 1e-6));
+    }
+
+    #[test]
+    fn intersects_aabb_separates_disjoint_boxes() {
+        let corners = [[10.0, 10.0], [20.0, 10.0], [20.0, 20.0]];
+        let path = triangle_soup(&corners, &[0; 3]);
+        assert_eq!(path.bounding_box(), Some(((10.0, 10.0), (20.0, 20.0))));
+        assert!(path.intersects_aabb((0.0, 0.0), (15.0, 15.0)));
+        assert!(path.intersects_aabb((12.0, 12.0), (13.0, 13.0)));
+        assert!(path.intersects_aabb((20.0, 20.0), (30.0, 30.0)));
+        assert!(!path.intersects_aabb((21.0, 0.0), (30.0, 30.0)));
+        assert!(!path.intersects_aabb((0.0, 0.0), (30.0, 9.0)));
+
+        assert!(path.intersects_aabb((5.0, 5.0), (0.0, 0.0)));
+        assert!(triangle_soup(&[], &[]).intersects_aabb((100.0, 100.0), (200.0, 200.0)));
+    }
+
+    #[test]
+    fn scale_colors_multiplies_rgb_and_keeps_alpha() {
+        let path = RenderablePath {
+            bgcolor: [0.4, 0.2, 0.1, 1.0],
+            gradient_colors: Some(vec![[0.6, 0.1, 0.0, 0.5]]),
+            ..RenderablePath::new((1, 1), VertexBuffers::new())
+        };
+        let brighter = path.scale_colors(2.0);
+        assert_eq!(brighter.bgcolor, [0.8, 0.4, 0.2, 1.0]);
+        assert_eq!(brighter.gradient_colors, Some(vec![[1.0, 0.2, 0.0, 0.5]]));
+        assert_eq!(path.scale_colors(-1.0).bgcolor, [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn bounds_are_in_mesh_space() {
+        let corners = [[10.0, 10.0], [20.0, 10.0], [20.0, 20.0]];
+        let mut path = triangle_soup(&corners, &[0; 3]);
+        // A quarter turn, then 100 to the right.
+        path.local_transform = Some([[0.0, -1.0, 100.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert_eq!(path.bounding_box(), Some(((80.0, 10.0), (90.0, 20.0))));
+        assert!(path.intersects_aabb((85.0, 15.0), (86.0, 16.0)));
+        assert!(!path.intersects_aabb((10.0, 10.0), (20.0, 20.0)));
+
+        assert_eq!(path.to_mesh_space([10.0, 20.0]), (80.0, 10.0));
+        assert_eq!(path.to_vertex_space((80.0, 10.0)), Some([10.0, 20.0]));
+        path.local_transform = Some([[1.0, 2.0, 0.0], [2.0, 4.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert_eq!(path.to_vertex_space((1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn edges_and_distances_are_in_mesh_space() {
+        let corners = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let square = triangle_soup(&corners, &[0; 6]).dedup_vertices(0.0);
+        let mut placed = square.clone();
+        // Scaled by 10 and 2, then moved to (100, 50).
+        placed.local_transform = Some([[10.0, 0.0, 100.0], [0.0, 2.0, 50.0], [0.0, 0.0, 1.0]]);
+        let flat = placed.flattened();
+
+        let mut edges = placed.outline_boundary_edges();
+        let mut expected = flat.outline_boundary_edges();
+        edges.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges, expected);
+
+        for (x, y) in [(105.0, 51.0), (105.0, 60.0), (90.0, 51.0), (100.0, 50.0)] {
+            assert_eq!(placed.distance_to_edge(x, y), flat.distance_to_edge(x, y), "at ({}, {})", x, y);
+        }
+        assert_eq!(placed.distance_to_edge(105.0, 51.0), 1.0);
+        assert_eq!(placed.distance_to_edge(105.0, 60.0), 8.0);
+    }
+}
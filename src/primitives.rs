@@ -0,0 +1,263 @@
+//! Tessellation of common shapes built from code. Coordinates are used as given (no
+//! viewport transform is applied), so they should already be in the caller's render space.
+
+use std::fmt;
+
+use lyon::geom::{Arc, ArcFlags};
+use lyon::math::{point, vector, Angle};
+use lyon::path::builder::SvgPathBuilder;
+use lyon::path::Path;
+use lyon::tessellation::*;
+use usvg::Transform;
+
+use crate::path::{GpuVertex, PaintSpec, RenderablePath};
+use crate::svgload::VertexCtor;
+
+const TOLERANCE: f32 = 0.1;
+
+impl RenderablePath {
+    /// Fills a path built with lyon's builder API, e.g. `lyon::path::Path::builder()`.
+    pub fn from_lyon_path(path: &Path, paint: PaintSpec, size: (u32, u32)) -> Result<Self, TessellationError> {
+        let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+        FillTessellator::new().tessellate_path(path, &FillOptions::tolerance(TOLERANCE), &mut BuffersBuilder::new(
+            &mut mesh,
+            VertexCtor {
+                prim_id: 0,
+                transform: Transform::default(),
+            },
+        ))?;
+        Ok(RenderablePath::from_paint(size, &paint, mesh))
+    }
+
+    /// Wraps geometry tessellated elsewhere, e.g. by another tessellator or a boolean
+    /// operation: `indices` list triangles as triples of indices into `verts`.
+    pub fn from_geometry(verts: Vec<GpuVertex>, indices: Vec<u32>, paint: PaintSpec, size: (u32, u32)) -> Result<Self, GeometryError> {
+        if !indices.len().is_multiple_of(3) {
+            return Err(GeometryError::IncompleteTriangle { index_count: indices.len() });
+        }
+        if let Some((position, &index)) = indices.iter().enumerate().find(|(_, &i)| i as usize >= verts.len()) {
+            return Err(GeometryError::IndexOutOfBounds { position, index, vertex_count: verts.len() });
+        }
+        if let Some(vertex) = verts.iter().position(|v| !v.position.iter().all(|c| c.is_finite())) {
+            return Err(GeometryError::NonFiniteVertex { vertex });
+        }
+        if let PaintSpec::LinearGradient(g) = &paint {
+            if g.positions.len() != g.colors.len() {
+                return Err(GeometryError::GradientMismatch { positions: g.positions.len(), colors: g.colors.len() });
+            }
+        }
+        let mesh = VertexBuffers { vertices: verts, indices };
+        Ok(RenderablePath::from_paint(size, &paint, mesh))
+    }
+}
+
+/// Invalid input to [`RenderablePath::from_geometry`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeometryError {
+    /// The index count is not a multiple of 3.
+    IncompleteTriangle { index_count: usize },
+    /// `indices[position]` is `index`, past the end of the vertices.
+    IndexOutOfBounds { position: usize, index: u32, vertex_count: usize },
+    /// Vertex `vertex` has a NaN or infinite coordinate.
+    NonFiniteVertex { vertex: usize },
+    /// The gradient paint has a different number of stop positions and colors.
+    GradientMismatch { positions: usize, colors: usize },
+}
+
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeometryError::IncompleteTriangle { index_count } => {
+                write!(f, "{} indices do not form whole triangles", index_count)
+            }
+            GeometryError::IndexOutOfBounds { position, index, vertex_count } => {
+                write!(f, "index {} at position {} is out of bounds for {} vertices", index, position, vertex_count)
+            }
+            GeometryError::NonFiniteVertex { vertex } => write!(f, "vertex {} has a non-finite coordinate", vertex),
+            GeometryError::GradientMismatch { positions, colors } => {
+                write!(f, "gradient has {} stop positions but {} colors", positions, colors)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GeometryError {}
+
+/// Tessellates a circular arc, angles in radians. With `closed` the arc is joined to `center`
+/// and filled as a pie slice; otherwise the open arc is stroked with `line_width`, giving a
+/// ring segment.
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_arc(center: (f32, f32), radius: f32, start_angle: f32, sweep_angle: f32, closed: bool,
+                      line_width: f32, paint: &PaintSpec, size: (u32, u32)) -> Result<RenderablePath, TessellationError> {
+    let arc = Arc {
+        center: point(center.0, center.1),
+        radii: vector(radius, radius),
+        start_angle: Angle::radians(start_angle),
+        sweep_angle: Angle::radians(sweep_angle),
+        x_rotation: Angle::radians(0.0),
+    };
+
+    let mut builder = Path::builder();
+    if closed {
+        builder.begin(arc.center);
+        builder.line_to(arc.from());
+    } else {
+        builder.begin(arc.from());
+    }
+    arc.for_each_quadratic_bezier(&mut |q| {
+        builder.quadratic_bezier_to(q.ctrl, q.to);
+    });
+    builder.end(closed);
+    let path = builder.build();
+
+    if closed {
+        return RenderablePath::from_lyon_path(&path, paint.clone(), size);
+    }
+
+    let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+    let mut output = BuffersBuilder::new(&mut mesh, VertexCtor {
+        prim_id: 0,
+        transform: Transform::default(),
+    });
+    let options = StrokeOptions::tolerance(TOLERANCE).with_line_width(line_width);
+    StrokeTessellator::new().tessellate_path(&path, &options, &mut output)?;
+
+    Ok(RenderablePath::from_paint(size, paint, mesh))
+}
+
+/// Fills the rectangle with corner `(x, y)` and size `w` x `h` with elliptical corners of radii
+/// `rx` and `ry`, each clamped to `[0, min(w, h) / 2]`. Zero radii give a plain rectangle and
+/// radii of half the shorter side a capsule.
+#[allow(clippy::too_many_arguments)]
+pub fn tessellate_rounded_rect(x: f32, y: f32, w: f32, h: f32, rx: f32, ry: f32,
+                               paint: &PaintSpec, size: (u32, u32)) -> Result<RenderablePath, TessellationError> {
+    let limit = w.min(h) / 2.0;
+    let radii = vector(rx.min(limit).max(0.0), ry.min(limit).max(0.0));
+    let (rx, ry) = (radii.x, radii.y);
+    let corner = ArcFlags { large_arc: false, sweep: true };
+    let no_rotation = Angle::radians(0.0);
+
+    let mut builder = Path::builder().with_svg();
+    builder.move_to(point(x + rx, y));
+    builder.line_to(point(x + w - rx, y));
+    builder.arc_to(radii, no_rotation, corner, point(x + w, y + ry));
+    builder.line_to(point(x + w, y + h - ry));
+    builder.arc_to(radii, no_rotation, corner, point(x + w - rx, y + h));
+    builder.line_to(point(x + rx, y + h));
+    builder.arc_to(radii, no_rotation, corner, point(x, y + h - ry));
+    builder.line_to(point(x, y + ry));
+    builder.arc_to(radii, no_rotation, corner, point(x + rx, y));
+    builder.close();
+    RenderablePath::from_lyon_path(&builder.build(), paint.clone(), size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: PaintSpec = PaintSpec::Color([1.0, 0.0, 0.0, 1.0]);
+
+    fn rounded(w: f32, h: f32, rx: f32, ry: f32) -> RenderablePath {
+        tessellate_rounded_rect(2.0, 3.0, w, h, rx, ry, &RED, (1, 1)).unwrap()
+    }
+
+    fn bounds(path: &RenderablePath) -> (f32, f32, f32, f32) {
+        path.vertices.vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |b, v| {
+            (b.0.min(v.position[0]), b.1.min(v.position[1]), b.2.max(v.position[0]), b.3.max(v.position[1]))
+        })
+    }
+
+    #[test]
+    fn rounded_rect_vertex_counts() {
+        // A convex outline is filled as a fan: one triangle fewer than two per vertex.
+        for (rx, ry, vertices) in [(0.0, 0.0, 4), (-1.0, -1.0, 4), (1.0, 1.0, 13), (2.0, 1.0, 16), (2.0, 2.0, 17)] {
+            let path = rounded(10.0, 4.0, rx, ry);
+            assert_eq!(path.vertices.vertices.len(), vertices, "radii {} {}", rx, ry);
+            assert_eq!(path.vertices.indices.len(), 3 * (vertices - 2));
+            assert_eq!(bounds(&path), (2.0, 3.0, 12.0, 7.0));
+        }
+        assert_eq!(rounded(10.0, 4.0, 1.0, 1.0).bgcolor, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn radii_are_clamped_to_a_capsule() {
+        let capsule = rounded(10.0, 4.0, 2.0, 2.0);
+        assert_eq!(rounded(10.0, 4.0, 9.0, 9.0).vertices.vertices, capsule.vertices.vertices);
+        // Every vertex lies on the outline: 2 from the segment joining the centers of the ends.
+        for v in &capsule.vertices.vertices {
+            let [x, y] = v.position;
+            let distance = (x - x.clamp(4.0, 10.0)).hypot(y - 5.0);
+            assert!((distance - 2.0).abs() <= TOLERANCE, "({}, {}) is {} from the axis", x, y, distance);
+        }
+    }
+
+    fn arc(radius: f32, sweep: f32, closed: bool) -> RenderablePath {
+        tessellate_arc((1.0, 2.0), radius, 0.5, sweep, closed, 0.2, &RED, (1, 1)).unwrap()
+    }
+
+    /// Angles of the vertices of `path` around the arc center, from about 0.5 to 0.5 + 2π.
+    fn angles(path: &RenderablePath) -> Vec<f32> {
+        let mut angles: Vec<f32> = path.vertices.vertices.iter()
+            .filter(|v| v.position != [1.0, 2.0])
+            .map(|v| (v.position[1] - 2.0).atan2(v.position[0] - 1.0).rem_euclid(std::f32::consts::TAU))
+            .map(|a| if a < 0.25 { a + std::f32::consts::TAU } else { a })
+            .collect();
+        angles.sort_by(f32::total_cmp);
+        angles
+    }
+
+    #[test]
+    fn arc_vertex_count_follows_the_tolerance() {
+        for radius in [1.0, 10.0, 100.0] {
+            let path = arc(radius, 2.0, true);
+            let angles = angles(&path);
+            // A chord over `step` radians deviates r(1 - cos(step / 2)) from the arc.
+            let max_step = angles.windows(2).map(|w| w[1] - w[0]).fold(0.0, f32::max);
+            assert!(radius * (1.0 - (max_step / 2.0).cos()) <= TOLERANCE * 1.01, "radius {}: step {}", radius, max_step);
+            let minimum = (2.0 / (2.0 * (1.0 - TOLERANCE / radius).acos())).ceil() as usize;
+            assert!(angles.len() > minimum, "radius {}: {} vertices", radius, angles.len());
+        }
+        let count = |radius| arc(radius, 2.0, true).vertices.vertices.len();
+        assert!(count(1.0) < count(10.0) && count(10.0) < count(100.0));
+    }
+
+    #[test]
+    fn arc_ends_lie_on_the_circle() {
+        let (start, end) = (0.5f32, 0.5f32 + 2.0);
+        for closed in [true, false] {
+            let path = arc(10.0, 2.0, closed);
+            // Filled wedges have their rim on the circle, strokes straddle it by half the width;
+            // the quadratic approximation of the arc may stray from it by the tolerance.
+            let (inner, outer) = if closed { (10.0, 10.0) } else { (9.9, 10.1) };
+            for v in path.vertices.vertices.iter().filter(|v| v.position != [1.0, 2.0]) {
+                let r = (v.position[0] - 1.0).hypot(v.position[1] - 2.0);
+                assert!(r >= inner - TOLERANCE && r <= outer + TOLERANCE, "vertex at radius {}", r);
+            }
+            let angles = angles(&path);
+            assert!((angles[0] - start).abs() < 2e-3 && (angles[angles.len() - 1] - end).abs() < 2e-3, "{:?}", (angles[0], angles.last()));
+        }
+        assert!(arc(10.0, 2.0, true).vertices.vertices.iter().any(|v| v.position == [1.0, 2.0]), "wedge lacks its center");
+    }
+
+    #[test]
+    fn empty_and_full_sweeps() {
+        for closed in [true, false] {
+            let empty = arc(10.0, 0.0, closed);
+            let area: f32 = empty.triangles().map(triangle_area).sum();
+            assert!(area < 1e-3, "sweep 0 drew an area of {}", area);
+        }
+
+        let full = arc(10.0, std::f32::consts::TAU, true);
+        let area: f32 = full.triangles().map(triangle_area).sum();
+        let disc = std::f32::consts::PI * 100.0;
+        assert!((area - disc).abs() < 0.01 * disc, "full sweep covers {} of {}", area, disc);
+        let ring = arc(10.0, std::f32::consts::TAU, false);
+        let area: f32 = ring.triangles().map(triangle_area).sum();
+        let expected = std::f32::consts::TAU * 10.0 * 0.2;
+        assert!((area - expected).abs() < 0.02 * expected, "full ring covers {} of {}", area, expected);
+    }
+
+    fn triangle_area((a, b, c): ([f32; 2], [f32; 2], [f32; 2])) -> f32 {
+        ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+    }
+}
@@ -0,0 +1,183 @@
+//! Python bindings, built as the `svg_load` extension module.
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::font::{Font, Glyph};
+use crate::path::{RenderablePath, VertexBuffersExt};
+use crate::svgload::{load_svg_data, LoadOptions};
+use crate::ttfload;
+
+#[pyclass(name = "Path")]
+#[derive(Clone)]
+pub struct PyPath {
+    inner: RenderablePath,
+}
+
+#[pymethods]
+impl PyPath {
+    /// Flat `[x0, y0, x1, y1, ...]` vertex positions.
+    #[getter]
+    fn vertices(&self) -> Vec<f32> {
+        self.inner.vertex_positions().flatten().copied().collect()
+    }
+
+    #[getter]
+    fn indices(&self) -> Vec<u32> {
+        self.inner.vertices.indices.clone()
+    }
+
+    #[getter]
+    fn color(&self) -> [f32; 4] {
+        self.inner.bgcolor
+    }
+
+    #[getter]
+    fn gradient_positions(&self) -> Option<Vec<f32>> {
+        self.inner.gradient_pos.clone()
+    }
+
+    #[getter]
+    fn gradient_colors(&self) -> Option<Vec<[f32; 4]>> {
+        self.inner.gradient_colors.clone()
+    }
+
+    #[getter]
+    fn gradient_color_stops(&self) -> Option<Vec<(f32, [f32; 4])>> {
+        self.inner.gradient_color_stops()
+    }
+
+    #[getter]
+    fn gradient_start(&self) -> Option<(f32, f32)> {
+        self.inner.gradient_start
+    }
+
+    #[getter]
+    fn gradient_end(&self) -> Option<(f32, f32)> {
+        self.inner.gradient_end
+    }
+
+    #[getter]
+    fn triangle_count(&self) -> usize {
+        self.inner.vertices.triangle_count()
+    }
+}
+
+#[pyclass(name = "Scene")]
+pub struct PyScene {
+    #[pyo3(get)]
+    paths: Vec<PyPath>,
+}
+
+#[pyclass(name = "Glyph")]
+#[derive(Clone)]
+pub struct PyGlyph {
+    inner: Glyph,
+}
+
+#[pymethods]
+impl PyGlyph {
+    #[getter]
+    fn advance(&self) -> f32 {
+        self.inner.advance
+    }
+
+    #[getter]
+    fn bbox(&self) -> (f32, f32, f32, f32) {
+        self.inner.bbox
+    }
+
+    #[getter]
+    fn lsb(&self) -> f32 {
+        self.inner.lsb
+    }
+
+    #[getter]
+    fn rsb(&self) -> f32 {
+        self.inner.rsb
+    }
+
+    #[getter]
+    fn vertices(&self) -> Vec<f32> {
+        self.inner.outline.vertices.iter().flat_map(|v| v.position).collect()
+    }
+
+    #[getter]
+    fn indices(&self) -> Vec<u32> {
+        self.inner.outline.indices.clone()
+    }
+}
+
+#[pyclass(name = "Font")]
+pub struct PyFont {
+    inner: Font,
+}
+
+#[pymethods]
+impl PyFont {
+    #[getter]
+    fn name(&self) -> String {
+        self.inner.name.clone()
+    }
+
+    #[getter]
+    fn family_name(&self) -> String {
+        self.inner.family_name.clone()
+    }
+
+    #[getter]
+    fn ascender(&self) -> f32 {
+        self.inner.ascender
+    }
+
+    #[getter]
+    fn descender(&self) -> f32 {
+        self.inner.descender
+    }
+
+    #[getter]
+    fn line_gap(&self) -> f32 {
+        self.inner.line_gap
+    }
+
+    fn glyph(&self, ch: char) -> Option<PyGlyph> {
+        self.inner.glyph(ch).map(|g| PyGlyph { inner: g.clone() })
+    }
+}
+
+/// `source` is either the SVG document as `bytes` or a path to it.
+#[pyfunction]
+#[pyo3(signature = (source, tolerance = 0.1))]
+fn load_svg(source: &PyAny, tolerance: f32) -> PyResult<PyScene> {
+    let data = match source.downcast::<PyBytes>() {
+        Ok(bytes) => bytes.as_bytes().to_vec(),
+        Err(_) => {
+            let path: std::path::PathBuf = source.extract()?;
+            std::fs::read(path).map_err(|e| PyIOError::new_err(e.to_string()))?
+        }
+    };
+    let options = LoadOptions { tolerance, ..LoadOptions::default() };
+    let loaded = load_svg_data(&data, &options).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(PyScene {
+        paths: loaded.into_paths().into_iter().map(|inner| PyPath { inner }).collect(),
+    })
+}
+
+#[pyfunction]
+fn load_font(path: &str, symbols: &str) -> PyResult<PyFont> {
+    ttfload::load_font(path, symbols)
+        .map(|inner| PyFont { inner })
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pymodule]
+fn svg_load(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyScene>()?;
+    m.add_class::<PyPath>()?;
+    m.add_class::<PyFont>()?;
+    m.add_class::<PyGlyph>()?;
+    m.add_function(wrap_pyfunction!(load_svg, m)?)?;
+    m.add_function(wrap_pyfunction!(load_font, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Software rasterization of loaded primitives, for rendering without a GPU.
+//!
+//! Triangles are scan-converted at pixel centers without anti-aliasing. Centers on an edge
+//! follow the top-left rule, so edges shared by two triangles of a mesh are drawn once and
+//! translucent paths do not show seams.
+
+use std::fmt;
+
+use crate::path::RenderablePath;
+use crate::svgload::{load_svg, SvgLoadError};
+
+#[derive(Debug)]
+pub enum RasterError {
+    Load(SvgLoadError),
+    /// The pixels of a `width` x `height` image do not fit in memory.
+    TooLarge { width: u32, height: u32 },
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RasterError::Load(e) => write!(f, "{}", e),
+            RasterError::TooLarge { width, height } => write!(f, "a {}x{} image is too large to rasterize", width, height),
+        }
+    }
+}
+
+impl std::error::Error for RasterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RasterError::Load(e) => Some(e),
+            RasterError::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<SvgLoadError> for RasterError {
+    fn from(e: SvgLoadError) -> Self {
+        RasterError::Load(e)
+    }
+}
+
+/// Loads `filename` and rasterizes it stretched over a `width` x `height` image, see
+/// [`rasterize`]. Embedded raster images are not drawn.
+pub fn load_svg_to_rgba_image(filename: &str, width: u32, height: u32) -> Result<Vec<u8>, RasterError> {
+    let loaded = load_svg(filename)?;
+    rasterize(&loaded.into_paths(), width, height)
+}
+
+/// Draws `paths` in order over a transparent `width` x `height` image. Meshes are in the
+/// loader's unit square, y-up, after their `local_transform`. Returns `width * height * 4` bytes of straight-alpha RGBA,
+/// top row first, or [`RasterError::TooLarge`] if the image does not fit in memory.
+pub fn rasterize(paths: &[RenderablePath], width: u32, height: u32) -> Result<Vec<u8>, RasterError> {
+    let (w, h) = (width as f32, height as f32);
+    let count = (width as usize).checked_mul(height as usize)
+        .filter(|n| n.checked_mul(std::mem::size_of::<[f32; 4]>()).is_some_and(|bytes| bytes <= isize::MAX as usize))
+        .ok_or(RasterError::TooLarge { width, height })?;
+    let mut pixels = vec![[0.0f32; 4]; count];
+    for path in paths {
+        let path = &*path.flattened();
+        for (a, b, c) in path.triangles() {
+            let corners = [a, b, c].map(|[x, y]| ([x * w, (1.0 - y) * h], [x, y]));
+            fill_triangle(&mut pixels, width, height, corners, |pos| {
+                let mut color = paint_at(path, pos);
+                color[3] *= path.path_opacity;
+                color
+            });
+        }
+    }
+    Ok(pixels.iter().flat_map(|p| p.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)).collect())
+}
+
+/// Blends the pixels whose centers lie in the triangle `corners`, each given in pixel space and
+/// in mesh space. `color` is called with the mesh position of the pixel center.
+fn fill_triangle(pixels: &mut [[f32; 4]], width: u32, height: u32, mut corners: [([f32; 2], [f32; 2]); 3], color: impl Fn([f32; 2]) -> [f32; 4]) {
+    let [a, b, c] = corners.map(|c| c.0);
+    let area = edge(a, b, c);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+    if area < 0.0 {
+        corners.swap(1, 2);
+    }
+    let area = area.abs();
+    let p = corners.map(|c| c.0);
+    let y_min = p.iter().map(|p| p[1]).fold(f32::MAX, f32::min);
+    let y_max = p.iter().map(|p| p[1]).fold(f32::MIN, f32::max);
+    let rows = (y_min - 0.5).ceil().max(0.0) as i64..=((y_max - 0.5).floor() as i64).min(height as i64 - 1);
+
+    for row in rows {
+        let py = row as f32 + 0.5;
+        let (mut lo, mut hi) = (0i64, width as i64 - 1);
+        for i in 0..3 {
+            let (from, to) = (p[i], p[(i + 1) % 3]);
+            let (dx, dy) = (to[0] - from[0], to[1] - from[1]);
+            // Edge function at (x, py) is m * x + k, non-negative inside.
+            let (m, k) = (-dy, dy * from[0] + dx * (py - from[1]));
+            // Top-left rule: with positive area in y-down space, top edges run rightwards and
+            // left edges upwards.
+            let inclusive = dy < 0.0 || (dy == 0.0 && dx > 0.0);
+            if m == 0.0 {
+                if k < 0.0 || (k == 0.0 && !inclusive) {
+                    lo = i64::MAX;
+                }
+            } else if m > 0.0 {
+                let bound = -k / m - 0.5;
+                lo = lo.max(if inclusive { bound.ceil() as i64 } else { bound.floor() as i64 + 1 });
+            } else {
+                let bound = -k / m - 0.5;
+                hi = hi.min(if inclusive { bound.floor() as i64 } else { bound.ceil() as i64 - 1 });
+            }
+        }
+        for col in lo..=hi {
+            let q = [col as f32 + 0.5, py];
+            let wa = edge(p[1], p[2], q) / area;
+            let wb = edge(p[2], p[0], q) / area;
+            let wc = 1.0 - wa - wb;
+            let m = corners.map(|c| c.1);
+            let pos = [
+                wa * m[0][0] + wb * m[1][0] + wc * m[2][0],
+                wa * m[0][1] + wb * m[1][1] + wc * m[2][1],
+            ];
+            blend(&mut pixels[(row * width as i64 + col) as usize], color(pos));
+        }
+    }
+}
+
+/// Twice the signed area of `a b p`, positive when `p` is left of `a -> b` in y-down space.
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Color of `path` at mesh position `pos`: its gradient if it has one, padded past the ends,
+/// otherwise `bgcolor`.
+fn paint_at(path: &RenderablePath, pos: [f32; 2]) -> [f32; 4] {
+    let (stops, start, end) = match (path.gradient_color_stops(), path.gradient_start, path.gradient_end) {
+        (Some(stops), Some(s), Some(e)) if !stops.is_empty() => (stops, s, e),
+        _ => return path.bgcolor,
+    };
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let len = dx * dx + dy * dy;
+    let t = if len > 0.0 { ((pos[0] - start.0) * dx + (pos[1] - start.1) * dy) / len } else { 0.0 };
+
+    let last = stops.len() - 1;
+    if t <= stops[0].0 {
+        return stops[0].1;
+    }
+    if t >= stops[last].0 {
+        return stops[last].1;
+    }
+    let i = stops.iter().position(|&(p, _)| p > t).unwrap_or(last).max(1);
+    let span = stops[i].0 - stops[i - 1].0;
+    let f = if span > 0.0 { (t - stops[i - 1].0) / span } else { 1.0 };
+    let (c0, c1) = (stops[i - 1].1, stops[i].1);
+    [0, 1, 2, 3].map(|k| c0[k] + (c1[k] - c0[k]) * f)
+}
+
+/// Source-over of straight-alpha `src` onto `dst`.
+fn blend(dst: &mut [f32; 4], src: [f32; 4]) {
+    let sa = src[3].clamp(0.0, 1.0);
+    let out_a = sa + dst[3] * (1.0 - sa);
+    if out_a <= 0.0 {
+        return;
+    }
+    for k in 0..3 {
+        dst[k] = (src[k] * sa + dst[k] * dst[3] * (1.0 - sa)) / out_a;
+    }
+    dst[3] = out_a;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::tessellation::VertexBuffers;
+    use crate::path::{GpuVertex, GradientSpec, PaintSpec};
+    use crate::svgload::{load_svg_data, LoadOptions};
+
+    fn pixel(image: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * width + x) * 4) as usize;
+        [image[i], image[i + 1], image[i + 2], image[i + 3]]
+    }
+
+    #[test]
+    fn local_transforms_are_applied() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <g transform="translate(16 8)"><rect width="8" height="8" fill="#f00"/></g>
+        </svg>"##;
+        let flat = rasterize(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths(), 32, 32).unwrap();
+        let paths = load_svg_data(svg, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap().into_paths();
+        assert!(paths[0].local_transform.is_some());
+        let local = rasterize(&paths, 32, 32).unwrap();
+        assert_eq!(pixel(&local, 32, 20, 12), [255, 0, 0, 255]);
+        assert_eq!(pixel(&local, 32, 4, 4), [0, 0, 0, 0]);
+        assert!(local == flat);
+    }
+
+    fn quad(corners: [[f32; 2]; 4], paint: PaintSpec) -> RenderablePath {
+        let mut mesh = VertexBuffers::new();
+        mesh.vertices = corners.iter().map(|&position| GpuVertex { position, prim_id: 0 }).collect();
+        mesh.indices = vec![0, 1, 2, 0, 2, 3];
+        RenderablePath::from_paint((1, 1), &paint, mesh)
+    }
+
+    #[test]
+    fn a_red_square_covers_its_center_only() {
+        let path = std::env::temp_dir().join(format!("svg-load-raster-{}.svg", std::process::id()));
+        std::fs::write(&path, r#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+            <rect x="4" y="4" width="8" height="8" fill="red"/></svg>"#).unwrap();
+        let image = load_svg_to_rgba_image(path.to_str().unwrap(), 16, 16);
+        std::fs::remove_file(&path).ok();
+        let image = image.unwrap();
+        assert_eq!(image.len(), 16 * 16 * 4);
+        assert_eq!(pixel(&image, 16, 8, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 16, 4, 4), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 16, 11, 11), [255, 0, 0, 255]);
+        for (x, y) in [(3, 8), (12, 8), (8, 3), (8, 12), (0, 0), (15, 15)] {
+            assert_eq!(pixel(&image, 16, x, y), [0, 0, 0, 0], "({}, {})", x, y);
+        }
+        assert!(matches!(load_svg_to_rgba_image("/nonexistent/file.svg", 1, 1), Err(RasterError::Load(SvgLoadError::Io(_)))));
+    }
+
+    #[test]
+    fn gradients_are_padded_past_their_endpoints() {
+        let gradient = PaintSpec::LinearGradient(GradientSpec {
+            start: (0.25, 0.0),
+            end: (0.75, 0.0),
+            positions: vec![0.0, 1.0],
+            colors: vec![[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+        });
+        let image = rasterize(&[quad([[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]], gradient)], 4, 1).unwrap();
+        let reds: Vec<u8> = (0..4).map(|x| pixel(&image, 4, x, 0)[0]).collect();
+        assert_eq!(reds, [0, 64, 191, 255]);
+    }
+
+    #[test]
+    fn pixel_centers_on_edges_follow_the_top_left_rule() {
+        // Edges run through pixel centers: x = 0.5 and 2.5, y = 0.5 and 2.5 in pixels. The
+        // diagonal shared by both triangles passes through the centers of (0, 0) and (1, 1).
+        let corners = [[0.125, 0.875], [0.625, 0.875], [0.625, 0.375], [0.125, 0.375]];
+        let image = rasterize(&[quad(corners, PaintSpec::Color([0.0, 0.0, 1.0, 0.5]))], 4, 4).unwrap();
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = if x < 2 && y < 2 { [0, 0, 255, 128] } else { [0, 0, 0, 0] };
+                assert_eq!(pixel(&image, 4, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_images_are_an_error() {
+        assert!(matches!(rasterize(&[], u32::MAX, u32::MAX), Err(RasterError::TooLarge { width: u32::MAX, height: u32::MAX })));
+        assert_eq!(rasterize(&[], 0, 0).unwrap(), Vec::<u8>::new());
+    }
+}
@@ -0,0 +1,184 @@
+//! Signed distance fields, an alternative to triangle meshes for text: one small bitmap per
+//! glyph that a shader thresholds at any scale.
+
+use std::collections::HashMap;
+
+use lyon::math::Point;
+use lyon::path::iterator::PathIterator;
+use lyon::path::PathEvent;
+use serde::{Serialize, Deserialize};
+
+use crate::ttfload::{load_font_with_options, FontLoadError, FontOptions};
+
+/// Value of the field on the outline; inside is above, outside below.
+pub const SDF_EDGE: u8 = 128;
+
+/// Glyphs of a font as distance fields rendered at a fixed pixel size. Metrics are in pixels.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SdfFont {
+    pub name: String,
+    pub px_size: u32,
+    /// Distance in pixels at which the field saturates.
+    pub spread: f32,
+    pub ascender: f32,
+    pub descender: f32,
+    pub line_gap: f32,
+    pub glyph_map: HashMap<u32, SdfGlyph>,
+}
+
+impl SdfFont {
+    pub fn glyph(&self, ch: char) -> Option<&SdfGlyph> {
+        self.glyph_map.get(&u32::from(ch))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SdfGlyph {
+    pub advance: f32,
+    /// Offset of the bitmap's left edge from the glyph origin.
+    pub bearing_x: i32,
+    /// Offset of the bitmap's top edge above the baseline.
+    pub bearing_y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// `width * height` distances, top row first; see [`SDF_EDGE`].
+    pub data: Vec<u8>,
+}
+
+/// Loads `symbols` from `filename` as distance fields with an em of `px_size` pixels. The field
+/// extends `spread` pixels around each outline. Glyphs without an outline get an empty bitmap.
+pub fn load_font_sdf(filename: &str, symbols: &str, px_size: u32, spread: f32) -> Result<SdfFont, FontLoadError> {
+    let options = FontOptions {
+        retain_path_events: true,
+        tessellate: false,
+        em_size: px_size as f32,
+        ..FontOptions::default()
+    };
+    let font = load_font_with_options(filename, symbols, &options)?;
+
+    let glyph_map = font.glyph_map.iter().map(|(&cp, glyph)| {
+        let mut sdf = SdfGlyph { advance: glyph.advance, ..SdfGlyph::default() };
+        if let Some(events) = glyph.path_events.as_ref().filter(|e| !e.is_empty()) {
+            let (x_min, y_min, x_max, y_max) = glyph.bbox;
+            sdf.bearing_x = (x_min - spread).floor() as i32;
+            sdf.bearing_y = (y_max + spread).ceil() as i32;
+            sdf.width = ((x_max + spread).ceil() as i32 - sdf.bearing_x).max(0) as u32;
+            sdf.height = (sdf.bearing_y - (y_min - spread).floor() as i32).max(0) as u32;
+            sdf.data = distance_field(events, (sdf.bearing_x as f32, sdf.bearing_y as f32), sdf.width, sdf.height, spread);
+        }
+        (cp, sdf)
+    }).collect();
+
+    Ok(SdfFont {
+        name: font.name,
+        px_size,
+        spread,
+        ascender: font.ascender,
+        descender: font.descender,
+        line_gap: font.line_gap,
+        glyph_map,
+    })
+}
+
+/// Samples the signed distance to the filled (nonzero) path `events` at the pixel centers of a
+/// `width` x `height` bitmap whose top-left corner is at `top_left` in the y-up path space.
+/// Distances are mapped from `[-spread, spread]` to `[0, 255]`, positive inside.
+pub fn distance_field(events: &[PathEvent], top_left: (f32, f32), width: u32, height: u32, spread: f32) -> Vec<u8> {
+    let segments = line_segments(events);
+    let mut data = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for col in 0..width {
+            let p = Point::new(top_left.0 + col as f32 + 0.5, top_left.1 - row as f32 - 0.5);
+            let mut dist = f32::MAX;
+            let mut winding = 0;
+            for &(a, b) in &segments {
+                dist = dist.min(segment_distance(p, a, b));
+                if (a.y <= p.y) != (b.y <= p.y) {
+                    let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+                    if x > p.x {
+                        winding += if b.y > a.y { 1 } else { -1 };
+                    }
+                }
+            }
+            let signed = if winding != 0 { dist } else { -dist };
+            let value = SDF_EDGE as f32 + signed / spread.max(f32::EPSILON) * 127.0;
+            data.push(value.round().clamp(0.0, 255.0) as u8);
+        }
+    }
+    data
+}
+
+/// Flattened edges of `events`, with open subpaths closed like a fill closes them.
+fn line_segments(events: &[PathEvent]) -> Vec<(Point, Point)> {
+    let mut segments = Vec::new();
+    for event in events.iter().copied().flattened(0.05) {
+        match event {
+            PathEvent::Line { from, to } => segments.push((from, to)),
+            PathEvent::End { last, first, .. } if last != first => segments.push((last, first)),
+            _ => {}
+        }
+    }
+    segments
+}
+
+fn segment_distance(p: Point, a: Point, b: Point) -> f32 {
+    let ab = b - a;
+    let len = ab.square_length();
+    let t = if len > 0.0 { ((p - a).dot(ab) / len).clamp(0.0, 1.0) } else { 0.0 };
+    (a + ab * t - p).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DEJAVU_SANS;
+
+    fn sample(glyph: &SdfGlyph, x: f32, y: f32) -> u8 {
+        let col = (x.floor() as i32 - glyph.bearing_x) as u32;
+        let row = (glyph.bearing_y - y.ceil() as i32) as u32;
+        glyph.data[(row * glyph.width + col) as usize]
+    }
+
+    #[test]
+    fn field_is_positive_inside_the_stem_of_i_and_negative_around_it() {
+        let font = load_font_sdf(DEJAVU_SANS, "I", 64, 4.0).unwrap();
+        let i = font.glyph('I').unwrap();
+        assert_eq!(i.data.len(), (i.width * i.height) as usize);
+
+        let mid_x = i.bearing_x as f32 + i.width as f32 / 2.0;
+        let mid_y = i.bearing_y as f32 - i.height as f32 / 2.0;
+        assert!(sample(i, mid_x, mid_y) > SDF_EDGE);
+        // Corners of the bitmap are a full spread away from the stem.
+        assert_eq!(i.data[0], 0);
+        assert_eq!(*i.data.last().unwrap(), 0);
+        assert!(sample(i, i.bearing_x as f32 + 1.0, mid_y) < SDF_EDGE);
+    }
+
+    #[test]
+    fn metrics_match_the_mesh_loader() {
+        let (px, spread) = (48, 3.0);
+        let sdf = load_font_sdf(DEJAVU_SANS, "Ig", px, spread).unwrap();
+        let options = FontOptions { em_size: px as f32, ..FontOptions::default() };
+        let mesh = load_font_with_options(DEJAVU_SANS, "Ig", &options).unwrap();
+        assert_eq!((sdf.ascender, sdf.descender, sdf.line_gap), (mesh.ascender, mesh.descender, mesh.line_gap));
+
+        for ch in ['I', 'g'] {
+            let (field, glyph) = (sdf.glyph(ch).unwrap(), mesh.glyph(ch).unwrap());
+            assert_eq!(field.advance, glyph.advance);
+            let (x_min, y_min, x_max, y_max) = glyph.bbox;
+            assert!(field.bearing_x as f32 <= x_min - spread && field.bearing_x as f32 > x_min - spread - 1.0);
+            assert!(field.bearing_y as f32 >= y_max + spread && (field.bearing_y as f32) < y_max + spread + 1.0);
+            assert!(field.bearing_x as f32 + field.width as f32 >= x_max + spread);
+            assert!(field.bearing_y as f32 - field.height as f32 <= y_min - spread);
+        }
+    }
+
+    #[test]
+    fn glyphs_without_outline_keep_their_advance() {
+        let font = load_font_sdf(DEJAVU_SANS, " ", 64, 4.0).unwrap();
+        let space = font.glyph(' ').unwrap();
+        assert!(space.data.is_empty());
+        assert_eq!((space.width, space.height), (0, 0));
+        assert!((space.advance - 651.0 / 2048.0 * 64.0).abs() < 1e-3);
+    }
+}
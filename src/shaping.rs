@@ -0,0 +1,104 @@
+//! Text shaping with rustybuzz, for scripts and typography that per-character glyph lookup
+//! gets wrong (Arabic joining, Indic reordering, ligatures, mark positioning).
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fontface::FontFace;
+use crate::ttfload::{parse_face, sfnt_data, FontLoadError, FontOptions};
+
+/// One positioned glyph of shaped text. Lengths are in em units like `Glyph::advance`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_id: u16,
+    /// Byte offset in the shaped text of the first character this glyph belongs to; glyphs
+    /// of one ligature or decomposition share it, which is what caret mapping needs.
+    pub cluster: u32,
+    pub x_advance: f32,
+    pub y_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+/// Shapes `text` with face 0 of `face_data`. `features` use the CSS-like syntax of rustybuzz,
+/// e.g. `"kern"`, `"-liga"` or `"ss01=1"`. Glyphs are returned in visual order, so
+/// right-to-left runs come out reversed with positive advances.
+pub fn shape_text(face_data: &[u8], text: &str, features: &[&str]) -> Result<Vec<ShapedGlyph>, FontLoadError> {
+    shape_text_with_options(face_data, text, features, &FontOptions::default())
+}
+
+/// Like [`shape_text`], for the face and variation instance selected by `options`.
+pub fn shape_text_with_options(face_data: &[u8], text: &str, features: &[&str], options: &FontOptions) -> Result<Vec<ShapedGlyph>, FontLoadError> {
+    let face_data = sfnt_data(face_data)?;
+    let face = parse_face(&face_data, options)?;
+    let scale = options.scale(&face) as f32;
+    let face = rustybuzz::Face::from_face(face).ok_or(FontLoadError::UnshapeableFace)?;
+    let features = features.iter()
+        .map(|f| rustybuzz::Feature::from_str(f).map_err(|_| FontLoadError::InvalidFeature(f.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(&face, &features, buffer);
+
+    Ok(output.glyph_infos().iter().zip(output.glyph_positions())
+        .map(|(info, pos)| ShapedGlyph {
+            glyph_id: info.glyph_id as u16,
+            cluster: info.cluster,
+            x_advance: pos.x_advance as f32 * scale,
+            y_advance: pos.y_advance as f32 * scale,
+            x_offset: pos.x_offset as f32 * scale,
+            y_offset: pos.y_offset as f32 * scale,
+        })
+        .collect())
+}
+
+impl FontFace {
+    /// Shapes `text` with this face and tessellates every glyph the result refers to, so each
+    /// `glyph_id` can then be looked up with [`FontFace::glyph_by_id`].
+    pub fn shape(&mut self, text: &str, features: &[&str]) -> Result<Vec<ShapedGlyph>, FontLoadError> {
+        let shaped = shape_text_with_options(self.data(), text, features, self.options())?;
+        for glyph in &shaped {
+            self.glyph_by_id(glyph.glyph_id)?;
+        }
+        Ok(shaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // "Salaam": lam and alef join into one ligature glyph.
+    const ARABIC: &str = "\u{0633}\u{0644}\u{0627}\u{0645}";
+
+    #[test]
+    fn arabic_is_shaped_right_to_left() {
+        let shaped = shape_text(&fixtures::read("DejaVuSans.ttf"), ARABIC, &[]).unwrap();
+        assert_ne!(shaped.len(), ARABIC.chars().count());
+        let clusters: Vec<u32> = shaped.iter().map(|g| g.cluster).collect();
+        assert!(clusters.windows(2).all(|w| w[0] > w[1]), "clusters {:?} not in visual order", clusters);
+        assert_eq!(clusters.last(), Some(&0));
+        assert!(shaped.iter().all(|g| g.x_advance > 0.0 && g.x_advance < 1.5));
+    }
+
+    #[test]
+    fn shaped_glyphs_are_tessellated_by_id() {
+        let mut face = FontFace::from_file(fixtures::DEJAVU_SANS).unwrap();
+        let shaped = face.shape(ARABIC, &[]).unwrap();
+        for g in &shaped {
+            let glyph = face.glyph_by_id(g.glyph_id).unwrap().unwrap();
+            assert!(!glyph.outline.indices.is_empty());
+            assert!((glyph.advance - g.x_advance).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn invalid_features_are_an_error() {
+        let result = shape_text(&fixtures::read("DejaVuSans.ttf"), "fi", &["kern", "not a feature"]);
+        assert!(matches!(result, Err(FontLoadError::InvalidFeature(f)) if f == "not a feature"));
+    }
+}
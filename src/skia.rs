@@ -0,0 +1,151 @@
+//! Conversions to tiny-skia types for CPU-side rasterization of single primitives.
+//!
+//! Loaded geometry lives in the crate's y-up space, while tiny-skia pixmaps are y-down, so
+//! every conversion takes the transform mapping one into the other explicitly, e.g.
+//! `Transform::from_row(w, 0.0, 0.0, -h, 0.0, h)` for meshes produced by `load_svg`.
+
+use lyon::path::PathEvent;
+use tiny_skia::{Color, GradientStop, LinearGradient, Paint, Path, PathBuilder, Point, Shader, SpreadMode, Transform};
+
+use crate::path::RenderablePath;
+
+/// Converts path events to a tiny-skia path, applying `transform`. Returns `None` for empty
+/// or degenerate outlines.
+pub fn outline_to_skia_path(events: &[PathEvent], transform: Transform) -> Option<Path> {
+    let mut pb = PathBuilder::new();
+    for event in events {
+        match *event {
+            PathEvent::Begin { at } => pb.move_to(at.x, at.y),
+            PathEvent::Line { to, .. } => pb.line_to(to.x, to.y),
+            PathEvent::Quadratic { ctrl, to, .. } => pb.quad_to(ctrl.x, ctrl.y, to.x, to.y),
+            PathEvent::Cubic { ctrl1, ctrl2, to, .. } => pb.cubic_to(ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y),
+            PathEvent::End { close, .. } => {
+                if close {
+                    pb.close()
+                }
+            }
+        }
+    }
+    pb.finish()?.transform(transform)
+}
+
+/// Builds a path from the triangles of an already tessellated primitive, for when the
+/// original outline is no longer available. `transform` applies after `local_transform`.
+pub fn mesh_to_skia_path(path: &RenderablePath, transform: Transform) -> Option<Path> {
+    let path = path.flattened();
+    let verts = &path.vertices.vertices;
+    let mut pb = PathBuilder::new();
+    for tri in path.vertices.indices.chunks_exact(3) {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| verts[i as usize].position);
+        pb.move_to(a[0], a[1]);
+        pb.line_to(b[0], b[1]);
+        pb.line_to(c[0], c[1]);
+        pb.close();
+    }
+    pb.finish()?.transform(transform)
+}
+
+fn to_color(rgba: [f32; 4]) -> Color {
+    let [r, g, b, a] = rgba.map(|c| c.clamp(0.0, 1.0));
+    Color::from_rgba(r, g, b, a).unwrap_or(Color::BLACK)
+}
+
+/// Paint of `path`: a solid color, or a linear gradient shader whose start/end points are
+/// mapped by `local_transform` and `transform` like the geometry. `path_opacity` is folded
+/// into the alpha.
+pub fn paint_to_skia(path: &RenderablePath, transform: Transform) -> Paint<'static> {
+    let path = path.flattened();
+    let mut paint = Paint {
+        shader: Shader::SolidColor(to_color(path.bgcolor)),
+        anti_alias: true,
+        ..Default::default()
+    };
+
+    if let (Some(stops), Some(start), Some(end)) = (path.gradient_color_stops(), path.gradient_start, path.gradient_end) {
+        let stops = stops.into_iter()
+            .map(|(p, c)| GradientStop::new(p, to_color(c)))
+            .collect();
+        if let Some(shader) = LinearGradient::new(
+            Point::from_xy(start.0, start.1),
+            Point::from_xy(end.0, end.1),
+            stops,
+            SpreadMode::Pad,
+            transform,
+        ) {
+            paint.shader = shader;
+        }
+    }
+    paint.shader.apply_opacity(path.path_opacity.clamp(0.0, 1.0));
+    paint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_skia::{FillRule, Pixmap};
+    use crate::svgload::{load_svg_data, LoadOptions};
+
+    fn render(paths: &[RenderablePath], size: u32) -> Pixmap {
+        let mut pixmap = Pixmap::new(size, size).unwrap();
+        let to_pixels = Transform::from_row(size as f32, 0.0, 0.0, -(size as f32), 0.0, size as f32);
+        for path in paths {
+            let skia_path = mesh_to_skia_path(path, to_pixels).unwrap();
+            pixmap.fill_path(&skia_path, &paint_to_skia(path, to_pixels), FillRule::Winding, Transform::identity(), None);
+        }
+        pixmap
+    }
+
+    #[test]
+    fn red_circle_fills_its_center() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+            <circle cx="32" cy="32" r="24" fill="red"/></svg>"#;
+        let pixmap = render(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths(), 64);
+        let center = pixmap.pixel(32, 32).unwrap();
+        assert_eq!((center.red(), center.green(), center.blue(), center.alpha()), (255, 0, 0, 255));
+        assert_eq!(pixmap.pixel(1, 1).unwrap().alpha(), 0);
+    }
+
+    #[test]
+    fn gradient_and_opacity_reach_the_paint() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64">
+            <linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="64" y2="0">
+                <stop offset="0" stop-color="#000"/><stop offset="1" stop-color="#fff"/>
+            </linearGradient>
+            <rect width="64" height="64" fill="url(#g)" opacity="0.5"/></svg>"##;
+        let pixmap = render(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths(), 64);
+        let (left, right) = (pixmap.pixel(2, 32).unwrap(), pixmap.pixel(61, 32).unwrap());
+        assert!((left.alpha() as i32 - 128).abs() <= 1 && (right.alpha() as i32 - 128).abs() <= 1);
+        assert!(left.red() < 20 && right.red() > 110);
+    }
+
+    #[test]
+    fn empty_outline_has_no_path() {
+        assert!(outline_to_skia_path(&[], Transform::identity()).is_none());
+        let square = [
+            PathEvent::Begin { at: lyon::math::point(0.0, 0.0) },
+            PathEvent::Line { from: lyon::math::point(0.0, 0.0), to: lyon::math::point(2.0, 0.0) },
+            PathEvent::Line { from: lyon::math::point(2.0, 0.0), to: lyon::math::point(2.0, 2.0) },
+            PathEvent::End { last: lyon::math::point(2.0, 2.0), first: lyon::math::point(0.0, 0.0), close: true },
+        ];
+        let path = outline_to_skia_path(&square, Transform::from_scale(3.0, 3.0)).unwrap();
+        assert_eq!((path.bounds().right(), path.bounds().bottom()), (6.0, 6.0));
+    }
+
+    #[test]
+    fn local_transforms_are_applied() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32">
+            <linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="8" y2="0">
+                <stop offset="0" stop-color="#f00"/><stop offset="1" stop-color="#00f"/>
+            </linearGradient>
+            <g transform="translate(16 8)"><rect width="8" height="8" fill="url(#g)"/></g>
+        </svg>"##;
+        let flat = render(&load_svg_data(svg, &LoadOptions::default()).unwrap().into_paths(), 32);
+        let local = render(&load_svg_data(svg, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap().into_paths(), 32);
+        let (left, right) = (local.pixel(16, 12).unwrap(), local.pixel(23, 12).unwrap());
+        assert!(left.red() > 200 && left.blue() < 40, "{:?}", left);
+        assert!(right.blue() > 200 && right.red() < 40, "{:?}", right);
+        assert_eq!(local.pixel(4, 4).unwrap().alpha(), 0);
+        let diff = flat.data().iter().zip(local.data()).map(|(a, b)| a.abs_diff(*b)).max();
+        assert!(diff <= Some(1), "{:?}", diff);
+    }
+}
@@ -1,28 +1,487 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::ops::Range;
+use std::sync::Arc;
 
 use lyon::math::Point;
 use lyon::path::PathEvent;
 use lyon::tessellation::*;
-use rctree::NodeEdge;
-use usvg::{LinearGradient, NodeKind, Paint, Transform, Tree};
-use crate::path::{RenderablePath, GpuVertex};
+use rctree::{Node, NodeEdge};
+use usvg::{LinearGradient, NodeKind, Paint, Pattern, Transform, Tree, Units};
+use serde::{Serialize, Deserialize};
+use crate::path::{matrix_from_transform, GpuVertex, RenderableImage, RenderablePath, ScenePrimitive};
 
-pub fn load_svg(filename: &str) -> Vec<RenderablePath> {
-    let opt = usvg::Options::default();
-    let file_data = std::fs::read(filename).unwrap();
-    let rtree = Tree::from_data(&file_data, &opt.to_ref()).unwrap();
+/// Tessellated primitives of a document together with its canvas.
+#[derive(Clone, Debug, Default)]
+pub struct LoadedSvg {
+    /// Paths and embedded images in drawing order. Vertex `prim_id`s, including those of the
+    /// image quads, are indices in `primitives`, so each image has a `prim_id` of its own.
+    pub primitives: Vec<ScenePrimitive>,
+    pub metadata: SvgMetadata,
+    pub stats: SvgStats,
+    /// Indices in `primitives` of the paths drawn by each element `id`, including the paths
+    /// of a group's descendants.
+    pub id_index: HashMap<String, Vec<usize>>,
+    /// Indices in `primitives` of the paths drawn by elements of each CSS class, from their
+    /// `class` attributes.
+    pub class_index: HashMap<String, Vec<usize>>,
+}
+
+impl LoadedSvg {
+    pub fn paths(&self) -> impl Iterator<Item = &RenderablePath> {
+        self.primitives.iter().filter_map(ScenePrimitive::as_path)
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &RenderableImage> {
+        self.primitives.iter().filter_map(ScenePrimitive::as_image)
+    }
+
+    /// Paths drawn by the element with `id` and its descendants, in drawing order.
+    pub fn by_id(&self, id: &str) -> Vec<&RenderablePath> {
+        self.indexed_paths(self.id_index.get(id))
+    }
+
+    /// Paths drawn by the elements of class `class` and their descendants, in drawing order.
+    pub fn by_class(&self, class: &str) -> Vec<&RenderablePath> {
+        self.indexed_paths(self.class_index.get(class))
+    }
+
+    fn indexed_paths(&self, indices: Option<&Vec<usize>>) -> Vec<&RenderablePath> {
+        indices.into_iter().flatten().filter_map(|&i| self.primitives.get(i)?.as_path()).collect()
+    }
+
+    /// Index in `primitives` of the topmost path whose mesh covers `(x, y)`, in mesh space;
+    /// the point is mapped into the space of each path's vertices through its
+    /// `local_transform`. Draw order is the order of `primitives`; `z_index` is not considered.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<usize> {
+        self.primitives.iter().rposition(|p| p.as_path().is_some_and(|path| {
+            path.to_vertex_space((x, y))
+                .is_some_and(|point| path.triangles().any(|(a, b, c)| triangle_contains(a, b, c, point)))
+        }))
+    }
+
+    /// The paths, dropping the images.
+    pub fn into_paths(self) -> Vec<RenderablePath> {
+        self.primitives.into_iter().filter_map(|p| match p {
+            ScenePrimitive::Path(path) => Some(path),
+            ScenePrimitive::Image(_) => None,
+        }).collect()
+    }
+}
+
+/// Whether `p` lies inside or on the edge of the triangle `a b c`, of either winding.
+fn triangle_contains(a: [f32; 2], b: [f32; 2], c: [f32; 2], p: [f32; 2]) -> bool {
+    let side = |u: [f32; 2], v: [f32; 2]| (v[0] - u[0]) * (p[1] - u[1]) - (v[1] - u[1]) * (p[0] - u[0]);
+    let (d0, d1, d2) = (side(a, b), side(b, c), side(c, a));
+    let has_neg = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+    let has_pos = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+    !(has_neg && has_pos)
+}
+
+/// Notes on how a document was tessellated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SvgStats {
+    /// Strokes kept as separate paths although `LoadOptions::stroke_as_separate_primitive`
+    /// is off.
+    pub paint_conflicts: Vec<PaintConflict>,
+    /// Ids of the paths whose pattern fill would need more than 4096 tiles and was left
+    /// empty; an empty string for paths without an id.
+    pub skipped_patterns: Vec<String>,
+}
+
+/// Why a stroke was not merged into its fill.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaintConflict {
+    /// The stroke paint differs from the fill paint; `path` indexes [`LoadedSvg::paths`] at
+    /// the fill, which the stroke follows.
+    StrokeDiffers { path: usize },
+}
+
+/// Canvas of a loaded document, for setting up the render target.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SvgMetadata {
+    /// Size of the canvas in pixels, from the `width` and `height` attributes.
+    pub width: u32,
+    pub height: u32,
+    /// `(x, y, width, height)` of the `viewBox`; the canvas size if the document has none.
+    pub view_box: (f64, f64, f64, f64),
+}
+
+#[derive(Debug)]
+pub enum SvgLoadError {
+    Io(std::io::Error),
+    Parse(usvg::Error),
+}
+
+impl fmt::Display for SvgLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgLoadError::Io(e) => write!(f, "cannot read svg: {}", e),
+            SvgLoadError::Parse(e) => write!(f, "cannot parse svg: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SvgLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SvgLoadError::Io(e) => Some(e),
+            SvgLoadError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for SvgLoadError {
+    fn from(e: std::io::Error) -> Self {
+        SvgLoadError::Io(e)
+    }
+}
+
+impl From<usvg::Error> for SvgLoadError {
+    fn from(e: usvg::Error) -> Self {
+        SvgLoadError::Parse(e)
+    }
+}
+
+#[must_use = "loading an SVG without using the result is a bug"]
+pub fn load_svg(filename: &str) -> Result<LoadedSvg, SvgLoadError> {
+    let file_data = std::fs::read(filename)?;
+    load_svg_from_bytes(&file_data)
+}
+
+/// Tessellates the SVG document `svg`. This is the entry point for new code; the other
+/// loaders read files or bytes, decompress svgz data and delegate to it.
+pub fn tessellate_svg_string(svg: &str, options: &LoadOptions) -> Result<LoadedSvg, SvgLoadError> {
+    // Named groups keep the ids of `<g>` elements for `LoadedSvg::id_index`.
+    let opt = usvg::Options {
+        keep_named_groups: true,
+        ..usvg::Options::default()
+    };
+    let text = with_current_color(svg, options.current_color);
+    let rtree = Tree::from_str(&text, &opt.to_ref())?;
+    let svg = rtree.svg_node();
+    let view_box = svg.view_box.rect;
+    let metadata = SvgMetadata {
+        width: svg.size.width() as u32,
+        height: svg.size.height() as u32,
+        view_box: (view_box.x(), view_box.y(), view_box.width(), view_box.height()),
+    };
+    let mut images = Vec::new();
+    let mut stats = SvgStats::default();
+    let mut path_ranges = Vec::new();
+    let paths = tessellate_nodes(&rtree.root(), Vec::new(), HashMap::new(), HashMap::new(), Some(&mut images),
+                                 Some(&mut stats), Some(&mut path_ranges), options);
+
+    // Each image goes after the paths drawn before it in the document and takes a prim_id of
+    // its own, so the prim_ids of the paths after it move up to their index in `primitives`.
+    let renumber = !images.is_empty();
+    let placed = |mut image: RenderableImage, slot: usize| {
+        for v in &mut image.quad.vertices {
+            v.prim_id = slot as u32;
+        }
+        ScenePrimitive::Image(image)
+    };
+    let mut primitives = Vec::with_capacity(paths.len() + images.len());
+    let mut path_slots = Vec::with_capacity(paths.len());
+    let mut images = images.into_iter().peekable();
+    for (i, mut path) in paths.into_iter().enumerate() {
+        while let Some((_, image)) = images.next_if(|(before, _)| *before == i) {
+            primitives.push(placed(image, primitives.len()));
+        }
+        if renumber {
+            let prim_id = primitives.len() as u32;
+            for v in &mut path.vertices.vertices {
+                v.prim_id = prim_id;
+            }
+        }
+        path_slots.push(primitives.len());
+        primitives.push(ScenePrimitive::Path(path));
+    }
+    for (_, image) in images {
+        primitives.push(placed(image, primitives.len()));
+    }
+
+    let to_slots = |mut index: HashMap<String, Vec<usize>>| {
+        for i in index.values_mut().flatten() {
+            *i = path_slots[*i];
+        }
+        index
+    };
+    let id_index = to_slots(index_ids(&rtree, &path_ranges));
+    let class_index = to_slots(index_classes(&text, &rtree, &path_ranges, &opt));
+    Ok(LoadedSvg { primitives, metadata, stats, id_index, class_index })
+}
+
+
+/// Loads `filename`, calling `progress(current, total)` after each path is tessellated.
+pub fn load_svg_with_progress<F>(filename: &str, progress: F) -> Result<LoadedSvg, SvgLoadError>
+    where F: Fn(usize, usize) + Send + Sync + 'static {
+    let file_data = std::fs::read(filename)?;
+    let options = LoadOptions {
+        progress: Some(Arc::new(progress)),
+        ..LoadOptions::default()
+    };
+    load_svg_data(&file_data, &options)
+}
+
+#[derive(Clone)]
+pub struct LoadOptions {
+    /// Maximum flattening error of curves, in SVG user units.
+    pub tolerance: f32,
+    /// Called with `(current, total)` after each path is tessellated.
+    pub progress: Option<Arc<dyn Fn(usize, usize) + Send + Sync>>,
+    /// Color that `currentColor` fills and strokes resolve to when the document sets no
+    /// `color` of its own, like the CSS `color` of the embedding context.
+    pub current_color: [f32; 4],
+    /// Emit each stroke as its own path. When off, a stroke with the same paint as its fill
+    /// is merged into the fill's mesh; other strokes stay separate and are recorded in
+    /// `SvgStats::paint_conflicts`.
+    pub stroke_as_separate_primitive: bool,
+    /// Bake each path's composed transform, including the mapping of the document into the
+    /// unit square, into its vertex positions and gradient endpoints, so meshes need no
+    /// further transform. When off, they stay in the path's own user space and the transform
+    /// is stored in `RenderablePath::local_transform`. Pattern fills are always baked.
+    /// The crate's consumers, from [`LoadedSvg::hit_test`] and `RenderablePath::bounding_box`
+    /// to batching, GPU packing and rasterization, work in mesh space either way.
+    pub flatten_transforms: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            tolerance: 0.1,
+            progress: None,
+            current_color: [1.0, 1.0, 1.0, 1.0],
+            stroke_as_separate_primitive: true,
+            flatten_transforms: true,
+        }
+    }
+}
+
+impl fmt::Debug for LoadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadOptions")
+            .field("tolerance", &self.tolerance)
+            .field("progress", &self.progress.is_some())
+            .field("current_color", &self.current_color)
+            .field("stroke_as_separate_primitive", &self.stroke_as_separate_primitive)
+            .field("flatten_transforms", &self.flatten_transforms)
+            .finish()
+    }
+}
+
+/// Like [`tessellate_svg_string`] with default options; `data` may also be compressed (svgz).
+pub fn load_svg_from_bytes(data: &[u8]) -> Result<LoadedSvg, SvgLoadError> {
+    load_svg_data(data, &LoadOptions::default())
+}
+
+/// Like [`tessellate_svg_string`] for the bytes of a plain or compressed (svgz) document.
+pub fn load_svg_data(data: &[u8], options: &LoadOptions) -> Result<LoadedSvg, SvgLoadError> {
+    tessellate_svg_string(&svg_text(data)?, options)
+}
+
+/// Same as [`load_svg_data`], from when that returned only the paths.
+pub fn load_svg_with_metadata(data: &[u8], options: &LoadOptions) -> Result<LoadedSvg, SvgLoadError> {
+    load_svg_data(data, options)
+}
+
+/// Text of the SVG document `data`, decompressed if it is svgz.
+fn svg_text(data: &[u8]) -> Result<Cow<'_, str>, usvg::Error> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data).read_to_string(&mut text).map_err(|_| usvg::Error::MalformedGZip)?;
+        return Ok(Cow::Owned(text));
+    }
+    std::str::from_utf8(data).map(Cow::Borrowed).map_err(|_| usvg::Error::NotAnUtf8Str)
+}
+
+/// Indices of the paths drawn by each element id of `tree`, where the `i`th of its
+/// [`drawn_paths`] drew the paths `ranges[i]`. Groups list the paths of their descendants.
+fn index_ids(tree: &Tree, ranges: &[Range<usize>]) -> HashMap<String, Vec<usize>> {
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (node, range) in drawn_paths(&tree.root()).zip(ranges) {
+        for ancestor in node.ancestors() {
+            let id = match &*ancestor.borrow() {
+                NodeKind::Group(g) => g.id.clone(),
+                NodeKind::Path(p) => p.id.clone(),
+                _ => continue,
+            };
+            if !id.is_empty() {
+                index.entry(id).or_default().extend(range.clone());
+            }
+        }
+    }
+    index
+}
+
+/// Indices of the paths drawn by the elements of each class of the document `text`, which
+/// converted to `tree` with `opt`; see [`index_ids`] for `ranges`. usvg keeps ids but drops
+/// classes, so classes are read with roxmltree and matched to paths through element ids.
+/// Classed elements without an id are matched through a second conversion of a copy of
+/// `text` that gives them unused ids; it draws the same paths in the same order.
+fn index_classes(text: &str, tree: &Tree, ranges: &[Range<usize>], opt: &usvg::Options) -> HashMap<String, Vec<usize>> {
+    let doc = match roxmltree::Document::parse_with_options(text, roxmltree::ParsingOptions { allow_dtd: true }) {
+        Ok(doc) => doc,
+        Err(_) => return HashMap::new(),
+    };
+    let prefix = unused_id_prefix(&doc);
+    let mut classes: HashMap<String, Vec<&str>> = HashMap::new();
+    let mut generated = Vec::new();
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        let class = match node.attribute("class") {
+            Some(class) => class,
+            None => continue,
+        };
+        let id = match (node.attribute("id"), start_tag_name_end(text, node)) {
+            (Some(id), _) => id.to_string(),
+            (None, Some(at)) => {
+                let id = format!("{}{}", prefix, generated.len());
+                generated.push((at, id.clone()));
+                id
+            }
+            (None, None) => continue,
+        };
+        classes.entry(id).or_default().extend(class.split_whitespace());
+    }
+    if classes.is_empty() {
+        return HashMap::new();
+    }
+
+    let copy;
+    let tree = match generated.is_empty() {
+        true => tree,
+        false => {
+            // Elements expanded from the same entity share a start tag, and so the first id.
+            generated.sort_by_key(|(at, _)| *at);
+            generated.dedup_by_key(|(at, _)| *at);
+            let mut with_ids = String::with_capacity(text.len() + generated.len() * 32);
+            let mut copied = 0;
+            for (at, id) in &generated {
+                with_ids.push_str(&text[copied..*at]);
+                with_ids.push_str(&format!(" id=\"{}\"", id));
+                copied = *at;
+            }
+            with_ids.push_str(&text[copied..]);
+            copy = match Tree::from_str(&with_ids, &opt.to_ref()) {
+                Ok(copy) => copy,
+                Err(_) => return HashMap::new(),
+            };
+            &copy
+        }
+    };
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (id, indices) in index_ids(tree, ranges) {
+        for class in classes.get(&id).into_iter().flatten() {
+            index.entry(class.to_string()).or_default().extend(&indices);
+        }
+    }
+    for indices in index.values_mut() {
+        indices.sort_unstable();
+        indices.dedup();
+    }
+    index
+}
+
+/// A prefix for generated ids that no id of `doc` starts with.
+fn unused_id_prefix(doc: &roxmltree::Document) -> String {
+    let ids: Vec<&str> = doc.descendants().filter_map(|n| n.attribute("id")).collect();
+    let mut prefix = String::from("svg-load-class-");
+    while ids.iter().any(|id| id.starts_with(&prefix)) {
+        prefix.push_str("x-");
+    }
+    prefix
+}
+
+/// Byte offset in `text` right after the tag name of the start tag of `element`, where
+/// attributes can be inserted.
+fn start_tag_name_end(text: &str, element: roxmltree::Node) -> Option<usize> {
+    let start = element.range().start;
+    let tag = text.get(start..)?.strip_prefix('<')?;
+    let len = tag.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+    Some(start + 1 + len)
+}
+
+/// Adds a `color` attribute to the root element so that `currentColor` inherits `color`
+/// unless the root already sets one. usvg resolves a missing `color` to black otherwise.
+/// The root is found with roxmltree, so markup in comments, CDATA or the DOCTYPE is skipped.
+fn with_current_color(text: &str, color: [f32; 4]) -> Cow<'_, str> {
+    let doc = match roxmltree::Document::parse_with_options(text, roxmltree::ParsingOptions { allow_dtd: true }) {
+        Ok(doc) => doc,
+        Err(_) => return Cow::Borrowed(text),
+    };
+    let root = doc.root_element();
+    let at = match start_tag_name_end(text, root) {
+        Some(at) if !root.has_attribute("color") => at,
+        _ => return Cow::Borrowed(text),
+    };
+    let [r, g, b, a] = color.map(|c| c.clamp(0.0, 1.0));
+    let attr = format!(" color=\"rgba({},{},{},{})\"", (r * 255.0).round(), (g * 255.0).round(), (b * 255.0).round(), a);
+    Cow::Owned([&text[..at], &attr, &text[at..]].concat())
+}
+
+/// Tessellates glyph `glyph_id` of a document from an OpenType `SVG ` table. The glyph is the
+/// element with id `glyph<N>`, or the whole document if there is none. Glyph documents are in
+/// font units with y pointing down and the baseline at y = 0; `transform` maps that space to
+/// the mesh space.
+pub(crate) fn load_svg_glyph(data: &[u8], glyph_id: u16, transform: Transform, options: &LoadOptions) -> Result<Vec<RenderablePath>, usvg::Error> {
+    let opt = usvg::Options {
+        keep_named_groups: true,
+        ..usvg::Options::default()
+    };
+    let rtree = Tree::from_data(data, &opt.to_ref())?;
+
+    let mut gradients = HashMap::new();
+    let mut patterns = HashMap::new();
+    for node in rtree.root().descendants() {
+        match &*node.borrow() {
+            NodeKind::LinearGradient(g) => { gradients.insert(g.id.clone(), g.clone()); }
+            NodeKind::Pattern(p) => { patterns.insert(p.id.clone(), node.clone()); }
+            _ => {}
+        }
+    }
+
+    let root = rtree.node_by_id(&format!("glyph{}", glyph_id)).unwrap_or_else(|| rtree.root());
+    let mut transforms = vec![transform];
+    let mut ancestors: Vec<Transform> = root.ancestors().skip(1)
+        .filter_map(|n| match &*n.borrow() {
+            NodeKind::Group(g) => Some(g.transform),
+            _ => None,
+        })
+        .collect();
+    ancestors.reverse();
+    transforms.extend(ancestors);
+    Ok(tessellate_nodes(&root, transforms, gradients, patterns, None, None, None, options))
+}
 
+/// Tessellates the paths under `root`. `transforms` are the transforms of the nodes above
+/// `root`, outermost first; paint servers outside `root` must be passed in `gradients` and
+/// `patterns`. Raster images are added to `images`, if given, with the number of paths drawn
+/// before them, and strokes that could not be merged into their fill to `stats`. `path_ranges`,
+/// if given, receives the indices of the paths drawn by each of the [`drawn_paths`] of `root`.
+#[allow(clippy::too_many_arguments)]
+fn tessellate_nodes(root: &Node<NodeKind>, mut transforms: Vec<Transform>, mut gradients: HashMap<String, LinearGradient>,
+                    mut patterns: HashMap<String, Node<NodeKind>>, mut images: Option<&mut Vec<(usize, RenderableImage)>>,
+                    mut stats: Option<&mut SvgStats>, mut path_ranges: Option<&mut Vec<Range<usize>>>,
+                    options: &LoadOptions) -> Vec<RenderablePath> {
     let mut fill_tess = FillTessellator::new();
     let mut stroke_tess = StrokeTessellator::new();
 
-    let mut gradients: HashMap<String, LinearGradient> = HashMap::new();
+    let mut pattern_depth = 0;
     let mut primitives : Vec<RenderablePath> = Vec::new();
+    // Group opacities below `root`; usvg moves element `opacity` onto groups.
+    let mut opacities: Vec<f32> = Vec::new();
 
     let mut size: (u32, u32) = (1,1);
 
-    let mut transforms = Vec::new();
+    let total = match options.progress {
+        Some(_) => drawn_paths(root).count(),
+        None => 0,
+    };
+    let mut done = 0;
 
-    for node_edge in rtree.root().traverse() {
+    for node_edge in root.traverse() {
         let (start, node) = match node_edge {
             NodeEdge::Start(x) => { (true, x) }
             NodeEdge::End(x) => { (false, x) }
@@ -30,9 +489,15 @@ pub fn load_svg(filename: &str) -> Vec<RenderablePath> {
 
         let data = &*node.borrow();
 
+        // Pattern content is only drawn through the fills that reference it.
+        if pattern_depth > 0 && !matches!(data, NodeKind::Pattern(_)) {
+            continue;
+        }
+
         match data {
             NodeKind::Svg(s) => {
-                if start {
+                // Callers passing their own transforms map the document space themselves.
+                if start && transforms.is_empty() {
                     size = (s.size.width() as u32, s.size.height() as u32);
                     let mut view = Transform::new_translate(-s.view_box.rect.x(), -s.view_box.rect.y());
                     view.append(&Transform::new_scale(1.0 / s.view_box.rect.width(), -1.0 / s.view_box.rect.height()));
@@ -49,7 +514,14 @@ pub fn load_svg(filename: &str) -> Vec<RenderablePath> {
             NodeKind::RadialGradient(_) => {}
             NodeKind::ClipPath(_) => {}
             NodeKind::Mask(_) => {}
-            NodeKind::Pattern(_) => {}
+            NodeKind::Pattern(pattern) => {
+                if start {
+                    patterns.insert(pattern.id.clone(), node.clone());
+                    pattern_depth += 1;
+                } else {
+                    pattern_depth -= 1;
+                }
+            }
             NodeKind::Filter(_) => {}
             NodeKind::Path(path) => {
                 if start {
@@ -58,56 +530,126 @@ pub fn load_svg(filename: &str) -> Vec<RenderablePath> {
                         transform.append(t);
                     }
                     transform.append(&data.transform());
-                    if path.fill.is_some() {
-                        let paint = &path.fill.as_ref().unwrap().paint;
-                        let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
-
-                        fill_tess
-                            .tessellate(
-                                convert_path(&path),
-                                &FillOptions::tolerance(0.1),
-                                &mut BuffersBuilder::new(
-                                    &mut mesh,
-                                    VertexCtor {
-                                        prim_id: primitives.len() as u32,
-                                        transform: transform.clone(),
-                                    },
-                                ),
-                            )
-                            .expect("Error during tesselation!");
-
-
-                        if path.stroke.is_some() {
-                            let mut mesh_s: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
-                            let stroke = path.stroke.as_ref().unwrap();
-                            let opts = convert_stroke(stroke);
-                            stroke_tess.tessellate(
-                                convert_path(path),
-                                &opts.with_tolerance(0.1),
-                                &mut BuffersBuilder::new(
-                                    &mut mesh_s,
-                                    VertexCtor {
-                                        prim_id: primitives.len() as u32,
-                                        transform: transform.clone(),
+                    // Without flattening, vertices and gradients stay in the path's user space.
+                    let (mesh_transform, local_transform) = match options.flatten_transforms {
+                        true => (transform, None),
+                        false => (Transform::default(), Some(matrix_from_transform(&transform))),
+                    };
+                    let first = primitives.len();
+                    // Fills are drawn before their strokes, as in SVG's default paint order.
+                    let mut plain_fill = None;
+                    if let Some(fill) = &path.fill {
+                        let paint = &fill.paint;
+                        let pattern = match paint {
+                            Paint::Link(link) => patterns.get(link),
+                            Paint::Color(_) => None,
+                        };
+                        let prim_id = primitives.len() as u32;
+                        match pattern {
+                            Some(pattern) => {
+                                let tiles = tessellate_pattern(pattern, path, fill.opacity.value() as f32, &transform, prim_id,
+                                                               &mut fill_tess, &mut stroke_tess, &mut gradients, size, options);
+                                match tiles {
+                                    Some(tiles) => primitives.extend(tiles),
+                                    None => if let Some(stats) = stats.as_deref_mut() {
+                                        stats.skipped_patterns.push(path.id.clone());
                                     },
-                                ),
-                            ).expect("Error during tesselation stroke!");
-
-                            let stoke_p = primitive_from_paint(&mut gradients, size.clone(), stroke.opacity.value() as f32, mesh_s, &stroke.paint, &transform);
-                            primitives.push(stoke_p);
+                                }
+                            }
+                            None => {
+                                let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+                                fill_tess
+                                    .tessellate(
+                                        convert_path(path),
+                                        &FillOptions::tolerance(options.tolerance),
+                                        &mut BuffersBuilder::new(
+                                            &mut mesh,
+                                            VertexCtor {
+                                                prim_id,
+                                                transform: mesh_transform,
+                                            },
+                                        ),
+                                    )
+                                    .expect("Error during tesselation!");
+                                let fill_p = primitive_from_paint(&mut gradients, size, fill.opacity.value() as f32, mesh, paint, &mesh_transform);
+                                plain_fill = Some(primitives.len());
+                                primitives.push(RenderablePath { local_transform, ..fill_p });
+                            }
                         }
+                    }
 
-                        let path = primitive_from_paint(&mut gradients, size.clone(), path.fill.as_ref().unwrap().opacity.value() as f32, mesh, paint, &transform);
-                        primitives.push(path);
+                    if let Some(stroke) = &path.stroke {
+                        let mut mesh_s: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+                        let opts = convert_stroke(stroke);
+                        stroke_tess.tessellate(
+                            convert_path(path),
+                            &opts.with_tolerance(options.tolerance),
+                            &mut BuffersBuilder::new(
+                                &mut mesh_s,
+                                VertexCtor {
+                                    prim_id: primitives.len() as u32,
+                                    transform: mesh_transform,
+                                },
+                            ),
+                        ).expect("Error during tesselation stroke!");
+
+                        let stroke_p = primitive_from_paint(&mut gradients, size, stroke.opacity.value() as f32, mesh_s, &stroke.paint, &mesh_transform);
+                        let stroke_p = RenderablePath { local_transform, ..stroke_p };
+                        match plain_fill.filter(|_| !options.stroke_as_separate_primitive) {
+                            Some(i) if stroke_p.paint() == primitives[i].paint() => {
+                                let fill_p = &mut primitives[i];
+                                let merged = VertexBuffers {
+                                    vertices: stroke_p.vertices.vertices.iter().map(|v| v.with_prim_id(i as u32)).collect(),
+                                    indices: stroke_p.vertices.indices,
+                                };
+                                append_mesh(&mut fill_p.vertices, &merged);
+                            }
+                            Some(i) => {
+                                if let Some(stats) = stats.as_deref_mut() {
+                                    stats.paint_conflicts.push(PaintConflict::StrokeDiffers { path: i });
+                                }
+                                primitives.push(stroke_p);
+                            }
+                            None => primitives.push(stroke_p),
+                        }
+                    }
+                    let opacity: f32 = opacities.iter().product();
+                    for primitive in &mut primitives[first..] {
+                        primitive.path_opacity = opacity;
+                    }
+                    if let Some(path_ranges) = path_ranges.as_deref_mut() {
+                        path_ranges.push(first..primitives.len());
+                    }
+                    if let Some(progress) = &options.progress {
+                        done += 1;
+                        progress(done, total);
+                    }
+                }
+            }
+            NodeKind::Image(image) => {
+                if let (true, Some(images)) = (start, images.as_deref_mut()) {
+                    let mut transform = Transform::default();
+                    for t in &transforms {
+                        transform.append(t);
+                    }
+                    transform.append(&image.transform);
+                    let (mesh_transform, local_transform) = match options.flatten_transforms {
+                        true => (transform, None),
+                        false => (Transform::default(), Some(matrix_from_transform(&transform))),
+                    };
+                    if let Some(image) = renderable_image(image, mesh_transform) {
+                        let opacity = opacities.iter().product();
+                        images.push((primitives.len(), RenderableImage { local_transform, opacity, ..image }));
                     }
                 }
             }
-            NodeKind::Image(_) => {}
             NodeKind::Group(g) => {
                 if start {
                     transforms.push(g.transform);
+                    opacities.push(g.opacity.value() as f32);
                 } else {
                     transforms.pop();
+                    opacities.pop();
                 }
             }
         }
@@ -115,16 +657,59 @@ pub fn load_svg(filename: &str) -> Vec<RenderablePath> {
     primitives
 }
 
+/// Path nodes under `root` that [`tessellate_nodes`] draws, in drawing order, i.e. those
+/// outside patterns.
+fn drawn_paths(root: &Node<NodeKind>) -> impl Iterator<Item = Node<NodeKind>> {
+    root.descendants()
+        .filter(|n| matches!(*n.borrow(), NodeKind::Path(_)))
+        .filter(|n| !n.ancestors().any(|a| matches!(*a.borrow(), NodeKind::Pattern(_))))
+}
+
+/// Quad and encoded data of a raster `image`; `transform` maps its user space to the space of
+/// the quad. The image is stretched over its viewport, `preserveAspectRatio` is not applied.
+/// Nested SVG images are skipped. The quad's `prim_id` is left at 0 for the caller to set.
+fn renderable_image(image: &usvg::Image, transform: Transform) -> Option<RenderableImage> {
+    let (mime_type, bytes) = match &image.kind {
+        usvg::ImageKind::PNG(data) => ("image/png", data),
+        usvg::ImageKind::JPEG(data) => ("image/jpeg", data),
+        usvg::ImageKind::GIF(data) => ("image/gif", data),
+        usvg::ImageKind::SVG(_) => return None,
+    };
+    let rect = image.view_box.rect;
+    let mut uv_to_quad = transform;
+    uv_to_quad.append(&Transform::new(rect.width(), 0.0, 0.0, rect.height(), rect.x(), rect.y()));
+
+    let mut quad = VertexBuffers::new();
+    for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+        let (x, y) = uv_to_quad.apply(u, v);
+        quad.vertices.push(GpuVertex { position: [x as f32, y as f32], prim_id: 0 });
+    }
+    quad.indices.extend_from_slice(&[0, 1, 2, 0, 2, 3]);
+    Some(RenderableImage {
+        bytes: bytes.to_vec(),
+        mime_type: mime_type.to_string(),
+        quad,
+        transform: matrix_from_transform(&uv_to_quad),
+        local_transform: None,
+        opacity: 1.0,
+    })
+}
+
+/// Appends the triangles of `other` to `mesh`.
+fn append_mesh(mesh: &mut VertexBuffers<GpuVertex, u32>, other: &VertexBuffers<GpuVertex, u32>) {
+    let base = mesh.vertices.len() as u32;
+    mesh.vertices.extend_from_slice(&other.vertices);
+    mesh.indices.extend(other.indices.iter().map(|i| i + base));
+}
+
 fn primitive_from_paint(gradients: &mut HashMap<String, LinearGradient>, size: (u32, u32), opacity: f32, mesh_s: VertexBuffers<GpuVertex, u32>, paint: &Paint, transform: &Transform) -> RenderablePath {
     match paint {
         Paint::Color(col) => {
             RenderablePath::from_color(size, col, opacity, mesh_s)
         }
         Paint::Link(link) => {
-            let grad = gradients.get(link);
-
-            if grad.is_some() {
-                RenderablePath::from_gradient(size, grad.unwrap(), mesh_s, transform)
+            if let Some(grad) = gradients.get(link) {
+                RenderablePath::from_gradient(size, grad, mesh_s, transform)
             } else {
                 RenderablePath::new(size,mesh_s)
             }
@@ -132,6 +717,185 @@ fn primitive_from_paint(gradients: &mut HashMap<String, LinearGradient>, size: (
     }
 }
 
+/// Upper bound on the tiles of one pattern fill; larger fills are left empty and reported in
+/// `SvgStats::skipped_patterns`.
+const MAX_PATTERN_TILES: i64 = 4096;
+
+/// Tessellates the content of `pattern_node` once, repeats it over the bounding box of `path`
+/// and clips the tiles to that box, which approximates clipping them to the path itself.
+/// Returns one primitive per fill or stroke inside the pattern, or `None` if the fill would
+/// need more than `MAX_PATTERN_TILES` tiles.
+#[allow(clippy::too_many_arguments)]
+fn tessellate_pattern(pattern_node: &Node<NodeKind>, path: &usvg::Path, opacity: f32, transform: &Transform, prim_id: u32,
+                      fill_tess: &mut FillTessellator, stroke_tess: &mut StrokeTessellator,
+                      gradients: &mut HashMap<String, LinearGradient>, size: (u32, u32), options: &LoadOptions) -> Option<Vec<RenderablePath>> {
+    let pattern: Pattern = match &*pattern_node.borrow() {
+        NodeKind::Pattern(p) => p.clone(),
+        _ => return Some(Vec::new()),
+    };
+    let bbox = match path.data.bbox().and_then(|b| b.to_rect()) {
+        Some(b) => b,
+        None => return Some(Vec::new()),
+    };
+    let rect = match pattern.units {
+        Units::ObjectBoundingBox => pattern.rect.bbox_transform(bbox),
+        Units::UserSpaceOnUse => pattern.rect,
+    };
+    let inverse = match invert(&pattern.transform) {
+        Some(t) => t,
+        None => return Some(Vec::new()),
+    };
+
+    let corners = [(bbox.left(), bbox.top()), (bbox.right(), bbox.top()),
+                   (bbox.right(), bbox.bottom()), (bbox.left(), bbox.bottom())]
+        .map(|(x, y)| inverse.apply(x, y));
+    let min_x = corners.iter().map(|c| c.0).fold(f64::INFINITY, f64::min);
+    let max_x = corners.iter().map(|c| c.0).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = corners.iter().map(|c| c.1).fold(f64::INFINITY, f64::min);
+    let max_y = corners.iter().map(|c| c.1).fold(f64::NEG_INFINITY, f64::max);
+    let cols = ((min_x - rect.x()) / rect.width()).floor() as i64..((max_x - rect.x()) / rect.width()).ceil() as i64;
+    let rows = ((min_y - rect.y()) / rect.height()).floor() as i64..((max_y - rect.y()) / rect.height()).ceil() as i64;
+    if (cols.end - cols.start).saturating_mul(rows.end - rows.start) > MAX_PATTERN_TILES {
+        return None;
+    }
+
+    let content = match (pattern.view_box, pattern.content_units) {
+        (Some(vb), _) => usvg::utils::view_box_to_transform(vb.rect, vb.aspect, rect.size()),
+        (None, Units::ObjectBoundingBox) => Transform::new_scale(bbox.width(), bbox.height()),
+        (None, Units::UserSpaceOnUse) => Transform::default(),
+    };
+    let tile_transform = |col: i64, row: i64| {
+        let mut t = pattern.transform;
+        t.translate(rect.x() + col as f64 * rect.width(), rect.y() + row as f64 * rect.height());
+        t.append(&content);
+        t
+    };
+    let mut gradient_transform = *transform;
+    gradient_transform.append(&tile_transform(0, 0));
+    let (sx, sy) = tile_transform(0, 0).get_scale();
+    let tolerance = options.tolerance / sx.max(sy).max(f64::EPSILON) as f32;
+    let clip = ([bbox.left() as f32, bbox.top() as f32], [bbox.right() as f32, bbox.bottom() as f32]);
+
+    let mut primitives = Vec::new();
+    for (child, child_transform) in pattern_paths(pattern_node) {
+        let ctor = |id| VertexCtor { prim_id: id, transform: child_transform };
+        let mut pieces = Vec::new();
+        if let Some(fill) = &child.fill {
+            let mut tile: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+            let id = prim_id + primitives.len() as u32 + pieces.len() as u32;
+            if fill_tess.tessellate(convert_path(&child), &FillOptions::tolerance(tolerance),
+                                    &mut BuffersBuilder::new(&mut tile, ctor(id))).is_ok() {
+                pieces.push((tile, &fill.paint, fill.opacity.value() as f32));
+            }
+        }
+        if let Some(stroke) = &child.stroke {
+            let mut tile: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+            let id = prim_id + primitives.len() as u32 + pieces.len() as u32;
+            if stroke_tess.tessellate(convert_path(&child), &convert_stroke(stroke).with_tolerance(tolerance),
+                                      &mut BuffersBuilder::new(&mut tile, ctor(id))).is_ok() {
+                pieces.push((tile, &stroke.paint, stroke.opacity.value() as f32));
+            }
+        }
+
+        for (tile, paint, piece_opacity) in pieces {
+            let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    let t = tile_transform(col, row);
+                    let placed: Vec<GpuVertex> = tile.vertices.iter().map(|v| {
+                        let p = t.apply(v.position[0] as f64, v.position[1] as f64);
+                        GpuVertex { position: [p.0 as f32, p.1 as f32], prim_id: v.prim_id }
+                    }).collect();
+                    clip_triangles(&placed, &tile.indices, clip, &mut mesh);
+                }
+            }
+            for v in &mut mesh.vertices {
+                let p = transform.apply(v.position[0] as f64, v.position[1] as f64);
+                v.position = [p.0 as f32, p.1 as f32];
+            }
+            primitives.push(primitive_from_paint(gradients, size, opacity * piece_opacity, mesh, paint, &gradient_transform));
+        }
+    }
+    Some(primitives)
+}
+
+/// Paths below a pattern node with their transforms relative to the pattern content space.
+fn pattern_paths(pattern_node: &Node<NodeKind>) -> Vec<(usvg::Path, Transform)> {
+    let mut stack = vec![Transform::default()];
+    let mut paths = Vec::new();
+    for edge in pattern_node.traverse() {
+        match edge {
+            NodeEdge::Start(node) => match &*node.borrow() {
+                NodeKind::Group(g) => {
+                    let mut t = stack.last().copied().unwrap_or_default();
+                    t.append(&g.transform);
+                    stack.push(t);
+                }
+                NodeKind::Path(p) => {
+                    let mut t = stack.last().copied().unwrap_or_default();
+                    t.append(&p.transform);
+                    paths.push((p.clone(), t));
+                }
+                _ => {}
+            },
+            NodeEdge::End(node) => {
+                if let NodeKind::Group(_) = &*node.borrow() {
+                    stack.pop();
+                }
+            }
+        }
+    }
+    paths
+}
+
+fn invert(t: &Transform) -> Option<Transform> {
+    let det = t.a * t.d - t.b * t.c;
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(Transform::new(t.d / det, -t.b / det, -t.c / det, t.a / det,
+                        (t.c * t.f - t.d * t.e) / det, (t.b * t.e - t.a * t.f) / det))
+}
+
+/// Appends the triangles of `vertices`/`indices` clipped to the `(min, max)` box to `out`.
+/// Triangles fully inside are copied as is, the others are clipped and re-triangulated as fans.
+fn clip_triangles(vertices: &[GpuVertex], indices: &[u32], (min, max): ([f32; 2], [f32; 2]),
+                  out: &mut VertexBuffers<GpuVertex, u32>) {
+    let inside = |p: [f32; 2]| (0..2).all(|axis| p[axis] >= min[axis] && p[axis] <= max[axis]);
+    for tri in indices.chunks_exact(3) {
+        let corners = [tri[0], tri[1], tri[2]].map(|i| vertices[i as usize]);
+        let mut polygon: Vec<[f32; 2]> = corners.iter().map(|v| v.position).collect();
+        if !polygon.iter().all(|p| inside(*p)) {
+            for (axis, bound, keep_above) in [(0, min[0], true), (0, max[0], false), (1, min[1], true), (1, max[1], false)] {
+                let keep = |p: [f32; 2]| if keep_above { p[axis] >= bound } else { p[axis] <= bound };
+                let input = std::mem::take(&mut polygon);
+                for (i, &cur) in input.iter().enumerate() {
+                    let prev = input[(i + input.len() - 1) % input.len()];
+                    if keep(cur) != keep(prev) {
+                        let t = (bound - prev[axis]) / (cur[axis] - prev[axis]);
+                        polygon.push([prev[0] + (cur[0] - prev[0]) * t, prev[1] + (cur[1] - prev[1]) * t]);
+                    }
+                    if keep(cur) {
+                        polygon.push(cur);
+                    }
+                }
+                if polygon.is_empty() {
+                    break;
+                }
+            }
+        }
+        if polygon.len() < 3 {
+            continue;
+        }
+        let base = out.vertices.len() as u32;
+        let prim_id = corners[0].prim_id;
+        out.vertices.extend(polygon.iter().map(|&position| GpuVertex { position, prim_id }));
+        for i in 1..polygon.len() as u32 - 1 {
+            out.indices.extend_from_slice(&[base, base + i, base + i + 1]);
+        }
+    }
+}
+
 pub struct VertexCtor {
     pub prim_id: u32,
     pub transform: Transform,
@@ -169,6 +933,7 @@ pub struct PathConvIter<'a> {
     first: Point,
     needs_end: bool,
     deferred: Option<PathEvent>,
+    remaining: usize,
 }
 
 impl<'l> Iterator for PathConvIter<'l> {
@@ -179,6 +944,9 @@ impl<'l> Iterator for PathConvIter<'l> {
         }
 
         let next = self.iter.next();
+        if next.is_some() {
+            self.remaining -= 1;
+        }
         match next {
             Some(usvg::PathSegment::MoveTo { x, y }) => {
                 if self.needs_end {
@@ -251,15 +1019,28 @@ impl<'l> Iterator for PathConvIter<'l> {
             }
         }
     }
+
+    /// Every remaining segment yields one event, except a `MoveTo` that also has to end the
+    /// previous subpath, plus one trailing `End` for an unclosed last subpath.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let deferred = self.deferred.is_some() as usize;
+        let trailing = (self.needs_end || self.remaining > 0) as usize;
+        (self.remaining + deferred, Some(self.remaining * 2 + deferred + trailing))
+    }
 }
 
-pub fn convert_path(p: &usvg::Path) -> PathConvIter {
+/// Once `next` returns `None` the slice iterator is exhausted and the trailing `End` has been
+/// emitted, so every later call returns `None` too.
+impl std::iter::FusedIterator for PathConvIter<'_> {}
+
+pub fn convert_path(p: &usvg::Path) -> PathConvIter<'_> {
     PathConvIter {
         iter: p.data.iter(),
         first: Point::new(0.0, 0.0),
         prev: Point::new(0.0, 0.0),
         deferred: None,
         needs_end: false,
+        remaining: p.data.len(),
     }
 }
 
@@ -279,4 +1060,297 @@ pub fn convert_stroke(s: &usvg::Stroke) -> StrokeOptions {
         .with_line_width(s.width.value() as f32)
         .with_line_cap(linecap)
         .with_line_join(linejoin)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterned(tile: f64) -> String {
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">
+            <defs><pattern id="dots" width="{0}" height="{0}" patternUnits="userSpaceOnUse">
+                <rect width="{1}" height="{1}" fill="red"/>
+            </pattern></defs>
+            <rect id="area" width="1000" height="1000" fill="url(#dots)"/>
+        </svg>"#, tile, tile / 2.0)
+    }
+
+    #[test]
+    fn patterns_are_tiled_over_the_fill() {
+        let loaded = tessellate_svg_string(&patterned(100.0), &LoadOptions::default()).unwrap();
+        assert!(loaded.stats.skipped_patterns.is_empty());
+        assert_eq!(loaded.primitives.len(), 1);
+        let mesh = &loaded.paths().next().unwrap().vertices;
+        assert_eq!(mesh.indices.len(), 100 * 6);
+    }
+
+    #[test]
+    fn patterns_with_too_many_tiles_are_reported() {
+        let loaded = tessellate_svg_string(&patterned(1.0), &LoadOptions::default()).unwrap();
+        assert_eq!(loaded.stats.skipped_patterns, ["area"]);
+        assert!(loaded.paths().all(|p| p.vertices.indices.is_empty()));
+    }
+
+    const CURRENT_COLOR: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+        <rect width="10" height="10" fill="currentColor"/>
+    </svg>"#;
+
+    #[test]
+    fn current_color_comes_from_the_options() {
+        let options = LoadOptions { current_color: [0.0, 0.0, 1.0, 1.0], ..LoadOptions::default() };
+        let loaded = tessellate_svg_string(CURRENT_COLOR, &options).unwrap();
+        let colors: Vec<[f32; 4]> = loaded.paths().map(|p| p.bgcolor).collect();
+        assert_eq!(colors, [[0.0, 0.0, 1.0, 1.0]]);
+    }
+
+    #[test]
+    fn a_color_on_the_root_wins_over_the_options() {
+        let svg = CURRENT_COLOR.replace("<svg ", "<svg color=\"#ff0000\" ");
+        let options = LoadOptions { current_color: [0.0, 0.0, 1.0, 1.0], ..LoadOptions::default() };
+        let loaded = tessellate_svg_string(&svg, &options).unwrap();
+        assert_eq!(loaded.paths().next().unwrap().bgcolor, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    const CLASSED: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+        <rect class="swatch" width="5" height="10" fill="currentColor"/>
+        <rect width="5" height="10" x="5" fill="blue"/>
+    </svg>"#;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn every_loader_resolves_current_color_and_classes_like_the_string_entry_point() {
+        let options = LoadOptions { current_color: [0.0, 1.0, 0.0, 1.0], ..LoadOptions::default() };
+        let expected = tessellate_svg_string(CLASSED, &options).unwrap();
+        assert_eq!(expected.paths().next().unwrap().bgcolor, [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(expected.class_index["swatch"], [0]);
+
+        for data in [CLASSED.as_bytes().to_vec(), gzip(CLASSED)] {
+            let loaded = load_svg_with_metadata(&data, &options).unwrap();
+            assert_eq!(loaded.class_index, expected.class_index);
+            let loaded = load_svg_data(&data, &options).unwrap();
+            assert_eq!(loaded.class_index, expected.class_index);
+            let paths: Vec<&RenderablePath> = loaded.paths().collect();
+            assert_eq!(paths[0].bgcolor, [0.0, 1.0, 0.0, 1.0]);
+            assert_eq!(paths[1].bgcolor, expected.paths().nth(1).unwrap().bgcolor);
+        }
+        assert_eq!(load_svg_from_bytes(&gzip(CLASSED)).unwrap().class_index, expected.class_index);
+    }
+
+    #[test]
+    fn progress_loading_goes_through_the_same_pipeline() {
+        let path = std::env::temp_dir().join(format!("svg-load-progress-{}.svgz", std::process::id()));
+        std::fs::write(&path, gzip(CLASSED)).unwrap();
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let paths = load_svg_with_progress(path.to_str().unwrap(), move |current, total| seen.lock().unwrap().push((current, total)));
+        std::fs::remove_file(&path).ok();
+        let loaded = paths.unwrap();
+        assert_eq!(loaded.class_index["swatch"], [0]);
+        assert_eq!(loaded.metadata.width, 10);
+        let paths: Vec<&RenderablePath> = loaded.paths().collect();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].bgcolor, LoadOptions::default().current_color);
+        assert_eq!(*calls.lock().unwrap(), [(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn undecodable_data_is_a_parse_error() {
+        assert!(matches!(load_svg_data(&[0xff, 0xfe, 0x00], &LoadOptions::default()), Err(SvgLoadError::Parse(usvg::Error::NotAnUtf8Str))));
+        assert!(matches!(load_svg_data(&[0x1f, 0x8b, 0x00], &LoadOptions::default()), Err(SvgLoadError::Parse(usvg::Error::MalformedGZip))));
+    }
+
+    const CLASSES: &str = r##"<?xml version="1.0"?>
+<!DOCTYPE svg [<!ENTITY accent "#00ff00">]>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="30" height="10">
+    <style><![CDATA[ .warm > rect { fill: red } .cool { fill: blue } ]]></style>
+    <defs><rect id="cell" width="10" height="10"/></defs>
+    <!-- Classes without ids get generated ones. -->
+    <g class="warm" inkscape:label="Warm &amp; bright"><rect width="10" height="10"/></g>
+    <rect class="cool icon" x="10" width="10" height="10"/>
+    <use xlink:href="#cell" x="20" fill="&accent;" class="icon" id="copy"/>
+</svg>"##;
+
+    fn center(path: &RenderablePath) -> (f32, f32) {
+        let (x0, y0, x1, y1) = path.vertices.vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |b, v| {
+            (b.0.min(v.position[0]), b.1.min(v.position[1]), b.2.max(v.position[0]), b.3.max(v.position[1]))
+        });
+        ((x0 + x1) / 2.0, (y0 + y1) / 2.0)
+    }
+
+    #[test]
+    fn classes_are_indexed_from_the_parsed_document() {
+        for data in [CLASSES.as_bytes().to_vec(), gzip(CLASSES)] {
+            let svg = load_svg_from_bytes(&data).unwrap();
+            let colors: Vec<[f32; 4]> = svg.paths().map(|p| p.bgcolor).collect();
+            assert_eq!(colors, [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [0.0, 1.0, 0.0, 1.0]]);
+            assert_eq!(svg.class_index.len(), 3);
+            assert_eq!(svg.class_index["warm"], [0]);
+            assert_eq!(svg.class_index["cool"], [1]);
+            assert_eq!(svg.class_index["icon"], [1, 2]);
+            assert_eq!(svg.by_class("icon").len(), 2);
+            assert!(svg.by_class("missing").is_empty());
+            assert_eq!(svg.id_index["copy"], [2]);
+            assert!(svg.id_index.keys().all(|id| !id.starts_with("svg-load-class-")));
+        }
+    }
+
+    #[test]
+    fn hit_test_finds_the_topmost_path_under_the_point() {
+        let svg = tessellate_svg_string(CLASSES, &LoadOptions::default()).unwrap();
+        let paths: Vec<&RenderablePath> = svg.paths().collect();
+        for (i, path) in paths.iter().enumerate() {
+            let (x, y) = center(path);
+            assert_eq!(svg.hit_test(x, y), Some(i));
+        }
+        let (x, y) = center(paths[2]);
+        assert_eq!(svg.hit_test(x + 100.0, y), None);
+
+        let stacked = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+            <rect width="10" height="10" fill="red"/><rect x="2" y="2" width="6" height="6" fill="blue"/>
+        </svg>"#;
+        let svg = tessellate_svg_string(stacked, &LoadOptions::default()).unwrap();
+        let (x, y) = center(svg.paths().nth(1).unwrap());
+        assert_eq!(svg.hit_test(x, y), Some(1));
+    }
+
+    #[test]
+    fn hit_test_and_bounds_follow_local_transforms() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+            <g transform="translate(30 5) rotate(90)"><rect width="20" height="10" fill="red"/></g>
+            <rect x="2" y="30" width="4" height="4" fill="blue" transform="scale(1.5)"/>
+        </svg>"#;
+        let flat = tessellate_svg_string(svg, &LoadOptions::default()).unwrap();
+        let local = tessellate_svg_string(svg, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap();
+        for (flat_path, local_path) in flat.paths().zip(local.paths()) {
+            assert!(local_path.local_transform.is_some());
+            let ((x0, y0), (x1, y1)) = flat_path.bounding_box().unwrap();
+            let ((lx0, ly0), (lx1, ly1)) = local_path.bounding_box().unwrap();
+            for (a, b) in [(x0, lx0), (y0, ly0), (x1, lx1), (y1, ly1)] {
+                assert!((a - b).abs() < 1e-5, "{:?} vs {:?}", flat_path.bounding_box(), local_path.bounding_box());
+            }
+        }
+        let points = flat.paths().map(center).chain([(0.01, 0.01), (0.3, 0.6)]);
+        for (x, y) in points {
+            assert_eq!(local.hit_test(x, y), flat.hit_test(x, y), "at ({}, {})", x, y);
+        }
+        let (x, y) = center(flat.paths().next().unwrap());
+        assert_eq!(local.hit_test(x, y), Some(0));
+    }
+
+    #[test]
+    fn generated_class_ids_do_not_collide_with_the_document_ids() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+            <rect id="svg-load-class-0" width="5" height="5" fill="red"/>
+            <rect class="a" x="5" width="5" height="5" fill="blue"/>
+        </svg>"#;
+        let loaded = tessellate_svg_string(svg, &LoadOptions::default()).unwrap();
+        assert_eq!(loaded.id_index.len(), 1);
+        assert_eq!(loaded.id_index["svg-load-class-0"], [0]);
+        assert_eq!(loaded.class_index["a"], [1]);
+        assert_eq!(loaded.by_class("a")[0].bgcolor, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn classes_do_not_change_the_loaded_paths() {
+        // Without the style sheet, so that classes only feed the index.
+        let unstyled = CLASSES.replace("<style><![CDATA[ .warm > rect { fill: red } .cool { fill: blue } ]]></style>", "");
+        let unclassed = unstyled.replace(" class=\"warm\"", "").replace(" class=\"cool icon\"", "").replace(" class=\"icon\"", "");
+        let classed = tessellate_svg_string(&unstyled, &LoadOptions::default()).unwrap();
+        assert_eq!(classed.class_index["icon"], [1, 2]);
+        let plain = tessellate_svg_string(&unclassed, &LoadOptions::default()).unwrap();
+        assert!(plain.class_index.is_empty());
+        assert_eq!(classed.id_index, plain.id_index);
+        assert_eq!(classed.primitives.len(), plain.primitives.len());
+        for (a, b) in classed.paths().zip(plain.paths()) {
+            assert_eq!(a.vertices.vertices, b.vertices.vertices);
+            assert_eq!(a.vertices.indices, b.vertices.indices);
+            assert_eq!(a.paint(), b.paint());
+        }
+    }
+
+    #[test]
+    fn current_color_goes_on_the_root_after_markup_in_comments() {
+        let options = LoadOptions { current_color: [0.0, 0.0, 1.0, 1.0], ..LoadOptions::default() };
+        let prologues = [
+            "<!-- an <svg> icon -->",
+            "<!DOCTYPE svg [<!ENTITY icon \"<svg>\">]>",
+            "<?xml version=\"1.0\"?><!-- <svg color=\"red\"> -->",
+        ];
+        for prologue in prologues {
+            let svg = format!("{}{}", prologue, CURRENT_COLOR);
+            let loaded = tessellate_svg_string(&svg, &options).unwrap();
+            assert_eq!(loaded.paths().next().unwrap().bgcolor, [0.0, 0.0, 1.0, 1.0], "{}", prologue);
+        }
+        let cdata = CURRENT_COLOR.replace("<rect", "<style><![CDATA[ /* <svg> */ ]]></style><rect");
+        let loaded = tessellate_svg_string(&cdata, &options).unwrap();
+        assert_eq!(loaded.paths().next().unwrap().bgcolor, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn fills_come_before_their_strokes_and_unfilled_strokes_are_kept() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+            <rect width="4" height="4" fill="red" stroke="blue"/>
+            <rect x="5" width="4" height="4" fill="none" stroke="lime"/>
+        </svg>"#;
+        let loaded = tessellate_svg_string(svg, &LoadOptions::default()).unwrap();
+        let colors: Vec<[f32; 4]> = loaded.paths().map(|p| p.bgcolor).collect();
+        assert_eq!(colors, [[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], [0.0, 1.0, 0.0, 1.0]]);
+        for (i, path) in loaded.paths().enumerate() {
+            assert!(path.vertices.vertices.iter().all(|v| v.prim_id == i as u32));
+        }
+
+        let merged = LoadOptions { stroke_as_separate_primitive: false, ..LoadOptions::default() };
+        let loaded = tessellate_svg_string(svg, &merged).unwrap();
+        assert_eq!(loaded.paths().count(), 3);
+        assert_eq!(loaded.stats.paint_conflicts, [PaintConflict::StrokeDiffers { path: 0 }]);
+    }
+
+    const IMAGE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="20" height="20">
+        <rect width="4" height="4" fill="red"/>
+        <g transform="translate(5 6)" opacity="0.5">
+            <image width="8" height="4" xlink:href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg=="/>
+        </g>
+        <rect x="10" width="4" height="4" fill="blue"/>
+    </svg>"#;
+
+    #[test]
+    fn images_have_prim_ids_of_their_own() {
+        let loaded = tessellate_svg_string(IMAGE, &LoadOptions::default()).unwrap();
+        assert!(matches!(loaded.primitives[1], ScenePrimitive::Image(_)));
+        for (i, primitive) in loaded.primitives.iter().enumerate() {
+            let mesh = match primitive {
+                ScenePrimitive::Path(path) => &path.vertices,
+                ScenePrimitive::Image(image) => &image.quad,
+            };
+            assert!(!mesh.vertices.is_empty());
+            assert!(mesh.vertices.iter().all(|v| v.prim_id == i as u32), "primitive {}", i);
+        }
+        assert_eq!(loaded.hit_test(0.5 / 20.0, 1.0 - 0.5 / 20.0), Some(0));
+        assert_eq!(loaded.hit_test(10.5 / 20.0, 1.0 - 0.5 / 20.0), Some(2));
+    }
+
+    #[test]
+    fn images_take_group_opacity_and_local_transforms() {
+        let flat = tessellate_svg_string(IMAGE, &LoadOptions::default()).unwrap();
+        let local = tessellate_svg_string(IMAGE, &LoadOptions { flatten_transforms: false, ..LoadOptions::default() }).unwrap();
+        let (flat, local) = (flat.images().next().unwrap(), local.images().next().unwrap());
+        assert_eq!(flat.opacity, 0.5);
+        assert_eq!(local.opacity, 0.5);
+        assert!(flat.local_transform.is_none());
+        let m = local.local_transform.unwrap();
+        assert_eq!(local.quad.vertices[2].position, [8.0, 4.0]);
+        for (a, b) in flat.quad.vertices.iter().zip(&local.quad.vertices) {
+            let p = b.position;
+            let mapped = [m[0][0] * p[0] + m[0][1] * p[1] + m[0][2], m[1][0] * p[0] + m[1][1] * p[1] + m[1][2]];
+            assert!((a.position[0] - mapped[0]).abs() < 1e-6 && (a.position[1] - mapped[1]).abs() < 1e-6);
+        }
+        assert_eq!(flat.quad.vertices[0].position, [5.0 / 20.0, 1.0 - 6.0 / 20.0]);
+    }
+}
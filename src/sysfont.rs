@@ -0,0 +1,88 @@
+//! Font lookup by family, weight and style through a [`fontdb::Database`].
+
+use fontdb::{Database, Family, Query, Stretch};
+
+pub use fontdb::{Style, Weight};
+
+use crate::font::Font;
+use crate::ttfload::{load_font_from_bytes_with_options, FontLoadError, FontOptions};
+
+/// Database with the fonts installed on this system. Scanning is slow, so build it once and
+/// pass it to [`load_font_from_database`] instead of calling [`load_system_font`] repeatedly.
+pub fn system_database() -> Database {
+    let mut db = Database::new();
+    db.load_system_fonts();
+    db
+}
+
+/// Loads `symbols` from the installed face of `family` closest to `weight` and `style`.
+pub fn load_system_font(family: &str, weight: Weight, style: Style, symbols: &str) -> Result<Font, FontLoadError> {
+    load_font_from_database(&system_database(), family, weight, style, symbols, &FontOptions::default())
+}
+
+/// Loads `symbols` from the face of `family` in `db` that best matches `weight` and `style`,
+/// following CSS font matching (e.g. the nearest available weight). `options.face_index` is
+/// replaced by the index of the matched face.
+pub fn load_font_from_database(db: &Database, family: &str, weight: Weight, style: Style, symbols: &str,
+                               options: &FontOptions) -> Result<Font, FontLoadError> {
+    let query = Query {
+        families: &[Family::Name(family)],
+        weight,
+        stretch: Stretch::Normal,
+        style,
+    };
+    let id = db.query(&query).ok_or_else(|| FontLoadError::FamilyNotFound(family.to_string()))?;
+    db.with_face_data(id, |data, face_index| {
+        let options = FontOptions { face_index, ..options.clone() };
+        load_font_from_bytes_with_options(data, symbols, &options)
+    }).ok_or_else(|| {
+        let message = format!("failed to read font data for family '{}'", family);
+        FontLoadError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, message))
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    /// DejaVu Sans as installed regular, plus a copy registered as its bold that draws every
+    /// glyph twice as large so the two can be told apart.
+    fn database() -> Database {
+        let regular = fixtures::read("DejaVuSans.ttf");
+        let mut bold = regular.clone();
+        let count = u16::from_be_bytes([bold[4], bold[5]]) as usize;
+        for record in (0..count).map(|i| 12 + 16 * i) {
+            let offset = u32::from_be_bytes(bold[record + 8..record + 12].try_into().unwrap()) as usize;
+            match &bold[record..record + 4] {
+                b"head" => bold[offset + 18..offset + 20].copy_from_slice(&1024u16.to_be_bytes()),
+                b"OS/2" => bold[offset + 4..offset + 6].copy_from_slice(&700u16.to_be_bytes()),
+                _ => {}
+            }
+        }
+        let mut db = Database::new();
+        db.load_font_data(regular);
+        db.load_font_data(bold);
+        db
+    }
+
+    fn advance(db: &Database, weight: Weight) -> f32 {
+        let font = load_font_from_database(db, "DejaVu Sans", weight, Style::Normal, "a", &FontOptions::default()).unwrap();
+        font.glyph('a').unwrap().advance
+    }
+
+    #[test]
+    fn weights_match_the_nearest_face() {
+        let db = database();
+        let regular = advance(&db, Weight::NORMAL);
+        assert_eq!(advance(&db, Weight::BOLD), 2.0 * regular);
+        assert_eq!(advance(&db, Weight::SEMIBOLD), 2.0 * regular);
+        assert_eq!(advance(&db, Weight::LIGHT), regular);
+    }
+
+    #[test]
+    fn unknown_families_are_an_error() {
+        let result = load_font_from_database(&database(), "No Such Family", Weight::NORMAL, Style::Normal, "a", &FontOptions::default());
+        assert!(matches!(result, Err(FontLoadError::FamilyNotFound(family)) if family == "No Such Family"));
+    }
+}
@@ -1,106 +1,1840 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
 
 use lyon::math::Point;
 use lyon::path::PathEvent;
-use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, VertexBuffers};
+use lyon::tessellation::{BuffersBuilder, FillOptions, FillTessellator, LineCap, LineJoin, StrokeOptions, StrokeTessellator, VertexBuffers};
 use ttf_parser as ttf;
 use ttf_parser::Rect;
-use usvg::{Error, Transform};
+use usvg::Transform;
 
-use crate::font::{Font, Glyph};
+use crate::font::{ClusterPart, Font, FontMetrics, FontWarning, Glyph, GlyphCluster, GlyphImage, Ligature};
 use crate::path::GpuVertex;
-use crate::svgload::VertexCtor;
+use crate::svgload::{load_svg_glyph, LoadOptions, VertexCtor};
 
-const FONT_SIZE: f64 = 1.0;
+pub(crate) const FONT_SIZE: f32 = 1.0;
 
-pub fn load_font(filename: &str, symbols: &str) -> Result<Font, Box<dyn std::error::Error>> {
+/// Codepoint to glyph id of the face being loaded.
+type GlyphIdMap = HashMap<u32, ttf::GlyphId>;
+
+/// Width every glyph gets with `FontOptions::force_advance`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Advance {
+    /// The largest advance among the loaded glyphs.
+    MaxLoaded,
+    /// A fixed width in em.
+    Em(f32),
+}
+
+/// Stroke drawn along glyph outlines with `FontOptions::outline_stroke`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphStroke {
+    /// Line width in em, centered on the outline.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl GlyphStroke {
+    /// Stroke of `width_em` with round joins, which suits text outlines of any weight.
+    pub fn new(width_em: f32) -> Self {
+        GlyphStroke { width: width_em, cap: LineCap::Round, join: LineJoin::Round }
+    }
+}
+
+/// What to do with a requested character the font has no glyph for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingGlyph {
+    /// Map it to glyph 0 (.notdef).
+    #[default]
+    Notdef,
+    /// Leave it out of `Font::glyph_map`.
+    Skip,
+    /// Fail the whole load.
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct FontOptions {
+    pub missing_glyph: MissingGlyph,
+    /// Keep each glyph's outline as `Glyph::path_events` so it can be re-tessellated later.
+    pub retain_path_events: bool,
+    /// Face to load from a font collection (.ttc/.otc); must be 0 for single-face fonts.
+    pub face_index: u32,
+    /// Load the font's standard ligatures (`liga`) between the requested characters, see
+    /// `Font::ligatures`.
+    pub ligatures: bool,
+    /// Axis values of a variable font, e.g. `("wght", 600.0)`, applied before any glyph is read.
+    pub variations: Vec<(String, f32)>,
+    /// Preferred strike of bitmap glyphs, in pixels per em: the smallest strike at least this
+    /// large is used, or the largest one if none is.
+    pub bitmap_pixels_per_em: u16,
+    /// Give every loaded glyph the largest advance among them, for monospace layout in
+    /// editors and terminals. Outlines are not changed.
+    pub tabular_figures: bool,
+    /// Size of one em in the output; advances, metrics, bboxes and meshes all scale with it.
+    pub em_size: f32,
+    /// Maximum flattening error of glyph curves as a fraction of the em, e.g. `0.005`. `None`
+    /// keeps the historical 0.5 font units, whose visual size depends on the font's design grid.
+    pub tolerance: Option<f32>,
+    /// Slant of a synthetic italic in degrees, positive leaning right; 0 keeps glyphs upright.
+    /// Advances are not changed.
+    pub italic_angle: f32,
+    /// Stroke width as a fraction of the em, added around every outline to fake a bold face;
+    /// advances grow by the same width, `bold_strength * em_size` in output units. 0 keeps the
+    /// regular weight.
+    pub bold_strength: f32,
+    /// Tessellate glyph outlines into `Glyph::outline`. When off, only metrics and, with
+    /// `retain_path_events`, the outline path events are loaded.
+    pub tessellate: bool,
+    /// Width and height in em of the box drawn for `Font::notdef` when the font's own .notdef
+    /// glyph is empty.
+    pub notdef_size: (f32, f32),
+    /// Added to the glyph id to form the `prim_id` of every outline vertex, and to the layer
+    /// index of SVG-table glyph layers, so glyph meshes can share a primitive table with other
+    /// meshes, e.g. the paths of a [`crate::svgload::LoadedSvg`] when set past their count.
+    pub prim_id_base: u32,
+    /// Extra transform applied to every glyph outline in output units, after the scale and
+    /// `italic_angle`, e.g. a horizontal stretch. Advances and metrics are not changed.
+    pub glyph_transform: Option<Transform>,
+    /// Give every glyph, cluster and the .notdef glyph this advance, for terminal-style grids
+    /// with a proportional font. Kerning is not loaded in this mode. Takes precedence over
+    /// `tabular_figures`.
+    pub force_advance: Option<Advance>,
+    /// With `force_advance`, move each glyph's ink to the middle of its cell.
+    pub center_in_cell: bool,
+    /// Also stroke every outline into `Glyph::stroke_outline`, e.g. for outlined captions.
+    /// The fill in `Glyph::outline` is unchanged.
+    pub outline_stroke: Option<GlyphStroke>,
+}
+
+/// Flattening error in pixels targeted by [`FontOptions::for_pixel_size`].
+pub const MAX_PIXEL_ERROR: f32 = 0.25;
+
+impl Default for FontOptions {
+    fn default() -> Self {
+        FontOptions {
+            missing_glyph: MissingGlyph::default(),
+            retain_path_events: false,
+            face_index: 0,
+            ligatures: false,
+            variations: Vec::new(),
+            bitmap_pixels_per_em: 0,
+            tabular_figures: false,
+            em_size: FONT_SIZE,
+            tolerance: None,
+            italic_angle: 0.0,
+            bold_strength: 0.0,
+            tessellate: true,
+            notdef_size: (0.5, 0.7),
+            prim_id_base: 0,
+            glyph_transform: None,
+            force_advance: None,
+            center_in_cell: false,
+            outline_stroke: None,
+        }
+    }
+}
+
+impl FontOptions {
+    /// Sets variation `axis` (a 4-letter tag such as `wght` or `wdth`) to `value`.
+    pub fn variation(mut self, axis: &str, value: f32) -> Self {
+        self.variations.push((axis.to_string(), value));
+        self
+    }
+
+    /// Sets `tolerance` so curves are flattened to within [`MAX_PIXEL_ERROR`] when the em is
+    /// drawn `px_per_em` pixels tall.
+    pub fn for_pixel_size(self, px_per_em: f32) -> Self {
+        self.for_pixel_sizes(px_per_em, px_per_em)
+    }
+
+    /// Like [`FontOptions::for_pixel_size`] for glyphs drawn at any size from `min_px` to
+    /// `max_px` pixels per em; the largest size needs the tightest tolerance.
+    pub fn for_pixel_sizes(mut self, min_px: f32, max_px: f32) -> Self {
+        self.tolerance = Some(MAX_PIXEL_ERROR / min_px.max(max_px).max(1.0));
+        self
+    }
+
+    /// Fakes an italic face by shearing every glyph by `angle_degrees`, see `italic_angle`.
+    pub fn synthetic_italic(mut self, angle_degrees: f32) -> Self {
+        self.italic_angle = angle_degrees;
+        self
+    }
+
+    /// Fakes a bold face by stroking every outline with `strength_em`, see `bold_strength`.
+    pub fn synthetic_bold(mut self, strength_em: f32) -> Self {
+        self.bold_strength = strength_em;
+        self
+    }
+
+    /// Strokes every outline into `Glyph::stroke_outline`, see `outline_stroke`.
+    pub fn stroked(mut self, stroke: GlyphStroke) -> Self {
+        self.outline_stroke = Some(stroke);
+        self
+    }
+
+    /// Shear applied to outlines in output units, identity for upright glyphs.
+    fn shear(&self) -> Transform {
+        Transform::new(1.0, 0.0, (self.italic_angle as f64).to_radians().tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// From font units to output units: scales by `(sx, sy)`, then applies the shear and
+    /// `glyph_transform`.
+    fn outline_transform(&self, sx: f64, sy: f64) -> Transform {
+        let mut transform = self.glyph_transform.unwrap_or_default();
+        transform.append(&self.shear());
+        transform.append(&Transform::new_scale(sx, sy));
+        transform
+    }
+
+    /// Factor from the font units of `face` to output units.
+    pub(crate) fn scale(&self, face: &ttf::Face) -> f64 {
+        self.em_size as f64 / face.units_per_em() as f64
+    }
+
+    /// Fill tolerance in the font units of `face`, where outlines are tessellated.
+    fn font_unit_tolerance(&self, face: &ttf::Face) -> f32 {
+        self.tolerance.map_or(0.5, |t| t * face.units_per_em() as f32)
+    }
+}
+
+/// A variation axis of a variable font, in design units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisInfo {
+    pub tag: String,
+    /// Axis name from the name table, e.g. "Weight".
+    pub name: Option<String>,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+    pub hidden: bool,
+}
+
+/// Variation axes of the first face in `data`; empty for static or unparsable fonts.
+pub fn list_variation_axes(data: &[u8]) -> Vec<AxisInfo> {
+    let data = match sfnt_data(data) {
+        Ok(data) => data,
+        Err(_) => return Vec::new(),
+    };
+    match ttf::Face::from_slice(&data, 0) {
+        Ok(face) => variation_axes(&face),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn variation_axes(face: &ttf::Face) -> Vec<AxisInfo> {
+    face.variation_axes().into_iter().map(|axis| AxisInfo {
+        tag: axis.tag.to_string(),
+        name: face_name(face, axis.name_id),
+        min: axis.min_value,
+        default: axis.def_value,
+        max: axis.max_value,
+        hidden: axis.hidden,
+    }).collect()
+}
+
+/// Why a font could not be loaded.
+#[derive(Debug)]
+pub enum FontLoadError {
+    Io(std::io::Error),
+    /// The data is not a valid font, or a required table is broken.
+    MalformedFont(ttf::FaceParsingError),
+    /// The data is a font format this build cannot read, e.g. WOFF without the `woff` feature.
+    UnsupportedFormat(&'static str),
+    #[cfg(feature = "woff")]
+    Woff(crate::woff::WoffError),
+    /// `FontOptions::face_index` is not below the number of faces in the collection.
+    FaceIndexOutOfRange { index: u32, faces: u32 },
+    /// A `FontOptions::variations` axis the face does not have.
+    UnknownAxis { axis: String, available: Vec<AxisInfo> },
+    /// The face has no glyph for `codepoint` and `FontOptions::missing_glyph` is `Error`.
+    MissingGlyph { codepoint: u32 },
+    /// The outline of `glyph` cannot be read or tessellated and `FontOptions::missing_glyph`
+    /// is `Error`; the other policies load it with an empty mesh and a warning instead.
+    OutlineFailed { glyph: u16 },
+    InvalidRange(UnicodeRangeError),
+    /// A [`FaceChain`] without faces.
+    EmptyChain,
+    /// [`Font::add_symbols`] or [`Font::add_glyph_ids`] was given an em size or tolerance
+    /// other than the one the font was loaded with.
+    OptionsMismatch { loaded: (f32, Option<f32>), requested: (f32, Option<f32>) },
+    /// The face lacks tables text shaping needs.
+    UnshapeableFace,
+    /// A shaping feature that is not in the syntax of `rustybuzz::Feature`.
+    InvalidFeature(String),
+    /// No face of the font database belongs to the requested family.
+    FamilyNotFound(String),
+}
+
+impl fmt::Display for FontLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontLoadError::Io(e) => write!(f, "cannot read font: {}", e),
+            FontLoadError::MalformedFont(e) => write!(f, "cannot parse font: {}", e),
+            FontLoadError::UnsupportedFormat(format) => write!(f, "unsupported font format: {}", format),
+            #[cfg(feature = "woff")]
+            FontLoadError::Woff(e) => write!(f, "cannot unpack font: {}", e),
+            FontLoadError::FaceIndexOutOfRange { index, faces } => {
+                write!(f, "face index {} is out of range, the font has {} face(s)", index, faces)
+            }
+            FontLoadError::UnknownAxis { axis, available } => {
+                write!(f, "font has no variation axis '{}', available axes: ", axis)?;
+                if available.is_empty() {
+                    return write!(f, "none");
+                }
+                let available: Vec<String> = available.iter()
+                    .map(|a| format!("{} ({}..={}, default {})", a.tag, a.min, a.max, a.default))
+                    .collect();
+                write!(f, "{}", available.join(", "))
+            }
+            FontLoadError::MissingGlyph { codepoint } => write!(f, "font has no glyph for U+{:04X}", codepoint),
+            FontLoadError::OutlineFailed { glyph } => write!(f, "cannot read the outline of glyph {}", glyph),
+            FontLoadError::InvalidRange(e) => write!(f, "{}", e),
+            FontLoadError::EmptyChain => write!(f, "font chain is empty"),
+            FontLoadError::OptionsMismatch { loaded, requested } => write!(
+                f,
+                "font was loaded with em size {} and tolerance {:?}, not {} and {:?}",
+                loaded.0, loaded.1, requested.0, requested.1
+            ),
+            FontLoadError::UnshapeableFace => write!(f, "face cannot be used for shaping"),
+            FontLoadError::InvalidFeature(feature) => write!(f, "invalid font feature '{}'", feature),
+            FontLoadError::FamilyNotFound(family) => write!(f, "no font found for family '{}'", family),
+        }
+    }
+}
+
+impl std::error::Error for FontLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontLoadError::Io(e) => Some(e),
+            FontLoadError::MalformedFont(e) => Some(e),
+            #[cfg(feature = "woff")]
+            FontLoadError::Woff(e) => Some(e),
+            FontLoadError::InvalidRange(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FontLoadError {
+    fn from(e: std::io::Error) -> Self {
+        FontLoadError::Io(e)
+    }
+}
+
+impl From<ttf::FaceParsingError> for FontLoadError {
+    fn from(e: ttf::FaceParsingError) -> Self {
+        FontLoadError::MalformedFont(e)
+    }
+}
+
+#[cfg(feature = "woff")]
+impl From<crate::woff::WoffError> for FontLoadError {
+    fn from(e: crate::woff::WoffError) -> Self {
+        FontLoadError::Woff(e)
+    }
+}
+
+impl From<UnicodeRangeError> for FontLoadError {
+    fn from(e: UnicodeRangeError) -> Self {
+        FontLoadError::InvalidRange(e)
+    }
+}
+
+/// `data` as SFNT font data: WOFF and WOFF2 files are unpacked with the `woff` feature and
+/// rejected without it.
+pub(crate) fn sfnt_data(data: &[u8]) -> Result<Cow<'_, [u8]>, FontLoadError> {
+    #[cfg(feature = "woff")]
+    if crate::woff::is_woff(data) {
+        return Ok(Cow::Owned(crate::woff::to_sfnt(data)?));
+    }
+    #[cfg(not(feature = "woff"))]
+    if data.starts_with(b"wOFF") || data.starts_with(b"wOF2") {
+        return Err(FontLoadError::UnsupportedFormat("WOFF and WOFF2 fonts need the woff feature"));
+    }
+    Ok(Cow::Borrowed(data))
+}
+
+/// Parses the face selected by `options`, checking the index against the collection size first
+/// so an out-of-range index gets a clear error, and applies `options.variations`.
+pub(crate) fn parse_face<'a>(data: &'a [u8], options: &FontOptions) -> Result<ttf::Face<'a>, FontLoadError> {
+    let index = options.face_index;
+    let faces = ttf::fonts_in_collection(data).unwrap_or(1);
+    if index >= faces {
+        return Err(FontLoadError::FaceIndexOutOfRange { index, faces });
+    }
+    let mut face = ttf::Face::from_slice(data, index)?;
+    for (axis, value) in &options.variations {
+        let tag = ttf::Tag::from_bytes_lossy(axis.as_bytes());
+        if axis.len() != 4 || face.set_variation(tag, *value).is_none() {
+            return Err(FontLoadError::UnknownAxis { axis: axis.clone(), available: variation_axes(&face) });
+        }
+    }
+    Ok(face)
+}
+
+/// Names a font loaded from `path_buf` after the file, which also stands in for a missing
+/// family name.
+fn name_from_file(font: &mut Font, path_buf: &std::path::Path, face_index: u32) {
+    font.name = file_font_name(path_buf, face_index);
+    if font.family_name.is_empty() {
+        font.family_name = file_stem(path_buf);
+    }
+}
+
+fn file_stem(path_buf: &std::path::Path) -> String {
+    path_buf.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn file_font_name(path_buf: &std::path::Path, face_index: u32) -> String {
+    let file: String = path_buf.file_name().unwrap().to_str().unwrap().into();
+    if face_index == 0 {
+        file
+    } else {
+        format!("{}#{}", file, face_index)
+    }
+}
+
+#[must_use = "loading a font without using the result is a bug"]
+pub fn load_font(filename: &str, symbols: &str) -> Result<Font, FontLoadError> {
+    load_font_with_options(filename, symbols, &FontOptions::default())
+}
+
+/// Loads the outlines of `symbols` as `Glyph::path_events` without tessellating them, e.g. for
+/// SDF generation or an own tessellator. Curves are kept as quadratic or cubic segments.
+pub fn load_font_outlines(filename: &str, symbols: &str) -> Result<Font, FontLoadError> {
+    let options = FontOptions {
+        retain_path_events: true,
+        tessellate: false,
+        ..FontOptions::default()
+    };
+    load_font_with_options(filename, symbols, &options)
+}
+
+pub fn load_font_with_options(filename: &str, symbols: &str, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let path_buf = PathBuf::from(filename);
+    let font_data = std::fs::read(&path_buf)?;
+    let mut font = load_font_from_bytes_with_options(&font_data, symbols, options)?;
+    name_from_file(&mut font, &path_buf, options.face_index);
+    Ok(font)
+}
+
+/// Loads a font from memory, e.g. one embedded with `include_bytes!`.
+pub fn load_font_from_bytes(data: &[u8], symbols: &str) -> Result<Font, FontLoadError> {
+    load_font_from_bytes_with_options(data, symbols, &FontOptions::default())
+}
+
+pub fn load_font_from_bytes_with_options(font_data: &[u8], symbols: &str, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let font_data = sfnt_data(font_data)?;
+    let face = parse_face(&font_data, options)?;
+    let (g_map, unresolved, clusters) = resolve_symbols(&face, symbols, options)?;
+    let mut font = build_font(&face, g_map, unresolved, options)?;
+    add_clusters(&mut font, &face, clusters, options)?;
+    finish_font(&mut font, &font_data, options);
+    Ok(font)
+}
+
+/// A font file or buffer to load glyphs from, see [`FaceChain`].
+#[derive(Clone, Debug, Default)]
+pub struct FaceSource {
+    pub data: Vec<u8>,
+    pub face_index: u32,
+}
+
+impl FaceSource {
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        FaceSource { data, face_index: 0 }
+    }
+
+    pub fn from_file(filename: &str) -> std::io::Result<Self> {
+        Ok(Self::from_bytes(std::fs::read(filename)?))
+    }
+
+    pub fn with_face_index(mut self, face_index: u32) -> Self {
+        self.face_index = face_index;
+        self
+    }
+}
+
+/// Faces tried in order for every requested character by [`load_font_chain`]. Unlike
+/// [`crate::font::FontChain`], which falls back between already loaded fonts, this merges
+/// the faces into a single [`Font`] at load time.
+#[derive(Clone, Debug, Default)]
+pub struct FaceChain {
+    pub faces: Vec<FaceSource>,
+}
+
+impl FaceChain {
+    pub fn new(primary: FaceSource) -> Self {
+        FaceChain { faces: vec![primary] }
+    }
+
+    pub fn with_fallback(mut self, fallback: FaceSource) -> Self {
+        self.faces.push(fallback);
+        self
+    }
+}
+
+pub fn load_font_chain(chain: &FaceChain, symbols: &str) -> Result<Font, FontLoadError> {
+    load_font_chain_with_options(chain, symbols, &FontOptions::default())
+}
+
+/// Loads each character of `symbols` from the first face of `chain` that maps it, recording
+/// that face in `Glyph::face`. Names and metrics come from the primary face; characters no
+/// face has are handled by `options.missing_glyph` against the primary face. Each source uses
+/// its own face index, and `options.variations` only apply to the primary face.
+pub fn load_font_chain_with_options(chain: &FaceChain, symbols: &str, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let primary = chain.faces.first().ok_or(FontLoadError::EmptyChain)?;
+    let data = chain.faces.iter().map(|source| sfnt_data(&source.data)).collect::<Result<Vec<_>, _>>()?;
+    let primary_options = FontOptions { face_index: primary.face_index, ..options.clone() };
+    let primary_face = parse_face(&data[0], &primary_options)?;
+    let mut faces = vec![primary_face];
+    for (source, data) in chain.faces[1..].iter().zip(&data[1..]) {
+        let fallback_options = FontOptions {
+            face_index: source.face_index,
+            variations: Vec::new(),
+            ..options.clone()
+        };
+        faces.push(parse_face(data, &fallback_options)?);
+    }
+
+    let mut per_face: Vec<GlyphIdMap> = vec![HashMap::new(); faces.len()];
+    let mut missing = String::new();
+    for ch in symbols.chars() {
+        match faces.iter().enumerate().find_map(|(i, f)| f.glyph_index(ch).map(|id| (i, id))) {
+            Some((i, id)) => {
+                per_face[i].insert(u32::from(ch), id);
+            }
+            None => missing.push(ch),
+        }
+    }
+
+    let (fallback_ids, unresolved, clusters) = resolve_symbols(&faces[0], &missing, options)?;
+    per_face[0].extend(fallback_ids);
+    let mut per_face = per_face.into_iter();
+    let mut font = build_font(&faces[0], per_face.next().unwrap_or_default(), unresolved, options)?;
+    add_clusters(&mut font, &faces[0], clusters, options)?;
+    for (i, g_map) in per_face.enumerate() {
+        font.kerning.extend(load_kerning(&faces[i + 1], &g_map, &HashMap::new(), options));
+        font.mark_anchors.extend(load_mark_anchors(&faces[i + 1], &g_map, &HashMap::new(), options));
+        let glyphs = load_glyphs(&faces[i + 1], g_map, options, &mut font.warnings)?;
+        font.glyph_map.extend(glyphs.into_iter().map(|(cp, glyph)| (cp, Glyph { face: i + 1, ..glyph })));
+    }
+    finish_font(&mut font, &data[0], options);
+    Ok(font)
+}
+
+/// Restricts which codepoints [`load_font_all`] loads, to keep memory bounded for large
+/// (e.g. CJK) fonts.
+#[derive(Clone, Debug, Default)]
+pub struct CmapFilter {
+    /// Only codepoints inside one of these ranges are loaded; empty means all of them.
+    pub ranges: Vec<RangeInclusive<u32>>,
+    /// Keeps at most this many glyphs, lowest codepoints first.
+    pub max_glyphs: Option<usize>,
+}
+
+impl CmapFilter {
+    fn accepts(&self, cp: u32) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|r| r.contains(&cp))
+    }
+}
+
+/// Loads every glyph reachable from the font's unicode cmap subtables.
+pub fn load_font_all(filename: &str) -> Result<Font, FontLoadError> {
+    load_font_all_with_filter(filename, &CmapFilter::default(), &FontOptions::default())
+}
+
+pub fn load_font_all_with_filter(filename: &str, filter: &CmapFilter, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let path_buf = PathBuf::from(filename);
+    let font_data = std::fs::read(&path_buf)?;
+    let mut font = load_font_all_from_bytes(&font_data, filter, options)?;
+    name_from_file(&mut font, &path_buf, options.face_index);
+    Ok(font)
+}
+
+pub fn load_font_all_from_bytes(font_data: &[u8], filter: &CmapFilter, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let font_data = sfnt_data(font_data)?;
+    let face = parse_face(&font_data, options)?;
+    let g_map = cmap_glyphs(&face, filter);
+    let mut font = build_font(&face, g_map, Vec::new(), options)?;
+    finish_font(&mut font, &font_data, options);
+    Ok(font)
+}
+
+fn cmap_glyphs(face: &ttf::Face, filter: &CmapFilter) -> GlyphIdMap {
+    let mut map = BTreeMap::new();
+    if let Some(cmap) = face.tables().cmap {
+        for subtable in cmap.subtables {
+            if !subtable.is_unicode() {
+                continue;
+            }
+            subtable.codepoints(|cp| {
+                if char::from_u32(cp).is_none() || !filter.accepts(cp) {
+                    return;
+                }
+                if let Some(id) = subtable.glyph_index(cp) {
+                    if id.0 != 0 {
+                        map.entry(cp).or_insert(id);
+                    }
+                }
+            });
+        }
+    }
+    map.into_iter().take(filter.max_glyphs.unwrap_or(usize::MAX)).collect()
+}
+
+/// Malformed entry in a CSS `unicode-range` list, see [`parse_unicode_range`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnicodeRangeError {
+    /// The offending comma-separated entry, trimmed.
+    pub token: String,
+    /// Byte offset of `token` in the parsed string.
+    pub position: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for UnicodeRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid unicode-range entry {:?} at byte {}: {}", self.token, self.position, self.reason)
+    }
+}
+
+impl std::error::Error for UnicodeRangeError {}
+
+/// Parses a CSS `unicode-range` list such as `"U+0020-007E, U+00A0-00FF, U+4??"`: single
+/// codepoints, `start-end` ranges and `?` wildcards, case-insensitive.
+pub fn parse_unicode_range(ranges: &str) -> Result<Vec<RangeInclusive<u32>>, UnicodeRangeError> {
+    let mut parsed = Vec::new();
+    let mut offset = 0;
+    for entry in ranges.split(',') {
+        let token = entry.trim();
+        let position = offset + (entry.len() - entry.trim_start().len());
+        offset += entry.len() + 1;
+        let error = |reason| UnicodeRangeError { token: token.to_string(), position, reason };
+
+        let body = match token.get(..2) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("U+") => &token[2..],
+            _ => return Err(error("expected U+ prefix")),
+        };
+        let hex = |digits: &str| {
+            if digits.is_empty() || digits.len() > 6 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(error("expected 1 to 6 hex digits"));
+            }
+            Ok(u32::from_str_radix(digits, 16).unwrap_or(u32::MAX))
+        };
+        let range = if let Some((start, end)) = body.split_once('-') {
+            hex(start)?..=hex(end)?
+        } else if body.ends_with('?') {
+            let digits = body.trim_end_matches('?');
+            let wildcards = (body.len() - digits.len()) as u32;
+            if body.len() > 6 || digits.contains('?') {
+                return Err(error("wildcards must trail at most 6 digits"));
+            }
+            let start = if digits.is_empty() { 0 } else { hex(digits)? } << (4 * wildcards);
+            start..=start + ((1 << (4 * wildcards)) - 1)
+        } else {
+            let cp = hex(body)?;
+            cp..=cp
+        };
+        if range.start() > range.end() {
+            return Err(error("range start is after its end"));
+        }
+        if *range.end() > 0x10FFFF {
+            return Err(error("codepoint beyond U+10FFFF"));
+        }
+        parsed.push(range);
+    }
+    Ok(parsed)
+}
+
+/// Loads every codepoint of the CSS `unicode-range` list `ranges` (see
+/// [`parse_unicode_range`]) that the font covers. Uncovered codepoints are skipped whatever
+/// `FontOptions::missing_glyph` says and listed in `Font::unresolved`, so keep ranges tight.
+pub fn load_font_ranges(filename: &str, ranges: &str) -> Result<Font, FontLoadError> {
+    load_font_ranges_with_options(filename, ranges, &FontOptions::default())
+}
+
+pub fn load_font_ranges_with_options(filename: &str, ranges: &str, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let ranges = parse_unicode_range(ranges)?;
     let path_buf = PathBuf::from(filename);
     let font_data = std::fs::read(&path_buf)?;
+    let font_data = sfnt_data(&font_data)?;
+    let face = parse_face(&font_data, options)?;
+
+    let codepoints: BTreeSet<u32> = ranges.into_iter().flatten().collect();
+    let mut g_map = HashMap::new();
+    let mut unresolved = Vec::new();
+    for ch in codepoints.into_iter().filter_map(char::from_u32) {
+        match face.glyph_index(ch) {
+            Some(id) => {
+                g_map.insert(u32::from(ch), id);
+            }
+            None => unresolved.push(u32::from(ch)),
+        }
+    }
+    let mut font = build_font(&face, g_map, unresolved, options)?;
+    finish_font(&mut font, &font_data, options);
+    name_from_file(&mut font, &path_buf, options.face_index);
+    Ok(font)
+}
+
+/// Codepoints drawn as several glyphs, with the decomposed characters and their glyphs in
+/// drawing order.
+pub(crate) type ClusterIds = Vec<(u32, Vec<(char, ttf::GlyphId)>)>;
+
+/// Maps `symbols` to glyphs of `face`. Characters without a glyph whose canonical
+/// decomposition the face covers become clusters; the rest are unresolved.
+pub(crate) fn resolve_symbols(face: &ttf::Face, symbols: &str, options: &FontOptions) -> Result<(GlyphIdMap, Vec<u32>, ClusterIds), FontLoadError> {
+    let mut g_map = HashMap::new();
+    let mut unresolved = Vec::new();
+    let mut clusters: ClusterIds = Vec::new();
+
+    for ch in symbols.chars() {
+        let cp = u32::from(ch);
+        match face.glyph_index(ch) {
+            Some(id) => {
+                g_map.insert(cp, id);
+            }
+            None if clusters.iter().any(|(c, _)| *c == cp) => {}
+            None => {
+                if let Some(ids) = decomposed_glyphs(face, ch) {
+                    clusters.push((cp, ids));
+                    continue;
+                }
+                if !unresolved.contains(&cp) {
+                    unresolved.push(cp);
+                }
+                match options.missing_glyph {
+                    MissingGlyph::Notdef => {
+                        g_map.insert(cp, ttf::GlyphId(0));
+                    }
+                    MissingGlyph::Skip => {}
+                    MissingGlyph::Error => {
+                        return Err(FontLoadError::MissingGlyph { codepoint: cp });
+                    }
+                }
+            }
+        }
+    }
+    Ok((g_map, unresolved, clusters))
+}
+
+/// Canonical decomposition of `ch` with the glyph of each part, if it has several parts and
+/// `face` has all of them.
+fn decomposed_glyphs(face: &ttf::Face, ch: char) -> Option<Vec<(char, ttf::GlyphId)>> {
+    let mut parts = Vec::new();
+    unicode_normalization::char::decompose_canonical(ch, |part| parts.push(part));
+    if parts.len() < 2 {
+        return None;
+    }
+    parts.into_iter().map(|part| Some((part, face.glyph_index(part)?))).collect()
+}
+
+/// Loads the glyphs of `clusters` into `font.glyph_id_map` and records the clusters. The
+/// first part is the base and sets the advance; every mark is centered over the base, and
+/// above (below) marks that would overlap the glyphs under (over) them are moved up (down)
+/// until they touch.
+pub(crate) fn add_clusters(font: &mut Font, face: &ttf::Face, clusters: ClusterIds, options: &FontOptions) -> Result<(), FontLoadError> {
+    let mut ids: Vec<ttf::GlyphId> = clusters.iter()
+        .flat_map(|(_, parts)| parts.iter().map(|&(_, id)| id))
+        .filter(|id| !font.glyph_id_map.contains_key(&id.0))
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    let glyphs = load_glyphs_by_id(face, &ids, options, &mut font.warnings)?;
+    font.glyph_id_map.extend(glyphs);
+
+    // Marks go to their GPOS anchor when the font has one, else they are centered and stacked.
+    let anchors = MarkAnchors::new(face);
+    for (cp, parts) in clusters {
+        let bbox = |id: ttf::GlyphId| font.glyph_id_map.get(&id.0).map_or((0.0, 0.0, 0.0, 0.0), |g| g.bbox);
+        let (_, base_id) = parts[0];
+        let (x_min, mut bottom, x_max, mut top) = bbox(base_id);
+        let center = (x_min + x_max) / 2.0;
+        let mut cluster = GlyphCluster {
+            parts: vec![ClusterPart { glyph_id: base_id.0, offset: (0.0, 0.0) }],
+            advance: font.glyph_id_map.get(&base_id.0).map_or(0.0, |g| g.advance),
+        };
+        for &(mark, id) in &parts[1..] {
+            let (m_x_min, m_y_min, m_x_max, m_y_max) = bbox(id);
+            if let Some(offset) = anchors.get_scaled(base_id, id, face, options) {
+                top = top.max(m_y_max + offset.1);
+                bottom = bottom.min(m_y_min + offset.1);
+                cluster.parts.push(ClusterPart { glyph_id: id.0, offset });
+                continue;
+            }
+            let dx = center - (m_x_min + m_x_max) / 2.0;
+            let dy = match unicode_normalization::char::canonical_combining_class(mark) {
+                ccc if ccc >= 228 => {
+                    let dy = (top - m_y_min).max(0.0);
+                    top = m_y_max + dy;
+                    dy
+                }
+                ccc if (218..228).contains(&ccc) => {
+                    let dy = (bottom - m_y_max).min(0.0);
+                    bottom = m_y_min + dy;
+                    dy
+                }
+                _ => 0.0,
+            };
+            cluster.parts.push(ClusterPart { glyph_id: id.0, offset: (dx, dy) });
+        }
+        font.clusters.insert(cp, cluster);
+    }
+    Ok(())
+}
+
+pub(crate) fn build_font(face: &ttf::Face, g_map: GlyphIdMap, unresolved: Vec<u32>, options: &FontOptions) -> Result<Font, FontLoadError> {
+    let scale = options.scale(face);
+    let mut warnings = Vec::new();
+    let kerning = load_kerning(face, &g_map, &HashMap::new(), options);
+    let mark_anchors = load_mark_anchors(face, &g_map, &HashMap::new(), options);
+    let ligatures = if options.ligatures { find_ligatures(face, &g_map) } else { Vec::new() };
+    let ligature_ids: Vec<ttf::GlyphId> = ligatures.iter().map(|l| ttf::GlyphId(l.glyph_id)).collect();
+    let glyph_id_map = load_glyphs_by_id(face, &ligature_ids, options, &mut warnings)?;
+    let glyphs = load_glyphs(face, g_map, options, &mut warnings)?;
+    let notdef = load_notdef(face, options);
+
+    let upem = face.units_per_em() as i16;
+    let x_height = face.x_height().or_else(|| glyph_top(face, 'x')).unwrap_or(upem / 2);
+    let cap_height = face.capital_height().or_else(|| glyph_top(face, 'H')).unwrap_or(face.ascender());
+    let underline = face.underline_metrics()
+        .unwrap_or(ttf::LineMetrics { position: -upem / 10, thickness: upem / 20 });
+    let strikeout = face.strikeout_metrics()
+        .unwrap_or(ttf::LineMetrics { position: (x_height + underline.thickness) / 2, thickness: underline.thickness });
+    let em = |v: i16| v as f32 * scale as f32;
+
+    Ok(Font {
+        name: face_name(face, ttf::name_id::FAMILY).unwrap_or_default(),
+        family_name: face_name(face, ttf::name_id::FAMILY).unwrap_or_default(),
+        style_name: face_name(face, ttf::name_id::SUBFAMILY).unwrap_or_default(),
+        weight: face.weight().to_number(),
+        italic: face.is_italic() || face.is_oblique(),
+        monospace: is_monospaced(face, &glyphs),
+        ascender: em(face.ascender()),
+        descender: em(face.descender()),
+        line_gap: em(face.line_gap()),
+        x_height: em(x_height),
+        cap_height: em(cap_height),
+        underline_position: em(underline.position),
+        underline_thickness: em(underline.thickness),
+        strikeout_position: em(strikeout.position),
+        strikeout_thickness: em(strikeout.thickness),
+        vertical_ascender: face.vertical_ascender().map(em),
+        vertical_descender: face.vertical_descender().map(em),
+        vertical_line_gap: face.vertical_line_gap().map(em),
+        em_size: options.em_size,
+        tolerance: Some(options.font_unit_tolerance(face) / face.units_per_em() as f32),
+        // Set by the callers, which have the font data.
+        source_hash: 0,
+        glyph_map: glyphs,
+        kerning,
+        mark_anchors,
+        glyph_id_map,
+        ligatures,
+        clusters: HashMap::new(),
+        opentype_features: opentype_features(face),
+        notdef,
+        unresolved,
+        warnings,
+    })
+}
+
+/// Fits the advances of a freshly loaded or extended `font` and hashes its source `data`;
+/// the last step of every loader, so advances are fitted once over all loaded glyphs.
+fn finish_font(font: &mut Font, data: &[u8], options: &FontOptions) {
+    fit_advances(font, options);
+    font.source_hash = source_hash(data, font, options);
+}
+
+/// Glyph 0 of `face`, or a hollow box of `options.notdef_size` if it draws nothing. The box is
+/// styled like any outline, e.g. stroked, emboldened or slanted by `options`.
+fn load_notdef(face: &ttf::Face, options: &FontOptions) -> Glyph {
+    let mut fill_tess = FillTessellator::new();
+    let (glyph, _) = load_glyph(face, ttf::GlyphId(0), options, &mut fill_tess);
+    if glyph.bbox.0 < glyph.bbox.2 || glyph.image.is_some() || !glyph.svg_layers.is_empty() {
+        return glyph;
+    }
+
+    // Laid out in output units, drawn in the font units of outlines.
+    let em = options.em_size;
+    let unit = (1.0 / options.scale(face)) as f32;
+    let (width, height) = (options.notdef_size.0 * em, options.notdef_size.1 * em);
+    let advance = if glyph.advance > 0.0 { glyph.advance } else { width + 0.2 * em };
+    let (x0, x1) = ((advance - width) / 2.0, (advance + width) / 2.0);
+    let inset = width.min(height) / 8.0;
+    let mut path = lyon::path::Path::builder();
+    for (x0, y0, x1, y1) in [(x0, 0.0, x1, height), (x0 + inset, inset, x1 - inset, height - inset)] {
+        path.begin(Point::new(x0, y0) * unit);
+        path.line_to(Point::new(x1, y0) * unit);
+        path.line_to(Point::new(x1, y1) * unit);
+        path.line_to(Point::new(x0, y1) * unit);
+        path.end(true);
+    }
+    let bbox = ((x0 * unit) as f64, 0.0, (x1 * unit) as f64, (height * unit) as f64);
+    let outline = Some((path.build().iter().collect(), bbox));
+    let (boxed, _) = glyph_from_outline(face, ttf::GlyphId(0), outline, false, options, &mut fill_tess);
+    Glyph {
+        advance,
+        is_empty: false,
+        lsb: boxed.bbox.0,
+        rsb: advance - boxed.bbox.2,
+        ..boxed
+    }
+}
+
+/// Top of the outline of `ch` in font units, for metrics missing from the OS/2 table.
+fn glyph_top(face: &ttf::Face, ch: char) -> Option<i16> {
+    face.glyph_bounding_box(face.glyph_index(ch)?).map(|b| b.y_max)
+}
+
+/// Fixed pitch according to the post table or the OS/2 PANOSE, or with every glyph of the face
+/// advancing the same; the non-zero advances of `loaded` must agree in any case.
+fn is_monospaced(face: &ttf::Face, loaded: &HashMap<u32, Glyph>) -> bool {
+    // PANOSE family "Latin Text" (2) with proportion "Monospaced" (9).
+    let panose = face.table_data(ttf::Tag::from_bytes(b"OS/2"))
+        .is_some_and(|os2| os2.get(32) == Some(&2) && os2.get(35) == Some(&9));
+    let flagged = face.is_monospaced() || panose
+        || uniform_advances((0..face.number_of_glyphs()).filter_map(|id| face.glyph_hor_advance(ttf::GlyphId(id))));
+    flagged && uniform_advances(loaded.values().map(|g| g.advance))
+}
+
+/// Whether all non-zero `advances` are equal; zero advances are combining marks and the like.
+fn uniform_advances<T: PartialEq + Default>(advances: impl Iterator<Item = T>) -> bool {
+    let mut advances = advances.filter(|a| *a != T::default());
+    let first = advances.next();
+    advances.all(|a| Some(a) == first)
+}
+
+/// Applies `options.force_advance`, or else `options.tabular_figures`, to the glyphs of `font`.
+pub(crate) fn fit_advances(font: &mut Font, options: &FontOptions) {
+    let loaded = || font.glyph_map.values().chain(font.glyph_id_map.values()).map(|g| g.advance).fold(0.0, f32::max);
+    let Some(width) = fitted_advance(options, loaded) else {
+        return;
+    };
+    for glyph in font.glyph_map.values_mut().chain(font.glyph_id_map.values_mut()).chain([&mut font.notdef]) {
+        fit_glyph(glyph, width, options);
+    }
+    if options.force_advance.is_some() {
+        for cluster in font.clusters.values_mut() {
+            cluster.advance = width;
+        }
+    }
+}
+
+/// Advance every glyph gets under `options`, with `max_advance` giving the largest loaded
+/// one; `None` keeps the advances of the face.
+pub(crate) fn fitted_advance(options: &FontOptions, max_advance: impl FnOnce() -> f32) -> Option<f32> {
+    match options.force_advance {
+        Some(Advance::MaxLoaded) => Some(max_advance()),
+        Some(Advance::Em(em)) => Some(em * options.em_size),
+        None => options.tabular_figures.then(max_advance),
+    }
+}
+
+/// Largest advance of any glyph of `face` in output units, for fitting glyphs that are
+/// loaded one at a time.
+pub(crate) fn face_max_advance(face: &ttf::Face, options: &FontOptions) -> f32 {
+    let max = (0..face.number_of_glyphs()).filter_map(|id| face.glyph_hor_advance(ttf::GlyphId(id))).max().unwrap_or(0);
+    max as f32 * options.scale(face) as f32 + options.bold_strength.max(0.0) * options.em_size
+}
+
+/// Gives `glyph` the advance `width`, moving its ink to the middle of the cell for
+/// `options.force_advance` with `options.center_in_cell`.
+pub(crate) fn fit_glyph(glyph: &mut Glyph, width: f32, options: &FontOptions) {
+    let dx = if options.force_advance.is_some() && options.center_in_cell && glyph.bbox.0 < glyph.bbox.2 {
+        (width - (glyph.bbox.2 - glyph.bbox.0)) / 2.0 - glyph.bbox.0
+    } else {
+        0.0
+    };
+    if dx != 0.0 {
+        shift_glyph(glyph, dx);
+    }
+    glyph.lsb += dx;
+    glyph.rsb += width - glyph.advance - dx;
+    glyph.advance = width;
+}
+
+/// Moves the outline, bbox and SVG layers of `glyph` right by `dx`.
+fn shift_glyph(glyph: &mut Glyph, dx: f32) {
+    for v in &mut glyph.outline.vertices {
+        *v = v.offset_position(dx, 0.0);
+    }
+    glyph.bbox.0 += dx;
+    glyph.bbox.2 += dx;
+    if let Some(events) = &mut glyph.path_events {
+        let t = lyon::math::Transform::translation(dx, 0.0);
+        for e in events.iter_mut() {
+            *e = e.transformed(&t);
+        }
+    }
+    for layer in &mut glyph.svg_layers {
+        *layer = layer.transform_matrix([[1.0, 0.0, dx], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+    }
+}
+
+/// Fails with `OutlineFailed` for glyphs whose outline cannot be loaded when the missing-glyph
+/// policy is `Error`; otherwise such glyphs get an empty mesh and a warning.
+pub(crate) fn load_glyphs(face: &ttf::Face, g_map: GlyphIdMap, options: &FontOptions, warnings: &mut Vec<FontWarning>) -> Result<HashMap<u32, Glyph>, FontLoadError> {
+    let mut ids: Vec<(u32, ttf::GlyphId)> = g_map.into_iter().collect();
+    ids.sort_unstable_by_key(|&(cp, _)| cp);
+    let glyph_ids: Vec<ttf::GlyphId> = ids.iter().map(|&(_, id)| id).collect();
+    load_glyph_list(face, ids, options).into_iter().zip(glyph_ids).map(|((cp, glyph, has_advance, valid), id)| {
+        if !has_advance {
+            warnings.push(FontWarning::MissingAdvance { codepoint: cp });
+        }
+        if !valid {
+            if options.missing_glyph == MissingGlyph::Error {
+                return Err(FontLoadError::OutlineFailed { glyph: id.0 });
+            }
+            warnings.push(FontWarning::InvalidOutline { codepoint: cp });
+        }
+        Ok((cp, glyph))
+    }).collect()
+}
+
+/// Like [`load_glyphs`] but keyed by glyph id, for glyphs produced by shaping that may have
+/// no codepoint of their own (ligatures, contextual forms).
+pub(crate) fn load_glyphs_by_id(face: &ttf::Face, ids: &[ttf::GlyphId], options: &FontOptions, warnings: &mut Vec<FontWarning>) -> Result<HashMap<u16, Glyph>, FontLoadError> {
+    let ids = ids.iter().map(|&id| (id.0, id)).collect();
+    load_glyph_list(face, ids, options).into_iter().map(|(id, glyph, has_advance, valid)| {
+        if !has_advance {
+            warnings.push(FontWarning::MissingGlyphAdvance { glyph_id: id });
+        }
+        if !valid {
+            if options.missing_glyph == MissingGlyph::Error {
+                return Err(FontLoadError::OutlineFailed { glyph: id });
+            }
+            warnings.push(FontWarning::InvalidGlyphOutline { glyph_id: id });
+        }
+        Ok((id, glyph))
+    }).collect()
+}
+
+/// Loads `ids` in order, each with whether it has an advance and whether its outline could be
+/// read. Runs on all cores with the `rayon` feature; the result is the same either way.
+#[cfg(not(feature = "rayon"))]
+fn load_glyph_list<K>(face: &ttf::Face, ids: Vec<(K, ttf::GlyphId)>, options: &FontOptions) -> Vec<(K, Glyph, bool, bool)> {
+    load_glyph_list_sequential(face, ids, options)
+}
 
-    #[allow(unused_mut)]
-        let mut face = ttf::Face::from_slice(&font_data, 0)?;
-    // if face.is_variable() {
-    //     #[cfg(feature = "variable-fonts")] {
-    //         for variation in args.variations {
-    //         face.set_variation(variation.axis, variation.value)
-    //         .ok_or("failed to create variation coordinates")?;
-    //         }
-    //     }
-    // }
-
-    let units_per_em = face.units_per_em();
-    let scale = FONT_SIZE / units_per_em as f64;
+/// Single-threaded `load_glyph_list`, which the parallel one must match.
+#[cfg(any(test, not(feature = "rayon")))]
+fn load_glyph_list_sequential<K>(face: &ttf::Face, ids: Vec<(K, ttf::GlyphId)>, options: &FontOptions) -> Vec<(K, Glyph, bool, bool)> {
     let mut fill_tess = FillTessellator::new();
+    ids.into_iter().map(|(key, id)| {
+        let (glyph, valid) = load_glyph(face, id, options, &mut fill_tess);
+        (key, glyph, face.glyph_hor_advance(id).is_some(), valid)
+    }).collect()
+}
+
+#[cfg(feature = "rayon")]
+fn load_glyph_list<K: Send>(face: &ttf::Face, ids: Vec<(K, ttf::GlyphId)>, options: &FontOptions) -> Vec<(K, Glyph, bool, bool)> {
+    use rayon::prelude::*;
+
+    ids.into_par_iter().map_init(FillTessellator::new, |fill_tess, (key, id)| {
+        let (glyph, valid) = load_glyph(face, id, options, fill_tess);
+        (key, glyph, face.glyph_hor_advance(id).is_some(), valid)
+    }).collect()
+}
+
+/// Loads glyph `id`, and tells whether its outline could be read and tessellated; glyphs
+/// without an outline, like the space, count as readable. Failed glyphs get an empty mesh.
+fn load_glyph(face: &ttf::Face, id: ttf::GlyphId, options: &FontOptions, fill_tess: &mut FillTessellator) -> (Glyph, bool) {
+    let mut builder = Builder::new();
+    let ok = builder.build(face, id).is_some();
+    let is_empty = !ok && has_no_outline(face, id, &builder);
+    let b = builder.bbbox;
+    let outline = ok.then_some((builder.vec, (b.x_min as f64, b.y_min as f64, b.x_max as f64, b.y_max as f64)));
+    glyph_from_outline(face, id, outline, is_empty, options, fill_tess)
+}
+
+/// Path of a glyph in font units with its bounding box.
+type Outline = (Vec<PathEvent>, (f64, f64, f64, f64));
+
+/// Glyph `id` drawn by `outline`, or without one if it could not be read; see [`load_glyph`].
+fn glyph_from_outline(face: &ttf::Face, id: ttf::GlyphId, outline: Option<Outline>, is_empty: bool,
+                      options: &FontOptions, fill_tess: &mut FillTessellator) -> (Glyph, bool) {
+    let scale = options.scale(face);
+    let tolerance = options.font_unit_tolerance(face);
+
+    let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+    let mut ok = outline.is_some();
+
+    let mut bbox = (0.0,0.0,0.0,0.0);
+    let mut path_events = None;
+    let (mut stroke_outline, mut stroke_bbox) = (None, None);
+    if let Some((events, outline_bbox)) = outline {
+        let mut tessellated = true;
+        // Outlines are y-up with the baseline at y = 0 already, so only the scale, the
+        // synthetic italic shear and `glyph_transform` apply.
+        let transform = options.outline_transform(scale, scale);
+        bbox = outline_bbox;
+        if options.retain_path_events {
+            let t = lyon_transform(&transform);
+            path_events = Some(events.iter().map(|e| e.transformed(&t)).collect());
+        }
+        if options.tessellate && options.bold_strength > 0.0 {
+            // Font units, like the outline; the stroke overlaps the fill, which is fine for opaque text.
+            let width = options.bold_strength as f64 * face.units_per_em() as f64;
+            tessellated &= StrokeTessellator::new()
+                .tessellate(
+                    events.iter().copied(),
+                    &StrokeOptions::tolerance(tolerance).with_line_width(width as f32).with_line_join(LineJoin::Round),
+                    &mut BuffersBuilder::new(
+                        &mut mesh,
+                        VertexCtor {
+                            prim_id: options.prim_id_base + id.0 as u32,
+                            transform,
+                        },
+                    ),
+                )
+                .is_ok();
+            bbox = (bbox.0 - width / 2.0, bbox.1 - width / 2.0, bbox.2 + width / 2.0, bbox.3 + width / 2.0);
+        }
+        if let Some(stroke) = options.outline_stroke.filter(|_| options.tessellate) {
+            let mut stroke_mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
+            let stroke_options = StrokeOptions::tolerance(tolerance)
+                .with_line_width(stroke.width * face.units_per_em() as f32)
+                .with_line_cap(stroke.cap)
+                .with_line_join(stroke.join);
+            tessellated &= StrokeTessellator::new()
+                .tessellate(
+                    events.iter().copied(),
+                    &stroke_options,
+                    &mut BuffersBuilder::new(
+                        &mut stroke_mesh,
+                        VertexCtor {
+                            prim_id: options.prim_id_base + id.0 as u32,
+                            transform,
+                        },
+                    ),
+                )
+                .is_ok();
+            // Taken from the mesh since miter joins may reach past half the width.
+            if !stroke_mesh.vertices.is_empty() {
+                stroke_bbox = Some(stroke_mesh.vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |b, v| {
+                    (b.0.min(v.position[0]), b.1.min(v.position[1]), b.2.max(v.position[0]), b.3.max(v.position[1]))
+                }));
+                stroke_outline = Some(stroke_mesh);
+            }
+        }
+        if options.tessellate {
+            tessellated &= fill_tess
+                .tessellate(
+                    events,
+                    &FillOptions::tolerance(tolerance),
+                    &mut BuffersBuilder::new(
+                        &mut mesh,
+                        VertexCtor {
+                            prim_id: options.prim_id_base + id.0 as u32,
+                            transform,
+                        },
+                    ),
+                )
+                .is_ok();
+        }
+        if !tessellated {
+            (mesh, stroke_outline, stroke_bbox) = (VertexBuffers::new(), None, None);
+            ok = false;
+        }
+        let corners = [(bbox.0, bbox.1), (bbox.2, bbox.1), (bbox.0, bbox.3), (bbox.2, bbox.3)].map(|(x, y)| transform.apply(x, y));
+        bbox = corners.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, &(x, y)| {
+            (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y))
+        });
+    }
+
+    // SVG glyphs fall back to the outline above when their document does not parse.
+    let mut svg_layers = face.glyph_svg_image(id)
+        .filter(|_| options.tessellate)
+        .and_then(|doc| {
+            let transform = options.outline_transform(scale, -scale);
+            let options = LoadOptions { tolerance, ..LoadOptions::default() };
+            load_svg_glyph(doc, id.0, transform, &options).ok()
+        })
+        .unwrap_or_default();
+    for v in svg_layers.iter_mut().flat_map(|layer| &mut layer.vertices.vertices) {
+        v.prim_id += options.prim_id_base;
+    }
+
+    let image = if ok || !svg_layers.is_empty() {
+        None
+    } else {
+        face.glyph_raster_image(id, options.bitmap_pixels_per_em).map(|img| GlyphImage {
+            png: img.data.to_vec(),
+            width: img.width,
+            height: img.height,
+            x: img.x,
+            y: img.y,
+            pixels_per_em: img.pixels_per_em,
+        })
+    };
+
+    let advance = face.glyph_hor_advance(id).unwrap_or(0);
+    let v_origin_y = face.glyph_y_origin(id).or_else(|| {
+        let top = face.glyph_bounding_box(id).map_or(0, |b| b.y_max);
+        face.glyph_ver_side_bearing(id).map(|tsb| top.saturating_add(tsb))
+    });
+    debug_assert!(bbox.0 <= bbox.2 && bbox.1 <= bbox.3, "unordered bbox {:?} for glyph {}", bbox, id.0);
+    let advance = advance as f32 * scale as f32 + options.bold_strength.max(0.0) * options.em_size;
+    let lsb = face.glyph_hor_side_bearing(id).unwrap_or(0) as f32 * scale as f32;
+    let glyph = Glyph {
+        advance,
+        outline: mesh,
+        bbox: (bbox.0 as f32, bbox.1 as f32, bbox.2 as f32, bbox.3 as f32),
+        path_events,
+        face: 0,
+        svg_layers,
+        image,
+        is_empty,
+        v_advance: face.glyph_ver_advance(id).map(|a| a as f32 * scale as f32),
+        v_origin_y: v_origin_y.map(|y| y as f32 * scale as f32),
+        lsb,
+        rsb: advance - lsb - (bbox.2 - bbox.0) as f32,
+        stroke_outline,
+        stroke_bbox,
+    };
+    (glyph, ok || is_empty)
+}
+
+/// Whether the font stores no outline for `id` at all, as opposed to one that failed to parse.
+/// TrueType marks such glyphs with equal consecutive `loca` offsets; for other outline formats
+/// a glyph counts as empty if parsing produced no path commands.
+fn has_no_outline(face: &ttf::Face, id: ttf::GlyphId, builder: &Builder) -> bool {
+    let loca = match face.table_data(ttf::Tag::from_bytes(b"loca")) {
+        Some(data) if face.tables().glyf.is_some() => data,
+        _ => return builder.vec.is_empty(),
+    };
+    let offset = |i: usize| match face.tables().head.index_to_location_format {
+        ttf::head::IndexToLocationFormat::Short => loca.get(i * 2..i * 2 + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32 * 2),
+        ttf::head::IndexToLocationFormat::Long => loca.get(i * 4..i * 4 + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+    };
+    let i = id.0 as usize;
+    matches!((offset(i), offset(i + 1)), (Some(start), Some(end)) if start == end)
+}
+
+/// Pair kerning of a face: the lookups of the GPOS `kern` feature when the font has one, the
+/// legacy `kern` table otherwise. Each pair adjustment subtable is kept with its raw bytes,
+/// which list the pairs of format 1 subtables.
+struct KerningTables<'a> {
+    lookups: Vec<Vec<(ttf::gpos::PairAdjustment<'a>, &'a [u8])>>,
+    kern: Option<ttf::kern::Table<'a>>,
+}
+
+impl<'a> KerningTables<'a> {
+    fn new(face: &ttf::Face<'a>) -> Self {
+        let mut lookups = Vec::new();
+        if let (Some(gpos), Some(data)) = (face.tables().gpos, face.table_data(ttf::Tag::from_bytes(b"GPOS"))) {
+            for index in feature_lookups(&gpos, b"kern") {
+                if let Some(lookup) = gpos.lookups.get(index) {
+                    let pairs: Vec<_> = lookup.subtables.into_iter::<ttf::gpos::PositioningSubtable>()
+                        .zip(raw_lookup_subtables(data, index))
+                        .filter_map(|(s, raw)| match s {
+                            ttf::gpos::PositioningSubtable::Pair(pair) => Some((pair, raw)),
+                            _ => None,
+                        })
+                        .collect();
+                    if !pairs.is_empty() {
+                        lookups.push(pairs);
+                    }
+                }
+            }
+        }
+        let kern = if lookups.is_empty() { face.tables().kern } else { None };
+        KerningTables { lookups, kern }
+    }
+
+    /// Pairs of `glyphs` that some subtable may kern, read from the tables' coverage, pair
+    /// sets, class definitions and pair lists instead of trying every pair. Only legacy `kern`
+    /// subtables without a pair list (formats 2 and 3, rare outside old Apple fonts) are
+    /// tried for every pair.
+    fn candidate_pairs(&self, glyphs: &HashSet<ttf::GlyphId>) -> HashSet<(ttf::GlyphId, ttf::GlyphId)> {
+        let mut pairs = HashSet::new();
+        for (pair, raw) in self.lookups.iter().flatten() {
+            match pair {
+                ttf::gpos::PairAdjustment::Format1 { coverage, .. } => {
+                    for &left in glyphs {
+                        if let Some(set) = coverage.get(left) {
+                            pairs.extend(pair_set_glyphs(raw, set).filter(|r| glyphs.contains(r)).map(|r| (left, r)));
+                        }
+                    }
+                }
+                ttf::gpos::PairAdjustment::Format2 { coverage, classes, matrix } => {
+                    let mut lefts: HashMap<u16, Vec<ttf::GlyphId>> = HashMap::new();
+                    let mut rights: HashMap<u16, Vec<ttf::GlyphId>> = HashMap::new();
+                    for &id in glyphs {
+                        if coverage.contains(id) {
+                            lefts.entry(classes.0.get(id)).or_default().push(id);
+                        }
+                        rights.entry(classes.1.get(id)).or_default().push(id);
+                    }
+                    for (&c1, ls) in &lefts {
+                        for (&c2, rs) in &rights {
+                            if matrix.get((c1, c2)).is_some_and(|v| v.0.x_advance != 0) {
+                                pairs.extend(ls.iter().flat_map(|&l| rs.iter().map(move |&r| (l, r))));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(kern) = &self.kern {
+            for subtable in kern.subtables {
+                if !subtable.horizontal || subtable.variable || subtable.has_cross_stream {
+                    continue;
+                }
+                match subtable.format {
+                    ttf::kern::Format::Format0(table) => pairs.extend(table.pairs.into_iter()
+                        .map(|p| (p.left(), p.right()))
+                        .filter(|(l, r)| glyphs.contains(l) && glyphs.contains(r))),
+                    _ => pairs.extend(glyphs.iter().flat_map(|&l| glyphs.iter().map(move |&r| (l, r)))),
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Adjustment in font units; within a lookup the first matching subtable applies.
+    fn get(&self, left: ttf::GlyphId, right: ttf::GlyphId) -> i32 {
+        let mut value = 0;
+        for lookup in &self.lookups {
+            if let Some(v) = lookup.iter().find_map(|(pair, _)| pair_adjustment(pair, left, right)) {
+                value += v as i32;
+            }
+        }
+        if let Some(kern) = &self.kern {
+            for subtable in kern.subtables {
+                if subtable.horizontal && !subtable.variable && !subtable.has_cross_stream {
+                    value += subtable.glyphs_kerning(left, right).unwrap_or(0) as i32;
+                }
+            }
+        }
+        value
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
+
+/// Bytes of each subtable of lookup `index` of the GPOS table `gpos`, in lookup order, with
+/// extension subtables (type 9) resolved to the subtable they point to.
+fn raw_lookup_subtables(gpos: &[u8], index: u16) -> Vec<&[u8]> {
+    let subtables = || -> Option<Vec<&[u8]>> {
+        let list = read_u16(gpos, 8)? as usize;
+        let lookup = list + read_u16(gpos, list + 2 + 2 * index as usize)? as usize;
+        let (kind, count) = (read_u16(gpos, lookup)?, read_u16(gpos, lookup + 4)?);
+        (0..count as usize).map(|i| {
+            let start = lookup + read_u16(gpos, lookup + 6 + 2 * i)? as usize;
+            let start = if kind == 9 {
+                let offset = u32::from_be_bytes(gpos.get(start + 4..start + 8)?.try_into().ok()?);
+                start + offset as usize
+            } else {
+                start
+            };
+            gpos.get(start..)
+        }).collect()
+    };
+    subtables().unwrap_or_default()
+}
+
+/// Second glyphs of pair set `set` of the format 1 pair adjustment subtable `subtable`.
+fn pair_set_glyphs(subtable: &[u8], set: u16) -> impl Iterator<Item = ttf::GlyphId> + '_ {
+    let records = || -> Option<(usize, usize, usize)> {
+        let value_len = |format: u16| 2 * format.count_ones() as usize;
+        let record_len = 2 + value_len(read_u16(subtable, 4)?) + value_len(read_u16(subtable, 6)?);
+        let start = read_u16(subtable, 10 + 2 * set as usize)? as usize;
+        Some((start + 2, read_u16(subtable, start)? as usize, record_len))
+    };
+    let (start, count, record_len) = records().unwrap_or((0, 0, 0));
+    (0..count).filter_map(move |i| read_u16(subtable, start + i * record_len).map(ttf::GlyphId))
+}
+
+/// Mark-to-base attachment subtables (GPOS lookup type 4) of the `mark` feature.
+struct MarkAnchors<'a> {
+    subtables: Vec<ttf::gpos::MarkToBaseAdjustment<'a>>,
+}
+
+impl<'a> MarkAnchors<'a> {
+    fn new(face: &ttf::Face<'a>) -> Self {
+        let mut subtables = Vec::new();
+        if let Some(gpos) = face.tables().gpos {
+            for index in feature_lookups(&gpos, b"mark") {
+                if let Some(lookup) = gpos.lookups.get(index) {
+                    subtables.extend(lookup.subtables.into_iter::<ttf::gpos::PositioningSubtable>()
+                        .filter_map(|s| match s {
+                            ttf::gpos::PositioningSubtable::MarkToBase(m) => Some(m),
+                            _ => None,
+                        }));
+                }
+            }
+        }
+        MarkAnchors { subtables }
+    }
+
+    /// Offset of the mark origin from the base origin in font units, from the first subtable
+    /// covering both glyphs.
+    fn get(&self, base: ttf::GlyphId, mark: ttf::GlyphId) -> Option<(i32, i32)> {
+        self.subtables.iter().find_map(|m| {
+            let (class, mark_anchor) = m.marks.get(m.mark_coverage.get(mark)?)?;
+            let base_anchor = m.anchors.get(m.base_coverage.get(base)?, class)?;
+            Some((base_anchor.x as i32 - mark_anchor.x as i32, base_anchor.y as i32 - mark_anchor.y as i32))
+        })
+    }
+
+    /// [`MarkAnchors::get`] in output units, transformed like the outlines.
+    fn get_scaled(&self, base: ttf::GlyphId, mark: ttf::GlyphId, face: &ttf::Face, options: &FontOptions) -> Option<(f32, f32)> {
+        let (dx, dy) = self.get(base, mark)?;
+        let transform = options.outline_transform(options.scale(face), options.scale(face));
+        let (x, y) = transform.apply(dx as f64, dy as f64);
+        Some(((x - transform.e) as f32, (y - transform.f) as f32))
+    }
+}
+
+/// Sorted, distinct feature tags of the GSUB and GPOS tables of `face`.
+fn opentype_features(face: &ttf::Face) -> Vec<String> {
+    let tables = [face.tables().gsub, face.tables().gpos];
+    let mut tags: Vec<String> = tables.iter().flatten()
+        .flat_map(|table| table.features.into_iter().map(|f| f.tag.to_string()))
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    tags
+}
+
+/// Indices of the lookups of every feature tagged `tag`, in lookup order.
+fn feature_lookups(table: &ttf::opentype_layout::LayoutTable, tag: &[u8; 4]) -> Vec<u16> {
+    let tag = ttf::Tag::from_bytes(tag);
+    let mut indices: Vec<u16> = table.features.into_iter()
+        .filter(|f| f.tag == tag)
+        .flat_map(|f| f.lookup_indices.into_iter())
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Ligatures of the GSUB `liga` feature whose components are all among the loaded codepoints.
+pub(crate) fn find_ligatures(face: &ttf::Face, loaded: &GlyphIdMap) -> Vec<Ligature> {
+    let gsub = match face.tables().gsub {
+        Some(gsub) => gsub,
+        None => return Vec::new(),
+    };
+    let mut codepoints: BTreeMap<u16, u32> = BTreeMap::new();
+    for (&cp, id) in loaded {
+        if id.0 != 0 {
+            let entry = codepoints.entry(id.0).or_insert(cp);
+            *entry = (*entry).min(cp);
+        }
+    }
+
+    let mut ligatures = Vec::new();
+    for index in feature_lookups(&gsub, b"liga") {
+        let lookup = match gsub.lookups.get(index) {
+            Some(lookup) => lookup,
+            None => continue,
+        };
+        for subtable in lookup.subtables.into_iter::<ttf::gsub::SubstitutionSubtable>() {
+            let subst = match subtable {
+                ttf::gsub::SubstitutionSubtable::Ligature(subst) => subst,
+                _ => continue,
+            };
+            for (&first, &first_cp) in &codepoints {
+                let set = match subst.coverage.get(ttf::GlyphId(first)).and_then(|i| subst.ligature_sets.get(i)) {
+                    Some(set) => set,
+                    None => continue,
+                };
+                for ligature in set {
+                    let rest: Option<Vec<u32>> = ligature.components.into_iter()
+                        .map(|id| codepoints.get(&id.0).copied())
+                        .collect();
+                    if let Some(rest) = rest {
+                        let mut components = vec![first_cp];
+                        components.extend(rest);
+                        if !ligatures.iter().any(|l: &Ligature| l.components == components) {
+                            ligatures.push(Ligature { components, glyph_id: ligature.glyph.0 });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ligatures
+}
+
+fn pair_adjustment(pair: &ttf::gpos::PairAdjustment, left: ttf::GlyphId, right: ttf::GlyphId) -> Option<i16> {
+    let records = match pair {
+        ttf::gpos::PairAdjustment::Format1 { coverage, sets } => {
+            sets.get(coverage.get(left)?)?.get(right)?
+        }
+        ttf::gpos::PairAdjustment::Format2 { coverage, classes, matrix } => {
+            if !coverage.contains(left) {
+                return None;
+            }
+            matrix.get((classes.0.get(left), classes.1.get(right)))?
+        }
+    };
+    Some(records.0.x_advance)
+}
+
+/// Codepoints of `new` and `existing` by glyph id, skipping .notdef.
+fn codepoints_by_glyph(new: &GlyphIdMap, existing: &GlyphIdMap) -> HashMap<ttf::GlyphId, Vec<u32>> {
+    let mut codepoints: HashMap<ttf::GlyphId, Vec<u32>> = HashMap::new();
+    for (&cp, &id) in new.iter().chain(existing.iter().filter(|(cp, _)| !new.contains_key(cp))) {
+        if id.0 != 0 {
+            codepoints.entry(id).or_default().push(cp);
+        }
+    }
+    codepoints
+}
+
+/// Calls `f` with every codepoint pair of the glyph pair `(left, right)` that has at least one
+/// side in `new`.
+fn for_codepoint_pairs(codepoints: &HashMap<ttf::GlyphId, Vec<u32>>, new: &GlyphIdMap,
+                       (left, right): (ttf::GlyphId, ttf::GlyphId), mut f: impl FnMut(u32, u32)) {
+    for &l in &codepoints[&left] {
+        for &r in &codepoints[&right] {
+            if new.contains_key(&l) || new.contains_key(&r) {
+                f(l, r);
+            }
+        }
+    }
+}
+
+/// Non-zero kerning of every pair with at least one side in `new`, the other side in `new`
+/// or `existing`, in em units. Pairs come from the kerning tables, so the cost follows the
+/// number of kerned pairs rather than the square of the loaded glyphs.
+pub(crate) fn load_kerning(face: &ttf::Face, new: &GlyphIdMap, existing: &GlyphIdMap, options: &FontOptions) -> HashMap<(u32, u32), f32> {
+    let mut kerning = HashMap::new();
+    if options.force_advance.is_some() {
+        return kerning;
+    }
+    let tables = KerningTables::new(face);
+    let scale = options.scale(face) as f32;
+    let codepoints = codepoints_by_glyph(new, existing);
+    let glyphs: HashSet<ttf::GlyphId> = codepoints.keys().copied().collect();
+    for pair in tables.candidate_pairs(&glyphs) {
+        let value = tables.get(pair.0, pair.1);
+        if value != 0 {
+            for_codepoint_pairs(&codepoints, new, pair, |l, r| {
+                kerning.insert((l, r), value as f32 * scale);
+            });
+        }
+    }
+    kerning
+}
+
+/// Mark attachment offsets of every `(base, mark)` pair with at least one side in `new`, the
+/// other side in `new` or `existing`, in em units; see `Font::mark_anchors`. Pairs come from
+/// the coverage tables of the mark attachment subtables.
+pub(crate) fn load_mark_anchors(face: &ttf::Face, new: &GlyphIdMap, existing: &GlyphIdMap, options: &FontOptions) -> HashMap<(u32, u32), (f32, f32)> {
+    let anchors = MarkAnchors::new(face);
+    let mut offsets = HashMap::new();
+    if anchors.subtables.is_empty() {
+        return offsets;
+    }
+    let codepoints = codepoints_by_glyph(new, existing);
+    let mut pairs = HashSet::new();
+    for m in &anchors.subtables {
+        let marks: Vec<ttf::GlyphId> = codepoints.keys().copied().filter(|&id| m.mark_coverage.contains(id)).collect();
+        for base in codepoints.keys().copied().filter(|&id| m.base_coverage.contains(id)) {
+            pairs.extend(marks.iter().map(|&mark| (base, mark)));
+        }
+    }
+    for pair in pairs {
+        if let Some(offset) = anchors.get_scaled(pair.0, pair.1, face, options) {
+            for_codepoint_pairs(&codepoints, new, pair, |base, mark| {
+                offsets.insert((base, mark), offset);
+            });
+        }
+    }
+    offsets
+}
+
+/// FNV-1a hash of `data`, the codepoints requested from it (those in `glyph_map`, `clusters`
+/// and `unresolved` of `font`) and `options`, see `Font::source_hash`. Stable across runs,
+/// platforms and compiler versions, unlike `std`'s hashers.
+pub(crate) fn source_hash(data: &[u8], font: &Font, options: &FontOptions) -> u64 {
+    let codepoints: BTreeSet<u32> = font.glyph_map.keys().chain(font.clusters.keys()).chain(&font.unresolved)
+        .copied()
+        .collect();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = data.iter().copied()
+        .chain(codepoints.into_iter().flat_map(u32::to_le_bytes))
+        .chain(options_bytes(options));
+    for byte in bytes {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Every field of `options` in declaration order, little endian, with a tag byte before
+/// optional values and a length before lists, for [`source_hash`].
+fn options_bytes(options: &FontOptions) -> Vec<u8> {
+    let FontOptions {
+        missing_glyph, retain_path_events, face_index, ligatures, variations, bitmap_pixels_per_em, tabular_figures,
+        em_size, tolerance, italic_angle, bold_strength, tessellate, notdef_size, prim_id_base, glyph_transform,
+        force_advance, center_in_cell, outline_stroke,
+    } = options;
+    let mut bytes = Vec::new();
+    bytes.push(match missing_glyph {
+        MissingGlyph::Notdef => 0,
+        MissingGlyph::Skip => 1,
+        MissingGlyph::Error => 2,
+    });
+    bytes.push(*retain_path_events as u8);
+    bytes.extend(face_index.to_le_bytes());
+    bytes.push(*ligatures as u8);
+    bytes.extend((variations.len() as u32).to_le_bytes());
+    for (axis, value) in variations {
+        bytes.extend((axis.len() as u32).to_le_bytes());
+        bytes.extend(axis.bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+    bytes.extend(bitmap_pixels_per_em.to_le_bytes());
+    bytes.push(*tabular_figures as u8);
+    bytes.extend(em_size.to_le_bytes());
+    match tolerance {
+        Some(tolerance) => {
+            bytes.push(1);
+            bytes.extend(tolerance.to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.extend(italic_angle.to_le_bytes());
+    bytes.extend(bold_strength.to_le_bytes());
+    bytes.push(*tessellate as u8);
+    bytes.extend(notdef_size.0.to_le_bytes());
+    bytes.extend(notdef_size.1.to_le_bytes());
+    bytes.extend(prim_id_base.to_le_bytes());
+    match glyph_transform {
+        Some(t) => {
+            bytes.push(1);
+            for v in [t.a, t.b, t.c, t.d, t.e, t.f] {
+                bytes.extend(v.to_le_bytes());
+            }
+        }
+        None => bytes.push(0),
+    }
+    match force_advance {
+        Some(Advance::MaxLoaded) => bytes.push(1),
+        Some(Advance::Em(width)) => {
+            bytes.push(2);
+            bytes.extend(width.to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes.push(*center_in_cell as u8);
+    match outline_stroke {
+        Some(stroke) => {
+            bytes.push(1);
+            bytes.extend(stroke.width.to_le_bytes());
+            bytes.push(match stroke.cap {
+                LineCap::Butt => 0,
+                LineCap::Square => 1,
+                LineCap::Round => 2,
+            });
+            bytes.push(match stroke.join {
+                LineJoin::Miter => 0,
+                LineJoin::MiterClip => 1,
+                LineJoin::Round => 2,
+                LineJoin::Bevel => 3,
+            });
+        }
+        None => bytes.push(0),
+    }
+    bytes
+}
+
+/// Glyph ids of the codepoints already loaded into `font`, for kerning them against new ones.
+pub(crate) fn loaded_glyph_ids(face: &ttf::Face, font: &Font) -> GlyphIdMap {
+    font.glyph_map.keys()
+        .filter_map(|&cp| Some((cp, face.glyph_index(char::from_u32(cp)?)?)))
+        .collect()
+}
+
+/// Outcome of [`Font::add_symbols`].
+#[derive(Clone, Debug, Default)]
+pub struct AddedReport {
+    /// Codepoints tessellated and inserted by this call.
+    pub added: Vec<u32>,
+    /// Codepoints that were already in the font and left untouched.
+    pub already_present: Vec<u32>,
+    /// Codepoints the face has no glyph for.
+    pub unresolved: Vec<u32>,
+}
 
-    let mut g_map = HashMap::new();
+impl Font {
+    /// Loads `symbols` from the font file `filename`, taking the characters it lacks from
+    /// `fallback_font`. The name combines both file names, e.g. `"Roboto.ttf + Noto.ttf"`;
+    /// the family name is the primary font's.
+    pub fn load_with_fallback_charset(filename: &str, symbols: &str, fallback_font: &str) -> Result<Font, FontLoadError> {
+        let chain = FaceChain::new(FaceSource::from_file(filename)?)
+            .with_fallback(FaceSource::from_file(fallback_font)?);
+        let mut font = load_font_chain(&chain, symbols)?;
+        let (primary, fallback) = (PathBuf::from(filename), PathBuf::from(fallback_font));
+        font.name = format!("{} + {}", file_font_name(&primary, 0), file_font_name(&fallback, 0));
+        if font.family_name.is_empty() {
+            font.family_name = file_stem(&primary);
+        }
+        Ok(font)
+    }
 
-    // for encoding in face.tables().cmap.unwrap().subtables {
-    //     if !encoding.is_unicode() { continue; }
-    //     encoding.codepoints(|cp| {
-    //         g_map.insert(cp, encoding.glyph_index(cp).unwrap());
-    //     });
-    // }
+    /// Tessellates the characters of `symbols` not yet in `glyph_map` from `face_data`, which
+    /// must be the face this font was loaded from. Existing glyphs and the font metrics are
+    /// left unchanged. `options` must have the em size and tolerance the font was loaded with,
+    /// or the new glyphs would not match the old ones.
+    pub fn add_symbols(&mut self, face_data: &[u8], symbols: &str, options: &FontOptions) -> Result<AddedReport, FontLoadError> {
+        let face_data = sfnt_data(face_data)?;
+        let face = parse_face(&face_data, options)?;
+        self.check_scale(&face, options)?;
+        let mut report = AddedReport::default();
+        let mut new_symbols = String::new();
+        for ch in symbols.chars() {
+            let cp = u32::from(ch);
+            if self.glyph_map.contains_key(&cp) || self.clusters.contains_key(&cp) {
+                if !report.already_present.contains(&cp) {
+                    report.already_present.push(cp);
+                }
+            } else if !new_symbols.contains(ch) {
+                new_symbols.push(ch);
+            }
+        }
 
+        let (g_map, unresolved, clusters) = resolve_symbols(&face, &new_symbols, options)?;
+        add_clusters(self, &face, clusters, options)?;
+        let loaded = loaded_glyph_ids(&face, self);
+        let kerning = load_kerning(&face, &g_map, &loaded, options);
+        self.kerning.extend(kerning);
+        self.mark_anchors.extend(load_mark_anchors(&face, &g_map, &loaded, options));
+        if options.ligatures {
+            let mut all = loaded;
+            all.extend(g_map.iter().map(|(&cp, &id)| (cp, id)));
+            self.ligatures = find_ligatures(&face, &all);
+            let ids: Vec<u16> = self.ligatures.iter().map(|l| l.glyph_id).collect();
+            self.insert_glyph_ids(&face, &ids, options)?;
+        }
+        let glyphs = load_glyphs(&face, g_map, options, &mut self.warnings)?;
+        report.added = glyphs.keys().copied().collect();
+        report.added.sort_unstable();
+        for cp in &unresolved {
+            if !self.unresolved.contains(cp) {
+                self.unresolved.push(*cp);
+            }
+        }
+        report.unresolved = unresolved;
+        self.glyph_map.extend(glyphs);
+        finish_font(self, &face_data, options);
+        Ok(report)
+    }
 
-    for ch in symbols.chars() {
-        g_map.insert(u32::from(ch), face.glyph_index(ch).unwrap());
+    /// Whether loading the same codepoints from `data` with `options` would give a font with
+    /// a different `source_hash`, e.g. because the font file was replaced by another version.
+    pub fn is_stale(&self, data: &[u8], options: &FontOptions) -> bool {
+        sfnt_data(data).map_or(true, |data| source_hash(&data, self, options) != self.source_hash)
     }
 
-    let mut glyphs = HashMap::new();
+    /// Tessellates the glyphs `ids` of `face_data` not yet in `glyph_id_map`, e.g. the output
+    /// of shaping. Returns the ids that were added; ids beyond the face's glyph count are
+    /// ignored. `options` must match the font as for [`Font::add_symbols`].
+    pub fn add_glyph_ids(&mut self, face_data: &[u8], ids: &[u16], options: &FontOptions) -> Result<Vec<u16>, FontLoadError> {
+        let face_data = sfnt_data(face_data)?;
+        let face = parse_face(&face_data, options)?;
+        self.check_scale(&face, options)?;
+        let added = self.insert_glyph_ids(&face, ids, options)?;
+        fit_advances(self, options);
+        Ok(added)
+    }
 
-    for (cp, id) in g_map {
-        if let Some(_) = face.glyph_raster_image(id, std::u16::MAX) {
-            panic!("Raster fonts not supported!")
-        } else if let Some(_) = face.glyph_svg_image(id) {
-            panic!("Raster fonts not supported!")
-        } else {
-            let mut mesh: VertexBuffers<GpuVertex, u32> = VertexBuffers::new();
-
-            let mut builder = Builder::new();
-            let ok = builder.build(&face, id).is_ok();
-
-            let mut bbox = (0.0,0.0,0.0,0.0);
-            if ok {
-                let mut transform = Transform::new_translate(0.0,
-                                                             -face.glyph_y_origin(id).unwrap_or(0) as f64);
-                transform.scale(scale, scale);
-                bbox = (builder.bbbox.x_min as f64, builder.bbbox.y_min as f64, builder.bbbox.x_max as f64,builder.bbbox.y_max as f64);
-                fill_tess
-                    .tessellate(
-                        builder,
-                        &FillOptions::tolerance(0.5),
-                        &mut BuffersBuilder::new(
-                            &mut mesh,
-                            VertexCtor {
-                                prim_id: id.0 as u32,
-                                transform,
-                            },
-                        ),
-                    )
-                    .expect("Error during tesselation!");
-                transform.apply_to(&mut bbox.0,&mut bbox.1);
-                transform.apply_to(&mut bbox.2,&mut bbox.3);
-            }
-
-            glyphs.insert(cp, Glyph {
-                advance: face.glyph_hor_advance(id).unwrap() as f32 * scale as f32,
-                outline: mesh,
-                bbox: (bbox.0 as f32, bbox.1 as f32, bbox.2 as f32, bbox.3 as f32),
-            });
+    /// Fails unless `options` scale and tessellate `face` like the options this font was
+    /// loaded with. Fonts saved without a tolerance only check the em size.
+    fn check_scale(&self, face: &ttf::Face, options: &FontOptions) -> Result<(), FontLoadError> {
+        let tolerance = Some(options.font_unit_tolerance(face) / face.units_per_em() as f32);
+        if options.em_size == self.em_size && (self.tolerance.is_none() || self.tolerance == tolerance) {
+            return Ok(());
         }
+        Err(FontLoadError::OptionsMismatch { loaded: (self.em_size, self.tolerance), requested: (options.em_size, tolerance) })
     }
 
+    /// [`Font::add_glyph_ids`] without fitting the advances, for callers that fit them after
+    /// adding more glyphs.
+    pub(crate) fn insert_glyph_ids(&mut self, face: &ttf::Face, ids: &[u16], options: &FontOptions) -> Result<Vec<u16>, FontLoadError> {
+        let mut new_ids: Vec<ttf::GlyphId> = ids.iter()
+            .filter(|&&id| id < face.number_of_glyphs() && !self.glyph_id_map.contains_key(&id))
+            .map(|&id| ttf::GlyphId(id))
+            .collect();
+        new_ids.sort_unstable();
+        new_ids.dedup();
+        let glyphs = load_glyphs_by_id(face, &new_ids, options, &mut self.warnings)?;
+        self.glyph_id_map.extend(glyphs);
+        Ok(new_ids.into_iter().map(|id| id.0).collect())
+    }
+}
 
-    let font = Font {
-        name: path_buf.file_name().unwrap().to_str().unwrap().into(),
-        ascender: face.ascender() as f32 * scale as f32,
-        descender: face.descender() as f32 * scale as f32,
-        line_gap: face.line_gap() as f32 * scale as f32,
-        glyph_map: glyphs,
-    };
+/// Reads names and metrics from the font headers only, which is much cheaper than
+/// [`load_font`] when listing many fonts.
+pub fn load_font_metrics_only(filename: &str) -> Result<FontMetrics, FontLoadError> {
+    load_font_metrics_only_with_options(filename, &FontOptions::default())
+}
 
-    Ok(font)
+/// Like [`load_font_metrics_only`], for the face and variation instance selected by `options`,
+/// with lengths scaled to `options.em_size`. The glyph options are ignored.
+pub fn load_font_metrics_only_with_options(filename: &str, options: &FontOptions) -> Result<FontMetrics, FontLoadError> {
+    let path_buf = PathBuf::from(filename);
+    let font_data = std::fs::read(&path_buf)?;
+    let font_data = sfnt_data(&font_data)?;
+    let face = parse_face(&font_data, options)?;
+    let scale = options.scale(&face) as f32;
+
+    Ok(FontMetrics {
+        name: face_name(&face, ttf::name_id::FAMILY).unwrap_or_else(|| file_stem(&path_buf)),
+        style_name: face_name(&face, ttf::name_id::SUBFAMILY).unwrap_or_default(),
+        ascender: face.ascender() as f32 * scale,
+        descender: face.descender() as f32 * scale,
+        line_gap: face.line_gap() as f32 * scale,
+        cap_height: face.capital_height().map(|v| v as f32 * scale),
+        x_height: face.x_height().map(|v| v as f32 * scale),
+        units_per_em: face.units_per_em(),
+    })
+}
+
+/// Name table entry `name_id`, preferring the English (US) record, then any other Unicode
+/// record, then a Mac Roman one as found in old Macintosh fonts.
+fn face_name(face: &ttf::Face, name_id: u16) -> Option<String> {
+    const ENGLISH_US: u16 = 0x0409;
+    let mut fallback = None;
+    let mut mac_fallback = None;
+    for name in face.names() {
+        if name.name_id != name_id {
+            continue;
+        }
+        if name.platform_id == ttf::PlatformId::Macintosh && name.encoding_id == 0 {
+            mac_fallback.get_or_insert_with(|| decode_mac_roman(name.name));
+        } else if let Some(value) = name.to_string() {
+            if name.language_id == ENGLISH_US {
+                return Some(value);
+            }
+            fallback.get_or_insert(value);
+        }
+    }
+    fallback.or(mac_fallback)
+}
+
+/// Decodes Mac OS Roman, whose lower half is ASCII.
+fn decode_mac_roman(bytes: &[u8]) -> String {
+    const HIGH_HALF: &str = "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø\
+                             ¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ";
+    bytes.iter().map(|&b| match b {
+        0..=0x7F => b as char,
+        _ => HIGH_HALF.chars().nth(b as usize - 0x80).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }).collect()
 }
 
 struct Builder {
@@ -127,6 +1861,7 @@ impl ttf::OutlineBuilder for Builder {
             self.vec.push(PathEvent::Begin { at: point(x, y) });
         } else {
             self.first = point(x, y);
+            self.prev = self.first;
             self.needs_end = true;
             self.vec.push(PathEvent::Begin { at: self.first });
         }
@@ -167,9 +1902,10 @@ impl ttf::OutlineBuilder for Builder {
 
     fn close(&mut self) {
         self.needs_end = false;
+        let last = self.prev;
         self.prev = self.first;
         self.vec.push(PathEvent::End {
-            last: self.prev,
+            last,
             first: self.first,
             close: true,
         });
@@ -187,14 +1923,10 @@ impl Builder {
         }
     }
 
-    fn build(&mut self, face: &ttf::Face,
-             glyph_id: ttf::GlyphId) -> Result<&Self, Box<dyn std::error::Error>> {
-        self.bbbox = match face.outline_glyph(glyph_id, self) {
-            Some(v) => v,
-            None => return Err(Box::new(Error::InvalidSize)),
-        };
-
-        Ok(self)
+    /// Reads the outline of `glyph_id`; `None` if the glyph has none or it could not be parsed.
+    fn build(&mut self, face: &ttf::Face, glyph_id: ttf::GlyphId) -> Option<&Self> {
+        self.bbbox = face.outline_glyph(glyph_id, self)?;
+        Some(self)
     }
 
 }
@@ -208,6 +1940,1151 @@ impl IntoIterator for Builder {
     }
 }
 
+fn lyon_transform(t: &Transform) -> lyon::math::Transform {
+    lyon::math::Transform::new(t.a as f32, t.b as f32, t.c as f32, t.d as f32, t.e as f32, t.f as f32)
+}
+
 fn point(x: f32, y: f32) -> Point {
     Point::new(x, y)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DEJAVU_SANS;
+
+    /// Plane 16 private use, which DejaVu Sans does not cover.
+    const UNCOVERED: char = '\u{10FFFD}';
+
+    fn with_missing(policy: MissingGlyph) -> FontOptions {
+        FontOptions { missing_glyph: policy, ..FontOptions::default() }
+    }
+
+    #[test]
+    fn missing_glyph_maps_to_notdef_and_is_reported() {
+        let font = load_font(DEJAVU_SANS, &format!("a{}", UNCOVERED)).unwrap();
+        assert_eq!(font.unresolved, vec![u32::from(UNCOVERED)]);
+        let missing = font.glyph(UNCOVERED).expect("mapped to .notdef");
+        assert_eq!(missing.advance, font.notdef.advance);
+        assert!(font.glyph('a').is_some());
+    }
+
+    #[test]
+    fn missing_glyph_can_be_skipped_or_fail_the_load() {
+        let symbols = format!("{}a", UNCOVERED);
+        let font = load_font_with_options(DEJAVU_SANS, &symbols, &with_missing(MissingGlyph::Skip)).unwrap();
+        assert!(font.glyph(UNCOVERED).is_none());
+        assert_eq!(font.unresolved, vec![u32::from(UNCOVERED)]);
+
+        match load_font_with_options(DEJAVU_SANS, &symbols, &with_missing(MissingGlyph::Error)) {
+            Err(FontLoadError::MissingGlyph { codepoint }) => assert_eq!(codepoint, u32::from(UNCOVERED)),
+            other => panic!("expected MissingGlyph, got {:?}", other.map(|f| f.name)),
+        }
+    }
+
+    #[test]
+    fn bytes_and_file_loads_agree() {
+        let symbols = "Hello, World!";
+        let from_file = load_font(DEJAVU_SANS, symbols).unwrap();
+        let from_bytes = load_font_from_bytes(&std::fs::read(DEJAVU_SANS).unwrap(), symbols).unwrap();
+        assert_eq!(from_file.glyph_map.len(), from_bytes.glyph_map.len());
+        for (cp, glyph) in &from_file.glyph_map {
+            let other = &from_bytes.glyph_map[cp];
+            assert_eq!(glyph.advance, other.advance);
+            assert_eq!(glyph.outline.indices.len(), other.outline.indices.len());
+        }
+        assert_eq!(from_file.ascender, from_bytes.ascender);
+    }
+
+    #[test]
+    fn load_font_all_covers_the_cmap() {
+        let data = std::fs::read(DEJAVU_SANS).unwrap();
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let ranges = vec![0x20..=0x7E, 0x391..=0x3A9];
+        let expected: BTreeSet<u32> = ranges.iter().cloned().flatten()
+            .filter(|&cp| char::from_u32(cp).and_then(|ch| face.glyph_index(ch)).is_some())
+            .collect();
+
+        let filter = CmapFilter { ranges: ranges.clone(), max_glyphs: None };
+        let options = FontOptions { tessellate: false, ..FontOptions::default() };
+        let font = load_font_all_with_filter(DEJAVU_SANS, &filter, &options).unwrap();
+        let loaded: BTreeSet<u32> = font.glyph_map.keys().copied().collect();
+        assert_eq!(loaded, expected);
+
+        let capped = CmapFilter { ranges, max_glyphs: Some(10) };
+        let font = load_font_all_with_filter(DEJAVU_SANS, &capped, &options).unwrap();
+        let loaded: BTreeSet<u32> = font.glyph_map.keys().copied().collect();
+        assert_eq!(loaded, expected.into_iter().take(10).collect());
+    }
+
+    #[test]
+    fn add_symbols_only_tessellates_new_codepoints() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let options = FontOptions::default();
+        let mut font = load_font_from_bytes_with_options(&data, "ab", &options).unwrap();
+        let (ascender, line_gap) = (font.ascender, font.line_gap);
+        let a = &font.glyph('a').unwrap().outline;
+        let (a_vertices, a_len) = (a.vertices.as_ptr(), a.vertices.len());
+
+        let report = font.add_symbols(&data, "ac", &options).unwrap();
+        assert_eq!(report.added, [u32::from('c')]);
+        assert_eq!(report.already_present, [u32::from('a')]);
+        let a = &font.glyph('a').unwrap().outline;
+        assert_eq!((a.vertices.as_ptr(), a.vertices.len()), (a_vertices, a_len));
+        assert_eq!((font.ascender, font.line_gap), (ascender, line_gap));
+        assert_eq!(crate::layout::layout_text(&font, "abc", (0.0, 0.0)).len(), 3);
+    }
+
+    /// Table with the given tag in the directory of the font starting at `font`.
+    fn table_record(data: &[u8], font: usize, tag: &[u8; 4]) -> usize {
+        let tables = u16::from_be_bytes([data[font + 4], data[font + 5]]) as usize;
+        (0..tables).map(|i| font + 12 + 16 * i)
+            .find(|&r| &data[r..r + 4] == tag)
+            .unwrap_or_else(|| panic!("no {:?} table", tag))
+    }
+
+    /// A TrueType collection of `fonts`, each a single-face sfnt.
+    fn collection(fonts: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = b"ttcf\0\x01\0\0".to_vec();
+        data.extend((fonts.len() as u32).to_be_bytes());
+        let mut offset = 12 + 4 * fonts.len();
+        for font in fonts {
+            data.extend((offset as u32).to_be_bytes());
+            offset += (font.len() + 3) & !3;
+        }
+        for font in fonts {
+            let base = data.len();
+            data.extend(font);
+            data.resize((data.len() + 3) & !3, 0);
+            let tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+            for i in 0..tables {
+                let at = base + 12 + 16 * i + 8;
+                let moved = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) + base as u32;
+                data[at..at + 4].copy_from_slice(&moved.to_be_bytes());
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn collections_load_the_selected_face() {
+        let regular = crate::fixtures::read("DejaVuSans.ttf");
+        // Halving units_per_em doubles every scaled metric.
+        let mut doubled = regular.clone();
+        let head = table_record(&doubled, 0, b"head");
+        let head = u32::from_be_bytes(doubled[head + 8..head + 12].try_into().unwrap()) as usize;
+        doubled[head + 18..head + 20].copy_from_slice(&1024u16.to_be_bytes());
+        let ttc = collection(&[regular, doubled]);
+
+        let load = |face_index| load_font_from_bytes_with_options(&ttc, "a", &FontOptions { face_index, ..FontOptions::default() });
+        let (first, second) = (load(0).unwrap(), load(1).unwrap());
+        let ratio = second.glyph('a').unwrap().advance / first.glyph('a').unwrap().advance;
+        assert!((ratio - 2.0).abs() < 1e-4, "advance ratio {}", ratio);
+        assert!(matches!(load(2), Err(FontLoadError::FaceIndexOutOfRange { index: 2, faces: 2 })));
+
+        let path = std::env::temp_dir().join(format!("svg-load-metrics-{}.ttc", std::process::id()));
+        std::fs::write(&path, &ttc).unwrap();
+        let metrics = |face_index| load_font_metrics_only_with_options(path.to_str().unwrap(), &FontOptions { face_index, ..FontOptions::default() });
+        let (first, second, third) = (metrics(0), metrics(1), metrics(2));
+        std::fs::remove_file(&path).ok();
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!((first.units_per_em, second.units_per_em), (2048, 1024));
+        assert!((second.ascender - 2.0 * first.ascender).abs() < 1e-5);
+        assert!(matches!(third, Err(FontLoadError::FaceIndexOutOfRange { index: 2, faces: 2 })));
+    }
+
+    /// DejaVu Sans with a `wght` axis from 100 to 900, default 400, whose HVAR widens every
+    /// glyph by `widen` font units at the heaviest weight. Outlines do not vary.
+    fn variable_dejavu(widen: i16) -> Vec<u8> {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let maxp = table_record(&data, 0, b"maxp");
+        let maxp = u32::from_be_bytes(data[maxp + 8..maxp + 12].try_into().unwrap()) as usize;
+        let glyphs = u16::from_be_bytes([data[maxp + 4], data[maxp + 5]]);
+
+        let fixed = |v: i32| (v << 16).to_be_bytes();
+        let mut fvar = vec![0, 1, 0, 0, 0, 16, 0, 2, 0, 1, 0, 20, 0, 0, 0, 8];
+        fvar.extend(b"wght");
+        fvar.extend(fixed(100));
+        fvar.extend(fixed(400));
+        fvar.extend(fixed(900));
+        fvar.extend([0, 0, 1, 0]);
+
+        // Header, then an item variation store with one region peaking at the axis maximum
+        // and one delta set per glyph.
+        let mut hvar = vec![0, 1, 0, 0, 0, 0, 0, 20];
+        hvar.extend([0; 12]);
+        hvar.extend([0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 22]);
+        hvar.extend([0, 1, 0, 1, 0, 0, 0x40, 0, 0x40, 0]);
+        hvar.extend(glyphs.to_be_bytes());
+        hvar.extend([0, 1, 0, 1, 0, 0]);
+        for _ in 0..glyphs {
+            hvar.extend(widen.to_be_bytes());
+        }
+        crate::fixtures::with_tables(&data, &[(b"fvar", &fvar), (b"HVAR", &hvar)])
+    }
+
+    #[test]
+    fn variations_apply_to_advances() {
+        let data = variable_dejavu(200);
+        let axes = list_variation_axes(&data);
+        assert_eq!(axes.len(), 1);
+        assert_eq!((axes[0].tag.as_str(), axes[0].min, axes[0].default, axes[0].max), ("wght", 100.0, 400.0, 900.0));
+
+        let load = |weight| load_font_from_bytes_with_options(&data, "a", &FontOptions::default().variation("wght", weight)).unwrap();
+        let (regular, black) = (load(400.0), load(900.0));
+        let widened = 200.0 * regular.em_size / 2048.0;
+        let difference = black.glyph('a').unwrap().advance - regular.glyph('a').unwrap().advance;
+        assert!((difference - widened).abs() < 0.01, "advance grew by {}", difference);
+    }
+
+    #[test]
+    fn unknown_axes_list_the_available_ones() {
+        let data = variable_dejavu(0);
+        match load_font_from_bytes_with_options(&data, "a", &FontOptions::default().variation("wdth", 75.0)) {
+            Err(FontLoadError::UnknownAxis { axis, available }) => {
+                assert_eq!(axis, "wdth");
+                assert_eq!(available.iter().map(|a| a.tag.as_str()).collect::<Vec<_>>(), ["wght"]);
+            }
+            other => panic!("expected UnknownAxis, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// DejaVu Sans with a cmap that only maps `chars`, scaled by `scale`.
+    fn partial_dejavu(chars: &[char], scale: u16) -> Vec<u8> {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let segments = chars.len() + 1;
+        let mut ends: Vec<u16> = chars.iter().map(|&ch| u32::from(ch) as u16).collect();
+        ends.push(0xFFFF);
+        let deltas: Vec<u16> = chars.iter()
+            .map(|&ch| face.glyph_index(ch).unwrap().0.wrapping_sub(u32::from(ch) as u16))
+            .chain([1])
+            .collect();
+
+        let mut subtable = vec![0, 4];
+        subtable.extend(((16 + 8 * segments) as u16).to_be_bytes());
+        subtable.extend([0, 0]);
+        subtable.extend(((2 * segments) as u16).to_be_bytes());
+        subtable.extend([0; 6]);
+        ends.iter().for_each(|e| subtable.extend(e.to_be_bytes()));
+        subtable.extend([0, 0]);
+        ends.iter().for_each(|s| subtable.extend(s.to_be_bytes()));
+        deltas.iter().for_each(|d| subtable.extend(d.to_be_bytes()));
+        subtable.extend(vec![0; 2 * segments]);
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+        cmap.extend(subtable);
+
+        let head = table_record(&data, 0, b"head");
+        let head = u32::from_be_bytes(data[head + 8..head + 12].try_into().unwrap()) as usize;
+        let mut head = data[head..head + 54].to_vec();
+        head[18..20].copy_from_slice(&(2048 / scale).to_be_bytes());
+        crate::fixtures::with_tables(&data, &[(b"cmap", &cmap), (b"head", &head)])
+    }
+
+    #[test]
+    fn chains_cover_the_union_of_their_faces() {
+        let chain = FaceChain::new(FaceSource::from_bytes(partial_dejavu(&['a', 'b'], 1)))
+            .with_fallback(FaceSource::from_bytes(partial_dejavu(&['b', 'c'], 2)));
+        let font = load_font_chain(&chain, "abcd").unwrap();
+        let faces: Vec<usize> = "abc".chars().map(|ch| font.glyph(ch).unwrap().face).collect();
+        assert_eq!(faces, [0, 0, 1]);
+        assert_eq!(font.unresolved, [u32::from('d')]);
+
+        let alone = load_font_from_bytes(&partial_dejavu(&['c'], 1), "c").unwrap();
+        let ratio = font.glyph('c').unwrap().advance / alone.glyph('c').unwrap().advance;
+        assert!((ratio - 2.0).abs() < 1e-4, "advance ratio {}", ratio);
+        assert_eq!(font.ascender, alone.ascender);
+    }
+
+    #[test]
+    fn kerning_comes_from_the_pair_tables() {
+        let font = load_font(crate::fixtures::DEJAVU_SANS, "AVTo.").unwrap();
+        assert!(font.kerning('A', 'V') < 0.0);
+        assert_eq!(font.kerning('o', 'o'), 0.0);
+
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let tables = KerningTables::new(&face);
+        let scale = FontOptions::default().scale(&face) as f32;
+        for left in "AVTo.".chars() {
+            for right in "AVTo.".chars() {
+                let ids = (face.glyph_index(left).unwrap(), face.glyph_index(right).unwrap());
+                let expected = tables.get(ids.0, ids.1) as f32 * scale;
+                assert_eq!(font.kerning(left, right), expected, "{}{}", left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn kerning_and_anchors_of_added_symbols_match_a_full_load() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let full = load_font_from_bytes(&data, "AVae\u{301}").unwrap();
+        let mut font = load_font_from_bytes(&data, "Aa").unwrap();
+        font.add_symbols(&data, "Ve\u{301}", &FontOptions::default()).unwrap();
+        assert_eq!(font.kerning, full.kerning);
+        assert_eq!(font.mark_anchors, full.mark_anchors);
+        assert!(full.mark_offset('e', '\u{301}').is_some());
+    }
+
+    #[test]
+    fn fallback_charset_fills_in_missing_characters() {
+        let dir = std::env::temp_dir().join(format!("svg-load-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (primary, fallback) = (dir.join("Primary.ttf"), dir.join("Fallback.ttf"));
+        std::fs::write(&primary, partial_dejavu(&['a'], 1)).unwrap();
+        std::fs::write(&fallback, partial_dejavu(&['a', 'b'], 2)).unwrap();
+        let (primary, fallback) = (primary.to_str().unwrap(), fallback.to_str().unwrap());
+
+        let font = Font::load_with_fallback_charset(primary, "ab", fallback).unwrap();
+        let alone = load_font(fallback, "b").unwrap();
+        assert_eq!(font.name, "Primary.ttf + Fallback.ttf");
+        assert_eq!(font.family_name(), "DejaVu Sans");
+        assert_eq!(font.glyph('a').unwrap().face, 0);
+        let (b, expected) = (font.glyph('b').unwrap(), alone.glyph('b').unwrap());
+        assert_eq!(b.face, 1);
+        assert_eq!(b.bbox, expected.bbox);
+        assert_eq!(b.outline.indices, expected.outline.indices);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// DejaVu Sans with an OpenType `SVG ` table holding one document per `(char, svg)`.
+    fn svg_dejavu(documents: &[(char, &str)]) -> Vec<u8> {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let mut entries: Vec<(u16, &str)> = documents.iter().map(|&(ch, doc)| (face.glyph_index(ch).unwrap().0, doc)).collect();
+        entries.sort_unstable();
+
+        let mut table = vec![0, 0, 0, 0, 0, 10, 0, 0, 0, 0];
+        let mut list = (entries.len() as u16).to_be_bytes().to_vec();
+        let mut docs = Vec::new();
+        for (id, doc) in &entries {
+            let doc = doc.replace("{id}", &id.to_string());
+            let offset = 2 + 12 * entries.len() + docs.len();
+            list.extend(id.to_be_bytes());
+            list.extend(id.to_be_bytes());
+            list.extend((offset as u32).to_be_bytes());
+            list.extend((doc.len() as u32).to_be_bytes());
+            docs.extend(doc.bytes());
+        }
+        table.extend(list);
+        table.extend(docs);
+        crate::fixtures::with_tables(&data, &[(b"SVG ", &table)])
+    }
+
+    #[test]
+    fn svg_table_glyphs_are_loaded_as_colored_layers() {
+        let square = r##"<svg xmlns="http://www.w3.org/2000/svg"><g id="glyph{id}">
+            <rect x="0" y="-2048" width="2048" height="2048" fill="#ff0000"/></g></svg>"##;
+        let data = svg_dejavu(&[('a', square), ('b', "<svg")]);
+        let font = load_font_from_bytes(&data, "ab").unwrap();
+
+        let a = font.glyph('a').unwrap();
+        assert_eq!(a.svg_layers.len(), 1);
+        assert_eq!(a.svg_layers[0].bgcolor, [1.0, 0.0, 0.0, 1.0]);
+        let em = font.em_size;
+        for v in &a.svg_layers[0].vertices.vertices {
+            assert!((-1e-3..=em + 1e-3).contains(&v.position[0]) && (-1e-3..=em + 1e-3).contains(&v.position[1]), "{:?}", v.position);
+        }
+        assert!(a.svg_layers[0].vertices.vertices.iter().any(|v| v.position[1] > em - 1e-3));
+
+        let b = font.glyph('b').unwrap();
+        assert!(b.svg_layers.is_empty());
+        assert!(!b.outline.indices.is_empty());
+    }
+
+    /// Start of a PNG file up to its header chunk, enough for the image size to be read.
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(width.to_be_bytes());
+        png.extend(height.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        png
+    }
+
+    /// DejaVu Sans with an `sbix` table whose strike at each of `strikes` pixels per em has a
+    /// square PNG of that size for each of `chars`.
+    fn sbix_dejavu(chars: &[char], strikes: &[u16]) -> Vec<u8> {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let ids: Vec<u16> = chars.iter().map(|&ch| face.glyph_index(ch).unwrap().0).collect();
+        let glyphs = face.number_of_glyphs() as usize;
+
+        let mut table = vec![0, 1, 0, 1];
+        table.extend((strikes.len() as u32).to_be_bytes());
+        let strike_len = 4 + 4 * (glyphs + 1) + ids.len() * (8 + png_header(0, 0).len());
+        for i in 0..strikes.len() {
+            table.extend(((8 + 4 * strikes.len() + i * strike_len) as u32).to_be_bytes());
+        }
+        for &ppem in strikes {
+            let mut offsets = Vec::new();
+            let mut images = Vec::new();
+            for id in 0..=glyphs {
+                offsets.extend(((4 + 4 * (glyphs + 1) + images.len()) as u32).to_be_bytes());
+                if ids.contains(&(id as u16)) {
+                    images.extend([0, 0, 0, 0]);
+                    images.extend(b"png ");
+                    images.extend(png_header(ppem as u32, ppem as u32));
+                }
+            }
+            table.extend(ppem.to_be_bytes());
+            table.extend(72u16.to_be_bytes());
+            table.extend(offsets);
+            table.extend(images);
+        }
+        crate::fixtures::with_tables(&data, &[(b"sbix", &table)])
+    }
+
+    #[test]
+    fn bitmaps_are_only_loaded_for_glyphs_without_outlines() {
+        let data = sbix_dejavu(&[' ', 'a'], &[20, 64]);
+        let options = FontOptions { bitmap_pixels_per_em: 60, ..FontOptions::default() };
+        let font = load_font_from_bytes_with_options(&data, " a", &options).unwrap();
+
+        let a = font.glyph('a').unwrap();
+        assert!(a.image.is_none());
+        assert!(!a.outline.indices.is_empty());
+
+        let image = font.glyph(' ').unwrap().image.as_ref().unwrap();
+        assert_eq!((image.width, image.height, image.pixels_per_em), (64, 64, 64));
+        assert!(image.png.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn tabular_figures_give_every_glyph_the_widest_advance() {
+        let options = FontOptions { tabular_figures: true, ..FontOptions::default() };
+        let font = load_font_with_options(crate::fixtures::DEJAVU_SANS, "il1W", &options).unwrap();
+        let proportional = load_font(crate::fixtures::DEJAVU_SANS, "W").unwrap();
+        let widest = proportional.glyph('W').unwrap().advance;
+        for ch in "il1W".chars() {
+            assert_eq!(font.glyph(ch).unwrap().advance, widest, "{}", ch);
+        }
+        assert_eq!(font.notdef.advance, widest);
+        assert_eq!(font.glyph('i').unwrap().bbox, load_font(crate::fixtures::DEJAVU_SANS, "i").unwrap().glyph('i').unwrap().bbox);
+    }
+
+    #[test]
+    fn tighter_tolerances_give_more_vertices() {
+        let load = |tolerance| {
+            let options = FontOptions { tolerance: Some(tolerance), ..FontOptions::default() };
+            let font = load_font_with_options(crate::fixtures::DEJAVU_SANS, "o", &options).unwrap();
+            font.glyph('o').unwrap().outline.vertices.len()
+        };
+        assert!(load(0.001) > load(0.02));
+    }
+
+    #[test]
+    fn metrics_scale_linearly_with_the_em_size() {
+        let load = |em_size| load_font_with_options(crate::fixtures::DEJAVU_SANS, "o", &FontOptions { em_size, ..FontOptions::default() }).unwrap();
+        let (small, large) = (load(1.0), load(32.0));
+        let close = |a: f32, b: f32| (a - b).abs() <= 1e-4 * b.abs().max(1.0);
+        let (o, big_o) = (small.glyph('o').unwrap(), large.glyph('o').unwrap());
+        assert!(close(32.0 * o.advance, big_o.advance));
+        assert!(close(32.0 * o.bbox.2, big_o.bbox.2) && close(32.0 * o.bbox.3, big_o.bbox.3));
+        assert!(close(32.0 * small.ascender, large.ascender) && close(32.0 * small.descender, large.descender));
+        let top = |g: &Glyph| g.outline.vertices.iter().map(|v| v.position[1]).fold(f32::MIN, f32::max);
+        assert!(close(32.0 * top(o), top(big_o)));
+    }
+
+    #[test]
+    fn glyphs_sit_on_the_baseline() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let font = load_font_from_bytes(&data, "xp").unwrap();
+        // The 'x' of DejaVu Sans 2.37 is 1120 of 2048 units tall.
+        let x_height = 1120.0 / 2048.0 * font.em_size;
+        assert!((font.x_height - x_height).abs() < 1e-4);
+
+        let x = font.glyph('x').unwrap();
+        let ys = || x.outline.vertices.iter().map(|v| v.position[1]);
+        let (bottom, top) = (ys().fold(f32::MAX, f32::min), ys().fold(f32::MIN, f32::max));
+        assert!(bottom.abs() < 0.01, "bottom of x at {}", bottom);
+        assert!((top - x_height).abs() < 0.01, "top of x at {}", top);
+        assert!(font.glyph('p').unwrap().bbox.1 < -0.1);
+    }
+    #[test]
+    fn empty_glyphs_differ_from_broken_ones() {
+        let font = load_font(crate::fixtures::DEJAVU_SANS, " a").unwrap();
+        let space = font.glyph(' ').unwrap();
+        assert!(space.is_empty);
+        assert!(space.outline.indices.is_empty());
+        assert!((space.advance - 651.0 / 2048.0 * font.em_size).abs() < 1e-4);
+        assert!(!font.glyph('a').unwrap().is_empty);
+        assert!(font.warnings.is_empty());
+
+        let font = load_font_from_bytes(&crate::fixtures::corrupted_dejavu('a'), " a").unwrap();
+        assert!(!font.glyph('a').unwrap().is_empty);
+        assert_eq!(font.warnings, [FontWarning::InvalidOutline { codepoint: u32::from('a') }]);
+    }
+
+    #[test]
+    fn broken_outlines_fail_the_load_under_the_error_policy() {
+        let data = crate::fixtures::corrupted_dejavu('a');
+        let id = ttf::Face::from_slice(&data, 0).unwrap().glyph_index('a').unwrap().0;
+        let error = load_font_from_bytes_with_options(&data, " a", &with_missing(MissingGlyph::Error))
+            .expect_err("a broken outline must fail the load");
+        assert!(matches!(error, FontLoadError::OutlineFailed { glyph } if glyph == id));
+        assert_eq!(error.to_string(), format!("cannot read the outline of glyph {}", id));
+
+        // Glyphs added by id follow the same policy.
+        let mut font = load_font_from_bytes_with_options(&data, " ", &with_missing(MissingGlyph::Error)).unwrap();
+        let added = font.add_glyph_ids(&data, &[id], &with_missing(MissingGlyph::Error));
+        assert!(matches!(added, Err(FontLoadError::OutlineFailed { glyph }) if glyph == id));
+        font.add_glyph_ids(&data, &[id], &with_missing(MissingGlyph::Skip)).unwrap();
+        assert!(font.glyph_id_map[&id].outline.indices.is_empty());
+        assert_eq!(font.warnings, [FontWarning::InvalidGlyphOutline { glyph_id: id }]);
+
+        let font = load_font_from_bytes_with_options(&data, " a", &with_missing(MissingGlyph::Skip)).unwrap();
+        assert!(font.glyph('a').unwrap().outline.indices.is_empty());
+        assert_eq!(font.warnings, [FontWarning::InvalidOutline { codepoint: u32::from('a') }]);
+    }
+
+    #[test]
+    fn decoration_metrics_match_the_font_tables() {
+        let font = load_font(crate::fixtures::DEJAVU_SANS, "a").unwrap();
+        // DejaVu Sans 2.37 in units of its 2048 unit em. Its OS/2 table predates the x and
+        // cap heights, which come from the 'x' and 'H' glyphs.
+        let em = |units: f32| units / 2048.0 * font.em_size;
+        let expected = [
+            (font.ascender, 1901.0), (font.descender, -483.0), (font.line_gap, 0.0),
+            (font.x_height, 1120.0), (font.cap_height, 1493.0),
+            (font.underline_position, -40.0), (font.underline_thickness, 90.0),
+            (font.strikeout_position, 530.0), (font.strikeout_thickness, 102.0),
+        ];
+        for (i, (value, units)) in expected.into_iter().enumerate() {
+            assert!((value - em(units)).abs() < 1e-4, "metric {}: {} != {}", i, value, em(units));
+        }
+    }
+
+    #[test]
+    fn missing_underline_metrics_fall_back_to_em_fractions() {
+        let data = crate::fixtures::with_tables(&crate::fixtures::read("DejaVuSans.ttf"), &[(b"post", &[0, 3, 0, 0])]);
+        let font = load_font_from_bytes(&data, "a").unwrap();
+        assert!((font.underline_position + font.em_size / 10.0).abs() < 1e-3);
+        assert!((font.underline_thickness - font.em_size / 20.0).abs() < 1e-3);
+    }
+
+    /// DejaVu Sans with vhea and vmtx tables: a vertical ascender and descender of half an em
+    /// and every glyph advancing one em with a top side bearing of `tsb` units.
+    fn vertical_dejavu(tsb: i16) -> Vec<u8> {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let glyphs = ttf::Face::from_slice(&data, 0).unwrap().number_of_glyphs();
+        let mut vhea = vec![0, 1, 0x10, 0];
+        for value in [1024i16, -1024, 0, 2048, 0, 0, 2048, 0, 1, 0, 0, 0, 0, 0, 0] {
+            vhea.extend(value.to_be_bytes());
+        }
+        vhea.extend(glyphs.to_be_bytes());
+        let mut vmtx = Vec::new();
+        for _ in 0..glyphs {
+            vmtx.extend(2048u16.to_be_bytes());
+            vmtx.extend(tsb.to_be_bytes());
+        }
+        crate::fixtures::with_tables(&data, &[(b"vhea", &vhea), (b"vmtx", &vmtx)])
+    }
+
+    #[test]
+    fn vertical_metrics_are_read_when_present() {
+        let data = vertical_dejavu(100);
+        let font = load_font_from_bytes(&data, "a").unwrap();
+        assert_eq!((font.vertical_ascender, font.vertical_descender, font.vertical_line_gap), (Some(0.5), Some(-0.5), Some(0.0)));
+        let a = font.glyph('a').unwrap();
+        assert_eq!(a.v_advance, Some(1.0));
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let top = face.glyph_bounding_box(face.glyph_index('a').unwrap()).unwrap().y_max;
+        assert_eq!(a.v_origin_y, Some((top + 100) as f32 / 2048.0));
+
+        let plain = load_font(crate::fixtures::DEJAVU_SANS, "a").unwrap();
+        assert_eq!((plain.vertical_ascender, plain.vertical_descender, plain.vertical_line_gap), (None, None, None));
+        let a = plain.glyph('a').unwrap();
+        assert_eq!((a.v_advance, a.v_origin_y), (None, None));
+    }
+
+    #[test]
+    fn synthetic_italic_shears_outlines_but_not_advances() {
+        let upright = load_font(crate::fixtures::DEJAVU_SANS, "l").unwrap();
+        let options = FontOptions::default().synthetic_italic(12.0);
+        let italic = load_font_with_options(crate::fixtures::DEJAVU_SANS, "l", &options).unwrap();
+        let (l, slanted) = (upright.glyph('l').unwrap(), italic.glyph('l').unwrap());
+        assert_eq!(slanted.advance, l.advance);
+
+        // The right edge of the stem, at the bottom and at the top of the glyph.
+        let right_edge_at = |g: &Glyph, y: f32| g.outline.vertices.iter()
+            .filter(|v| (v.position[1] - y).abs() < 1e-3)
+            .map(|v| v.position[0])
+            .fold(f32::MIN, f32::max);
+        let shift = 12f32.to_radians().tan() * l.bbox.3;
+        let top_shift = right_edge_at(slanted, l.bbox.3) - right_edge_at(l, l.bbox.3);
+        assert!((top_shift - shift).abs() < 1e-3, "top moved by {}, expected {}", top_shift, shift);
+        assert!((right_edge_at(slanted, 0.0) - right_edge_at(l, 0.0)).abs() < 1e-3);
+        assert!((slanted.bbox.2 - (l.bbox.2 + shift)).abs() < 1e-3 && slanted.bbox.0 == l.bbox.0);
+
+        let unslanted = load_font_with_options(crate::fixtures::DEJAVU_SANS, "l", &FontOptions::default().synthetic_italic(0.0)).unwrap();
+        assert_eq!(unslanted.glyph('l').unwrap().outline.vertices, l.outline.vertices);
+    }
+
+    #[test]
+    fn synthetic_bold_grows_glyphs_by_the_strength_in_em() {
+        let load = |strength| {
+            let options = FontOptions { em_size: 32.0, ..FontOptions::default() }.synthetic_bold(strength);
+            load_font_with_options(crate::fixtures::DEJAVU_SANS, "o", &options).unwrap()
+        };
+        let (regular, bold) = (load(0.0), load(0.05));
+        let (o, thick) = (regular.glyph('o').unwrap(), bold.glyph('o').unwrap());
+        let width = 0.05 * 32.0;
+        assert!((thick.advance - (o.advance + width)).abs() < 1e-4);
+        assert!(thick.outline.indices.len() > o.outline.indices.len());
+        let grown = [o.bbox.0 - thick.bbox.0, o.bbox.1 - thick.bbox.1, thick.bbox.2 - o.bbox.2, thick.bbox.3 - o.bbox.3];
+        assert!(grown.iter().all(|g| (g - width / 2.0).abs() < 1e-3), "bbox grew by {:?}", grown);
+
+        let plain = load_font_with_options(crate::fixtures::DEJAVU_SANS, "o", &FontOptions { em_size: 32.0, ..FontOptions::default() }).unwrap();
+        assert_eq!(o.outline.vertices, plain.glyph('o').unwrap().outline.vertices);
+        assert_eq!(o.advance, plain.glyph('o').unwrap().advance);
+    }
+
+    #[test]
+    fn outline_events_tessellate_like_the_glyph_mesh() {
+        let outlines = load_font_outlines(crate::fixtures::DEJAVU_SANS, "g").unwrap();
+        let meshes = load_font(crate::fixtures::DEJAVU_SANS, "g").unwrap();
+        let events = outlines.glyph('g').unwrap().path_events.as_ref().unwrap();
+        assert!(outlines.glyph('g').unwrap().outline.indices.is_empty());
+        assert!(events.iter().any(|e| matches!(e, PathEvent::Quadratic { .. })));
+        assert!(!events.iter().any(|e| matches!(e, PathEvent::Cubic { .. })));
+
+        let mut mesh: VertexBuffers<lyon::math::Point, u32> = VertexBuffers::new();
+        FillTessellator::new()
+            .tessellate(events.iter().copied(), &FillOptions::tolerance(0.5 / 2048.0),
+                        &mut BuffersBuilder::new(&mut mesh, |v: lyon::tessellation::FillVertex| v.position()))
+            .unwrap();
+        let expected = &meshes.glyph('g').unwrap().outline;
+        assert_eq!(mesh.indices, expected.indices);
+        for (p, v) in mesh.vertices.iter().zip(&expected.vertices) {
+            assert!((p.x - v.position[0]).abs() < 1e-5 && (p.y - v.position[1]).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn stroked_glyphs_extend_half_the_width_past_the_fill() {
+        let width = 0.04;
+        let options = FontOptions { em_size: 16.0, ..FontOptions::default() }.stroked(GlyphStroke::new(width));
+        let font = load_font_with_options(crate::fixtures::DEJAVU_SANS, "O ", &options).unwrap();
+        let o = font.glyph('O').unwrap();
+        let stroke = o.stroke_outline.as_ref().unwrap();
+        assert!(!stroke.indices.is_empty());
+        let (fill, outline) = (o.bbox, o.stroke_bbox.unwrap());
+        let half = width * font.em_size / 2.0;
+        let grown = [fill.0 - outline.0, fill.1 - outline.1, outline.2 - fill.2, outline.3 - fill.3];
+        assert!(grown.iter().all(|g| (g - half).abs() < 0.02 * half), "bbox grew by {:?}, expected {}", grown, half);
+        assert!(font.glyph(' ').unwrap().stroke_outline.as_ref().is_none_or(|m| m.indices.is_empty()));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_glyph_loading_matches_sequential() {
+        let data = crate::fixtures::corrupted_dejavu('k');
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let ids: Vec<_> = (0x20..0x250).filter_map(char::from_u32)
+            .filter_map(|ch| face.glyph_index(ch).map(|id| (u32::from(ch), id)))
+            .collect();
+        assert!(ids.len() > 400);
+        let options = FontOptions { retain_path_events: true, ..FontOptions::default() }.stroked(GlyphStroke::new(0.02));
+
+        let summary = |list: Vec<(u32, Glyph, bool, bool)>| list.into_iter()
+            .map(|(cp, glyph, advance, valid)| (cp, serde_json::to_string(&glyph).unwrap(), advance, valid))
+            .collect::<Vec<_>>();
+        let parallel = summary(load_glyph_list(&face, ids.clone(), &options));
+        let sequential = summary(load_glyph_list_sequential(&face, ids, &options));
+        assert!(parallel == sequential);
+        assert!(parallel.iter().any(|&(cp, _, _, valid)| cp == u32::from('k') && !valid));
+    }
+
+    #[test]
+    fn warnings_come_in_request_order_whatever_the_thread_count() {
+        let path = std::env::temp_dir().join(format!("svg-load-warnings-{}.ttf", std::process::id()));
+        std::fs::write(&path, crate::fixtures::corrupted_dejavu('q')).unwrap();
+        let symbols: String = ('a'..='z').rev().collect();
+        let fonts: Vec<_> = (0..3).map(|_| load_font(path.to_str().unwrap(), &symbols).unwrap()).collect();
+        std::fs::remove_file(&path).ok();
+        assert!(fonts[0].warnings.contains(&FontWarning::InvalidOutline { codepoint: u32::from('q') }));
+        for font in &fonts[1..] {
+            assert_eq!(font.warnings, fonts[0].warnings);
+            assert_eq!(serde_json::to_string(&font.glyph('m')).unwrap(), serde_json::to_string(&fonts[0].glyph('m')).unwrap());
+        }
+    }
+
+    #[test]
+    fn larger_pixel_sizes_get_finer_meshes() {
+        let load = |options: FontOptions| load_font_with_options(crate::fixtures::DEJAVU_SANS, "o", &options).unwrap();
+        let counts: Vec<_> = [12.0, 48.0, 96.0, 400.0].iter()
+            .map(|&px| load(FontOptions::default().for_pixel_size(px)).glyph('o').unwrap().outline.vertices.len())
+            .collect();
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]), "{:?}", counts);
+        assert!(counts[3] > counts[0], "{:?}", counts);
+
+        let font = load(FontOptions::default().for_pixel_size(48.0));
+        assert_eq!(font.tolerance, Some(MAX_PIXEL_ERROR / 48.0));
+        // A range is tessellated for its largest size, whichever order it is given in.
+        let range = load(FontOptions::default().for_pixel_sizes(96.0, 12.0));
+        assert_eq!(range.tolerance, Some(MAX_PIXEL_ERROR / 96.0));
+        assert_eq!(range.glyph('o').unwrap().outline.vertices.len(), counts[2]);
+    }
+
+    #[test]
+    fn unicode_ranges_parse_every_css_form() {
+        assert_eq!(parse_unicode_range("U+26").unwrap(), vec![0x26..=0x26]);
+        assert_eq!(parse_unicode_range("u+0020-007e").unwrap(), vec![0x20..=0x7E]);
+        assert_eq!(parse_unicode_range("U+4??").unwrap(), vec![0x400..=0x4FF]);
+        assert_eq!(parse_unicode_range("U+?????").unwrap(), vec![0..=0xFFFFF]);
+        assert_eq!(parse_unicode_range(" U+0-7F ,U+2013-2014,U+A0").unwrap(), vec![0..=0x7F, 0x2013..=0x2014, 0xA0..=0xA0]);
+    }
+
+    #[test]
+    fn unicode_range_errors_point_at_the_bad_entry() {
+        let error = |ranges| parse_unicode_range(ranges).unwrap_err();
+        let e = error("U+20, 0041, U+42");
+        assert_eq!((e.token.as_str(), e.position), ("0041", 6));
+        let e = error("U+20,U+7E-20");
+        assert_eq!((e.token.as_str(), e.position, e.reason), ("U+7E-20", 5, "range start is after its end"));
+        assert_eq!(error("U+12G").token, "U+12G");
+        assert_eq!(error("U+?1?").reason, "wildcards must trail at most 6 digits");
+        assert_eq!(error("U+110000").reason, "codepoint beyond U+10FFFF");
+        assert_eq!(error("U+1234567").reason, "expected 1 to 6 hex digits");
+        assert!(matches!(load_font_ranges(DEJAVU_SANS, "U+20,,U+21"), Err(FontLoadError::InvalidRange(e)) if e.position == 5));
+    }
+
+    #[test]
+    fn uncovered_range_codepoints_are_skipped_and_reported() {
+        let font = load_font_ranges(DEJAVU_SANS, "U+41-43, U+10FFFC-10FFFD").unwrap();
+        for ch in ['A', 'B', 'C'] {
+            assert!(font.glyph(ch).is_some(), "{}", ch);
+        }
+        assert_eq!(font.unresolved, vec![0x10FFFC, 0x10FFFD]);
+        assert!(!font.glyph_map.contains_key(&0x10FFFC));
+    }
+
+    #[test]
+    fn precomposed_characters_the_font_lacks_become_clusters() {
+        let data = partial_dejavu(&['e', '\u{301}'], 1);
+        let font = load_font_from_bytes(&data, "eé").unwrap();
+        assert!(font.glyph('é').is_none());
+        assert!(font.unresolved.is_empty());
+
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let cluster = font.cluster('é').unwrap();
+        let ids: Vec<u16> = cluster.parts.iter().map(|p| p.glyph_id).collect();
+        assert_eq!(ids, [face.glyph_index('e').unwrap().0, face.glyph_index('\u{301}').unwrap().0]);
+        assert_eq!(cluster.single(), None);
+        assert_eq!(cluster.advance, font.glyph('e').unwrap().advance);
+
+        let parts = font.glyph_parts('é');
+        assert_eq!(parts.len(), 2);
+        let ((e, e_offset), (acute, acute_offset)) = (parts[0], parts[1]);
+        assert_eq!(e_offset, (0.0, 0.0));
+        assert!(acute.bbox.1 + acute_offset.1 > e.bbox.3 * 0.9, "accent not above the e");
+        let plain = font.glyph_parts('e');
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].1, (0.0, 0.0));
+
+        let json = serde_json::to_string(&font).unwrap();
+        let restored: Font = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.cluster('é').unwrap().parts, cluster.parts);
+        assert_eq!(restored.glyph_parts('é').len(), 2);
+    }
+
+    /// DejaVu Sans with an empty glyph 0.
+    fn blank_notdef_dejavu() -> Vec<u8> {
+        let mut data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let width = match face.tables().head.index_to_location_format {
+            ttf::head::IndexToLocationFormat::Long => 4,
+            ttf::head::IndexToLocationFormat::Short => 2,
+        };
+        let record = table_record(&data, 0, b"loca");
+        let loca = u32::from_be_bytes(data[record + 8..record + 12].try_into().unwrap()) as usize;
+        // Start glyph 0 where glyph 1 starts.
+        data.copy_within(loca + width..loca + 2 * width, loca);
+        data
+    }
+
+    #[test]
+    fn notdef_is_the_fonts_own_box_when_it_draws_one() {
+        let font = load_font(DEJAVU_SANS, "a").unwrap();
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let outline = face.glyph_bounding_box(ttf::GlyphId(0)).unwrap();
+        let scale = FONT_SIZE / 2048.0;
+        assert!(!font.notdef.outline.indices.is_empty());
+        assert_eq!(font.notdef.advance, face.glyph_hor_advance(ttf::GlyphId(0)).unwrap() as f32 * scale);
+        assert!((font.notdef.bbox.3 - outline.y_max as f32 * scale).abs() < 1e-4);
+        assert!(std::ptr::eq(font.glyph_or_notdef('z'), &font.notdef));
+    }
+
+    #[test]
+    fn blank_notdef_is_replaced_by_a_box_of_the_configured_size() {
+        let data = blank_notdef_dejavu();
+        let advance = ttf::Face::from_slice(&data, 0).unwrap().glyph_hor_advance(ttf::GlyphId(0)).unwrap() as f32 / 2048.0;
+        let options = FontOptions { em_size: 1.0, notdef_size: (0.4, 0.6), ..FontOptions::default() };
+        let font = load_font_from_bytes_with_options(&data, "a", &options).unwrap();
+        let notdef = &font.notdef;
+        assert!(!notdef.outline.indices.is_empty());
+        assert!((notdef.advance - advance).abs() < 1e-6);
+        let (x0, y0, x1, y1) = notdef.bbox;
+        assert!(((x1 - x0) - 0.4).abs() < 1e-5 && (y1 - 0.6).abs() < 1e-5 && y0 == 0.0);
+        assert!(((x0 + x1) / 2.0 - advance / 2.0).abs() < 1e-5, "box is not centered in the advance");
+    }
+
+    #[test]
+    fn synthesized_notdef_follows_the_style_options() {
+        let data = blank_notdef_dejavu();
+        let plain = FontOptions { em_size: 1.0, ..FontOptions::default() };
+        let notdef = |options: &FontOptions| load_font_from_bytes_with_options(&data, "a", options).unwrap().notdef;
+        let box_ = notdef(&plain);
+        assert!(box_.stroke_outline.is_none());
+
+        let stroked = notdef(&plain.clone().stroked(GlyphStroke::new(0.04)));
+        assert!(stroked.stroke_outline.as_ref().is_some_and(|m| !m.indices.is_empty()));
+        let (fill, outline) = (stroked.bbox, stroked.stroke_bbox.unwrap());
+        assert!((fill.0 - outline.0 - 0.02).abs() < 1e-3 && (outline.3 - fill.3 - 0.02).abs() < 1e-3);
+
+        let bold = notdef(&plain.clone().synthetic_bold(0.02));
+        assert!(bold.bbox.2 - bold.bbox.0 > box_.bbox.2 - box_.bbox.0 + 0.01);
+
+        let italic = notdef(&plain.clone().synthetic_italic(12.0));
+        let top_right = italic.outline.vertices.iter().map(|v| v.position[0]).fold(f32::MIN, f32::max);
+        assert!(top_right > box_.bbox.2 + 0.1, "box is not slanted");
+    }
+
+    /// A name table with one record per `(platform, encoding, language, name id, bytes)`.
+    fn name_table(records: &[(u16, u16, u16, u16, &[u8])]) -> Vec<u8> {
+        let mut table = Vec::new();
+        let storage = 6 + 12 * records.len();
+        for v in [0, records.len() as u16, storage as u16] {
+            table.extend(v.to_be_bytes());
+        }
+        let mut strings = Vec::new();
+        for &(platform, encoding, language, name_id, bytes) in records {
+            for v in [platform, encoding, language, name_id, bytes.len() as u16, strings.len() as u16] {
+                table.extend(v.to_be_bytes());
+            }
+            strings.extend_from_slice(bytes);
+        }
+        table.extend(strings);
+        table
+    }
+
+    #[test]
+    fn names_and_style_match_the_published_values() {
+        let font = load_font(DEJAVU_SANS, "a").unwrap();
+        assert_eq!(font.family_name(), "DejaVu Sans");
+        assert_eq!(font.style_name, "Book");
+        assert_eq!((font.weight, font.italic, font.monospace), (400, false, false));
+        assert_eq!(load_font_metrics_only(DEJAVU_SANS).unwrap().name, font.family_name());
+    }
+
+    #[test]
+    fn mac_roman_names_are_decoded_and_missing_names_fall_back_to_the_file() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let mac = crate::fixtures::with_tables(&data, &[(b"name", &name_table(&[(1, 0, 0, 1, b"Caf\x8e Sans"), (1, 0, 0, 2, b"Roman")]))]);
+        let font = load_font_from_bytes(&mac, "a").unwrap();
+        assert_eq!((font.family_name(), font.style_name.as_str()), ("Café Sans", "Roman"));
+
+        let path = std::env::temp_dir().join(format!("svg-load-unnamed-{}.ttf", std::process::id()));
+        std::fs::write(&path, crate::fixtures::with_tables(&data, &[(b"name", &name_table(&[]))])).unwrap();
+        let font = load_font(path.to_str().unwrap(), "a");
+        let metrics = load_font_metrics_only(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(font.unwrap().family_name(), stem);
+        assert_eq!(metrics.unwrap().name, stem);
+    }
+
+    #[test]
+    fn flipped_and_rotated_glyphs_keep_ordered_bboxes() {
+        let load = |transform| {
+            let options = FontOptions { glyph_transform: transform, ..FontOptions::default() };
+            load_font_with_options(DEJAVU_SANS, "g", &options).unwrap().glyph('g').unwrap().clone()
+        };
+        let plain = load(None);
+        assert!(plain.bbox.1 < 0.0, "'g' descends below the baseline");
+        let mesh_bounds = |g: &Glyph| g.outline.vertices.iter().fold((f32::MAX, f32::MAX, f32::MIN, f32::MIN), |b, v| {
+            (b.0.min(v.position[0]), b.1.min(v.position[1]), b.2.max(v.position[0]), b.3.max(v.position[1]))
+        });
+        let close = |a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)| {
+            [a.0 - b.0, a.1 - b.1, a.2 - b.2, a.3 - b.3].iter().all(|d| d.abs() < 0.01)
+        };
+
+        let flipped = load(Some(Transform::new(1.0, 0.0, 0.0, -1.0, 0.0, 0.0)));
+        let (x0, y0, x1, y1) = plain.bbox;
+        assert!(close(flipped.bbox, (x0, -y1, x1, -y0)), "{:?}", flipped.bbox);
+        assert!(close(flipped.bbox, mesh_bounds(&flipped)));
+
+        let rotated = load(Some(Transform::new(0.0, 1.0, -1.0, 0.0, 0.0, 0.0)));
+        assert!(close(rotated.bbox, (-y1, x0, -y0, x1)), "{:?}", rotated.bbox);
+        assert!(rotated.bbox.0 <= rotated.bbox.2 && rotated.bbox.1 <= rotated.bbox.3);
+    }
+
+    #[test]
+    fn prim_id_base_keeps_glyph_ids_clear_of_svg_path_ids() {
+        use std::collections::HashSet;
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="10">
+            <rect width="10" height="10" fill="red"/><rect x="10" width="10" height="10" fill="lime"/>
+            <rect x="20" width="10" height="10" fill="blue"/></svg>"#;
+        let scene = crate::svgload::tessellate_svg_string(svg, &crate::svgload::LoadOptions::default()).unwrap();
+        let path_count = scene.paths().count() as u32;
+        let svg_ids: HashSet<u32> = scene.paths().flat_map(|p| &p.vertices.vertices).map(|v| v.prim_id).collect();
+        assert!(svg_ids.iter().all(|&id| id < path_count));
+
+        let options = FontOptions { prim_id_base: path_count, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "ab", &options).unwrap();
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        for ch in ['a', 'b'] {
+            let expected = face.glyph_index(ch).unwrap().0 as u32 + path_count;
+            assert!(font.glyph(ch).unwrap().outline.vertices.iter().all(|v| v.prim_id == expected), "{}", ch);
+        }
+        let glyph_ids: HashSet<u32> = font.glyph_map.values().chain([&font.notdef])
+            .flat_map(|g| &g.outline.vertices).map(|v| v.prim_id).collect();
+        assert!(glyph_ids.is_disjoint(&svg_ids));
+        assert!(font.notdef.outline.vertices.iter().all(|v| v.prim_id == path_count));
+    }
+
+    #[test]
+    fn side_bearings_match_the_hmtx_table() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let face = ttf::Face::from_slice(&data, 0).unwrap();
+        let options = FontOptions { em_size: 2048.0, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "AjT ", &options).unwrap();
+        for ch in ['A', 'j', 'T', ' '] {
+            let id = face.glyph_index(ch).unwrap();
+            let glyph = font.glyph(ch).unwrap();
+            assert_eq!(glyph.lsb, face.glyph_hor_side_bearing(id).unwrap() as f32, "{}", ch);
+            let ink = glyph.bbox.2 - glyph.bbox.0;
+            assert!((glyph.rsb - (glyph.advance - glyph.lsb - ink)).abs() < 1e-3, "{}", ch);
+        }
+        // The descender of 'j' hooks left of its origin.
+        assert!(font.glyph('j').unwrap().lsb < 0.0);
+        let space = font.glyph(' ').unwrap();
+        assert!(space.is_empty);
+        assert_eq!(space.rsb, space.advance - space.lsb);
+
+        let json = serde_json::to_string(font.glyph('j').unwrap()).unwrap();
+        let restored: Glyph = serde_json::from_str(&json).unwrap();
+        assert_eq!((restored.lsb, restored.rsb), (font.glyph('j').unwrap().lsb, font.glyph('j').unwrap().rsb));
+    }
+
+    #[test]
+    fn mark_offsets_come_from_the_mark_to_base_anchors() {
+        let options = FontOptions { em_size: 2048.0, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "e\u{301}", &options).unwrap();
+        // Base anchor (662, 1147) of 'e' minus mark anchor (-512, 1147) of U+0301.
+        assert_eq!(font.mark_offset('e', '\u{301}'), Some((1174.0, 0.0)));
+        assert_eq!(font.mark_offset('\u{301}', 'e'), None);
+
+        let em = load_font(DEJAVU_SANS, "e\u{301}").unwrap();
+        let (dx, dy) = em.mark_offset('e', '\u{301}').unwrap();
+        assert!((dx - 1174.0 / 2048.0).abs() < 1e-6 && dy == 0.0);
+    }
+
+    #[test]
+    fn fonts_without_mark_lookups_have_no_mark_offsets() {
+        // Version 1.0 with empty script, feature and lookup lists.
+        let gpos = [0, 1, 0, 0, 0, 10, 0, 12, 0, 14, 0, 0, 0, 0, 0, 0];
+        let data = crate::fixtures::with_tables(&crate::fixtures::read("DejaVuSans.ttf"), &[(b"GPOS", &gpos)]);
+        let font = load_font_from_bytes(&data, "e\u{301}").unwrap();
+        assert!(font.mark_anchors.is_empty());
+        assert!(font.kerning.is_empty());
+        assert_eq!(font.glyph('e').unwrap().advance, load_font(DEJAVU_SANS, "e").unwrap().glyph('e').unwrap().advance);
+    }
+
+    #[test]
+    fn source_hashes_are_pinned_and_cover_every_option() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let font = load_font(DEJAVU_SANS, "a").unwrap();
+        let defaults = FontOptions::default();
+        let hash = |options: &FontOptions| source_hash(&data, &font, options);
+        // Fixed-width encodings in a fixed order keep this value across platforms and releases.
+        assert_eq!(font.source_hash, 0x3d74_c2e1_f80a_566c);
+        assert_eq!(hash(&defaults), font.source_hash);
+        assert_eq!(options_bytes(&defaults).len(), 44);
+
+        let variants = [
+            FontOptions { missing_glyph: MissingGlyph::Skip, ..defaults.clone() },
+            FontOptions { retain_path_events: true, ..defaults.clone() },
+            FontOptions { face_index: 1, ..defaults.clone() },
+            FontOptions { ligatures: true, ..defaults.clone() },
+            defaults.clone().variation("wght", 700.0),
+            FontOptions { bitmap_pixels_per_em: 32, ..defaults.clone() },
+            FontOptions { tabular_figures: true, ..defaults.clone() },
+            FontOptions { em_size: 2.0, ..defaults.clone() },
+            FontOptions { tolerance: Some(0.5 / 2048.0), ..defaults.clone() },
+            defaults.clone().synthetic_italic(12.0),
+            defaults.clone().synthetic_bold(0.02),
+            FontOptions { tessellate: false, ..defaults.clone() },
+            FontOptions { notdef_size: (0.5, 0.8), ..defaults.clone() },
+            FontOptions { prim_id_base: 10, ..defaults.clone() },
+            FontOptions { glyph_transform: Some(Transform::new_scale(1.2, 1.0)), ..defaults.clone() },
+            FontOptions { force_advance: Some(Advance::MaxLoaded), ..defaults.clone() },
+            FontOptions { force_advance: Some(Advance::Em(0.6)), ..defaults.clone() },
+            FontOptions { center_in_cell: true, ..defaults.clone() },
+            defaults.clone().stroked(GlyphStroke::new(0.02)),
+            defaults.clone().stroked(GlyphStroke { join: LineJoin::Bevel, ..GlyphStroke::new(0.02) }),
+        ];
+        let mut hashes: Vec<u64> = variants.iter().map(hash).collect();
+        hashes.push(hash(&defaults));
+        let count = hashes.len();
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), count, "two option sets share a hash");
+
+        assert!(!font.is_stale(&data, &defaults));
+        assert!(font.is_stale(&data, &variants[0]));
+
+        // Any byte of the font and the loaded charset count too.
+        for offset in [0, data.len() / 2, data.len() - 1] {
+            let mut flipped = data.clone();
+            flipped[offset] ^= 1;
+            assert_ne!(source_hash(&flipped, &font, &defaults), font.source_hash, "byte {} is not hashed", offset);
+        }
+        let mut truncated = data.clone();
+        truncated.pop();
+        assert_ne!(source_hash(&truncated, &font, &defaults), font.source_hash);
+        for symbols in ["b", "ab", ""] {
+            let other = load_font(DEJAVU_SANS, symbols).unwrap();
+            assert_ne!(other.source_hash, font.source_hash, "charset {:?} has the hash of \"a\"", symbols);
+        }
+        let mut unresolved = font.clone();
+        unresolved.unresolved.push(0x10FFFD);
+        assert_ne!(source_hash(&data, &unresolved, &defaults), font.source_hash);
+    }
+
+    #[test]
+    fn glyph_transform_rotates_every_vertex() {
+        let plain = load_font(DEJAVU_SANS, "L").unwrap();
+        let options = FontOptions { glyph_transform: Some(Transform::new_rotate(45.0)), ..FontOptions::default() };
+        let rotated = load_font_with_options(DEJAVU_SANS, "L", &options).unwrap();
+        let (before, after) = (plain.glyph('L').unwrap(), rotated.glyph('L').unwrap());
+        assert_eq!(before.outline.indices, after.outline.indices);
+        let (sin, cos) = std::f32::consts::FRAC_PI_4.sin_cos();
+        for (a, b) in before.outline.vertices.iter().zip(&after.outline.vertices) {
+            let [x, y] = a.position;
+            let expected = [x * cos - y * sin, x * sin + y * cos];
+            assert!((b.position[0] - expected[0]).abs() < 1e-5 && (b.position[1] - expected[1]).abs() < 1e-5,
+                    "{:?} rotated to {:?}", a.position, b.position);
+        }
+        assert_eq!(before.advance, after.advance);
+    }
+
+    /// DejaVu Sans with the post table's `isFixedPitch` or the OS/2 PANOSE monospace flag set.
+    fn fixed_pitch_dejavu(panose: bool) -> Vec<u8> {
+        let mut data = crate::fixtures::read("DejaVuSans.ttf");
+        let (tag, at, bytes): (&[u8; 4], usize, &[u8]) = if panose { (b"OS/2", 32, &[2, 0, 0, 9]) } else { (b"post", 12, &[0, 0, 0, 1]) };
+        let record = table_record(&data, 0, tag);
+        let offset = u32::from_be_bytes(data[record + 8..record + 12].try_into().unwrap()) as usize;
+        data[offset + at..offset + at + 4].copy_from_slice(bytes);
+        data
+    }
+
+    #[test]
+    fn monospace_needs_a_flag_and_uniform_loaded_advances() {
+        assert!(!load_font(DEJAVU_SANS, "0123456789").unwrap().is_monospace());
+        for panose in [false, true] {
+            let data = fixed_pitch_dejavu(panose);
+            // DejaVu's digits share one advance, unlike 'i' and 'W'.
+            assert!(load_font_from_bytes(&data, "0123456789").unwrap().is_monospace());
+            assert!(!load_font_from_bytes(&data, "iW").unwrap().is_monospace());
+        }
+    }
+
+    #[test]
+    fn forced_advances_replace_widths_and_kerning() {
+        let max = load_font(DEJAVU_SANS, "AVW").unwrap().glyph('W').unwrap().advance;
+        let font = load_font_with_options(DEJAVU_SANS, "AVW", &FontOptions { force_advance: Some(Advance::MaxLoaded), ..FontOptions::default() }).unwrap();
+        assert!(font.glyph_map.values().all(|g| g.advance == max));
+        assert!(font.kerning.is_empty());
+
+        let plain = load_font(DEJAVU_SANS, "il").unwrap();
+        let options = FontOptions { force_advance: Some(Advance::Em(0.6)), center_in_cell: true, ..FontOptions::default() };
+        let font = load_font_with_options(DEJAVU_SANS, "il", &options).unwrap();
+        assert_eq!(font.notdef.advance, 0.6);
+        for ch in ['i', 'l'] {
+            let (before, after) = (plain.glyph(ch).unwrap(), font.glyph(ch).unwrap());
+            assert_eq!(after.advance, 0.6);
+            let dx = after.bbox.0 - before.bbox.0;
+            assert!(((after.bbox.0 + after.bbox.2) / 2.0 - 0.3).abs() < 1e-5, "{} is not centered", ch);
+            assert!(((after.bbox.2 - after.bbox.0) - (before.bbox.2 - before.bbox.0)).abs() < 1e-6);
+            assert!((after.outline.vertices[0].position[0] - before.outline.vertices[0].position[0] - dx).abs() < 1e-5);
+            assert!((after.lsb - after.bbox.0).abs() < 1e-5 && (after.lsb + (after.bbox.2 - after.bbox.0) + after.rsb - 0.6).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn family_name_comes_from_the_name_table_and_name_from_the_file() {
+        let path = std::env::temp_dir().join(format!("svg-load-renamed-{}.ttf", std::process::id()));
+        std::fs::write(&path, crate::fixtures::read("DejaVuSans.ttf")).unwrap();
+        let font = load_font(path.to_str().unwrap(), "a");
+        std::fs::remove_file(&path).ok();
+        let font = font.unwrap();
+        assert_eq!(font.name, path.file_name().unwrap().to_str().unwrap());
+        assert_eq!(font.family_name(), "DejaVu Sans");
+        assert_ne!(font.name, font.family_name);
+    }
+
+    #[test]
+    fn max_loaded_advances_are_fitted_over_every_added_glyph() {
+        let max = load_font(DEJAVU_SANS, "W").unwrap().glyph('W').unwrap().advance;
+        let options = FontOptions { force_advance: Some(Advance::MaxLoaded), ..FontOptions::default() };
+        let mut font = load_font_with_options(DEJAVU_SANS, "il", &options).unwrap();
+        assert!(font.notdef.advance < max);
+        font.add_symbols(&crate::fixtures::read("DejaVuSans.ttf"), "W", &options).unwrap();
+        assert!(font.glyph_map.values().chain([&font.notdef]).all(|g| g.advance == max));
+    }
+
+    #[test]
+    fn added_symbols_must_use_the_scale_of_the_font() {
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        let options = FontOptions { em_size: 2.0, ..FontOptions::default() };
+        let mut font = load_font_from_bytes_with_options(&data, "a", &options).unwrap();
+        for other in [FontOptions::default(), FontOptions { tolerance: Some(0.01), ..options.clone() }] {
+            let error = font.add_symbols(&data, "b", &other).expect_err("options differ from the font's");
+            assert!(matches!(error, FontLoadError::OptionsMismatch { loaded: (2.0, _), .. }));
+            assert!(font.add_glyph_ids(&data, &[3], &other).is_err());
+        }
+        assert!(font.glyph('b').is_none() && font.glyph_id_map.is_empty());
+        font.add_symbols(&data, "b", &options).unwrap();
+        assert!((font.glyph('b').unwrap().advance - 2.0 * load_font(DEJAVU_SANS, "b").unwrap().glyph('b').unwrap().advance).abs() < 1e-5);
+    }
+
+    #[test]
+    fn unreadable_and_malformed_fonts_have_their_own_errors() {
+        let missing = load_font("/nonexistent/svg-load/font.ttf", "a").expect_err("file does not exist");
+        assert!(matches!(&missing, FontLoadError::Io(e) if e.kind() == std::io::ErrorKind::NotFound));
+        assert!(missing.to_string().starts_with("cannot read font: "));
+
+        let data = crate::fixtures::read("DejaVuSans.ttf");
+        for broken in [&data[..64], b"definitely not a font file".as_slice(), &[]] {
+            let error = load_font_from_bytes(broken, "a").expect_err("data is not a font");
+            assert!(matches!(error, FontLoadError::MalformedFont(_)), "{:?}", error);
+            assert!(error.to_string().starts_with("cannot parse font: "));
+            assert!(std::error::Error::source(&error).is_some());
+        }
+    }
+
+    #[test]
+    fn svg_glyph_layers_start_at_prim_id_base() {
+        let layers = r##"<svg xmlns="http://www.w3.org/2000/svg"><g id="glyph{id}">
+            <rect x="0" y="-2048" width="1024" height="2048" fill="#ff0000"/>
+            <rect x="1024" y="-2048" width="1024" height="2048" fill="#0000ff"/></g></svg>"##;
+        let data = svg_dejavu(&[('a', layers)]);
+        let prim_ids = |base: u32| {
+            let options = FontOptions { prim_id_base: base, ..FontOptions::default() };
+            let font = load_font_from_bytes_with_options(&data, "a", &options).unwrap();
+            let glyph = font.glyph('a').unwrap();
+            assert_eq!(glyph.svg_layers.len(), 2);
+            glyph.svg_layers.iter()
+                .map(|layer| layer.vertices.vertices.iter().map(|v| v.prim_id).collect::<BTreeSet<u32>>())
+                .collect::<Vec<_>>()
+        };
+        let unshifted = prim_ids(0);
+        let shifted = prim_ids(1000);
+        assert!(shifted.iter().flatten().all(|&id| id >= 1000));
+        for (a, b) in unshifted.iter().zip(&shifted) {
+            assert_eq!(a.iter().map(|id| id + 1000).collect::<BTreeSet<u32>>(), *b);
+        }
+    }
+}
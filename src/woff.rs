@@ -0,0 +1,963 @@
+//! WOFF and WOFF2 web font containers, unpacked in memory to the plain SFNT (TrueType or
+//! OpenType) data they wrap, so they load exactly like the original font file.
+//!
+//! WOFF stores each table zlib-compressed. WOFF2 compresses all tables as one brotli stream
+//! and usually stores `glyf`, `loca` and `hmtx` in a transformed form, which is rebuilt here.
+//! WOFF2 font collections are not supported.
+
+use std::fmt;
+use std::io::Read;
+
+const WOFF_MAGIC: &[u8; 4] = b"wOFF";
+const WOFF2_MAGIC: &[u8; 4] = b"wOF2";
+
+/// Tags of the WOFF2 known-table index, see the WOFF2 specification, section 4.1.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WoffError {
+    /// The data ends before a structure it declares.
+    Truncated,
+    /// A table or the WOFF2 table stream does not decompress to its declared size.
+    Decompression,
+    /// A header, directory or transformed table holds impossible values.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for WoffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WoffError::Truncated => write!(f, "woff data is truncated"),
+            WoffError::Decompression => write!(f, "woff table data does not decompress"),
+            WoffError::Invalid(what) => write!(f, "invalid woff data: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for WoffError {}
+
+/// Whether `data` starts like a WOFF or WOFF2 file.
+pub fn is_woff(data: &[u8]) -> bool {
+    data.starts_with(WOFF_MAGIC) || data.starts_with(WOFF2_MAGIC)
+}
+
+/// Unpacks the WOFF or WOFF2 file `data` to SFNT data, which `ttf-parser` and the font
+/// loaders read.
+pub fn to_sfnt(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    if data.starts_with(WOFF_MAGIC) {
+        woff1_to_sfnt(data)
+    } else if data.starts_with(WOFF2_MAGIC) {
+        woff2_to_sfnt(data)
+    } else {
+        Err(WoffError::Invalid("not a woff or woff2 file"))
+    }
+}
+
+fn woff1_to_sfnt(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    r.skip(4)?;
+    let num_tables = r.u16()?;
+    r.skip(30)?;
+
+    let mut tables = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let tag = r.tag()?;
+        let (offset, comp_length, orig_length) = (r.u32()? as usize, r.u32()? as usize, r.u32()? as usize);
+        r.skip(4)?;
+        let stored = offset.checked_add(comp_length).and_then(|end| data.get(offset..end)).ok_or(WoffError::Truncated)?;
+        let table = if comp_length == orig_length {
+            stored.to_vec()
+        } else if comp_length < orig_length {
+            read_exactly(flate2::read::ZlibDecoder::new(stored), orig_length)?
+        } else {
+            return Err(WoffError::Invalid("table is larger compressed than uncompressed"));
+        };
+        tables.push((tag, table));
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+/// A WOFF2 table directory entry.
+struct Woff2Table {
+    tag: [u8; 4],
+    orig_length: usize,
+    /// Length in the decompressed stream, which differs from `orig_length` when transformed.
+    stream_length: usize,
+    transformed: bool,
+}
+
+fn woff2_to_sfnt(data: &[u8]) -> Result<Vec<u8>, WoffError> {
+    let mut r = Reader::new(data);
+    r.skip(4)?;
+    let flavor = r.u32()?;
+    if &flavor.to_be_bytes() == b"ttcf" {
+        return Err(WoffError::Invalid("woff2 font collections are not supported"));
+    }
+    r.skip(4)?;
+    let num_tables = r.u16()?;
+    r.skip(6)?;
+    let compressed_size = r.u32()? as usize;
+    r.skip(24)?;
+
+    let mut directory = Vec::with_capacity(num_tables as usize);
+    for _ in 0..num_tables {
+        let flags = r.u8()?;
+        let tag = match flags & 0x3f {
+            63 => r.tag()?,
+            index => *KNOWN_TAGS[index as usize],
+        };
+        let version = flags >> 6;
+        let orig_length = r.base128()? as usize;
+        // glyf and loca are transformed unless they say version 3; other tables only when
+        // they say a version other than 0.
+        let transformed = if &tag == b"glyf" || &tag == b"loca" { version != 3 } else { version != 0 };
+        let stream_length = if transformed { r.base128()? as usize } else { orig_length };
+        directory.push(Woff2Table { tag, orig_length, stream_length, transformed });
+    }
+
+    let compressed = r.bytes(compressed_size)?;
+    let total = directory.iter().try_fold(0usize, |sum, t| sum.checked_add(t.stream_length))
+        .ok_or(WoffError::Invalid("table sizes overflow"))?;
+    let stream = read_exactly(brotli_decompressor::Decompressor::new(compressed, 4096), total)?;
+
+    let mut streams = Vec::with_capacity(directory.len());
+    let mut offset = 0;
+    for table in &directory {
+        streams.push(&stream[offset..offset + table.stream_length]);
+        offset += table.stream_length;
+    }
+    let find = |tag: &[u8; 4]| directory.iter().position(|t| &t.tag == tag);
+
+    // The transformed glyf stream rebuilds glyf and loca together; hmtx needs the result.
+    let mut rebuilt_glyf = None;
+    if let Some(glyf) = find(b"glyf").filter(|&i| directory[i].transformed) {
+        let loca = find(b"loca").ok_or(WoffError::Invalid("transformed glyf without loca"))?;
+        if !directory[loca].transformed || directory[loca].stream_length != 0 {
+            return Err(WoffError::Invalid("loca must be transformed along with glyf"));
+        }
+        rebuilt_glyf = Some(GlyfTables::decode(streams[glyf])?);
+    }
+
+    let mut tables = Vec::with_capacity(directory.len());
+    for (table, stream) in directory.iter().zip(&streams) {
+        let bytes = match (&table.tag, table.transformed, &rebuilt_glyf) {
+            (_, false, _) => stream.to_vec(),
+            (b"glyf", true, Some(g)) => g.glyf.clone(),
+            (b"loca", true, Some(g)) => {
+                if g.loca.len() != table.orig_length {
+                    return Err(WoffError::Invalid("rebuilt loca has the wrong size"));
+                }
+                g.loca.clone()
+            }
+            (b"hmtx", true, _) => {
+                let glyf = rebuilt_glyf.as_ref().ok_or(WoffError::Invalid("transformed hmtx without transformed glyf"))?;
+                let hhea = find(b"hhea").map(|i| streams[i]).ok_or(WoffError::Invalid("transformed hmtx without hhea"))?;
+                let num_h_metrics = Reader::new(hhea.get(34..).ok_or(WoffError::Truncated)?).u16()?;
+                decode_hmtx(stream, num_h_metrics as usize, &glyf.x_mins)?
+            }
+            _ => return Err(WoffError::Invalid("unknown table transform")),
+        };
+        tables.push((table.tag, bytes));
+    }
+    Ok(build_sfnt(flavor, tables))
+}
+
+/// `glyf` and `loca` rebuilt from a transformed WOFF2 glyf table, with each glyph's `xMin`
+/// for rebuilding `hmtx`.
+struct GlyfTables {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    x_mins: Vec<i16>,
+}
+
+impl GlyfTables {
+    fn decode(data: &[u8]) -> Result<Self, WoffError> {
+        let mut header = Reader::new(data);
+        header.skip(2)?;
+        let option_flags = header.u16()?;
+        let num_glyphs = header.u16()? as usize;
+        let long_loca = header.u16()? != 0;
+        let mut sizes = [0usize; 7];
+        for size in &mut sizes {
+            *size = header.u32()? as usize;
+        }
+        let mut streams = Reader::new(header.remaining());
+        let mut n_contours = Reader::new(streams.bytes(sizes[0])?);
+        let mut n_points = Reader::new(streams.bytes(sizes[1])?);
+        let mut flags = Reader::new(streams.bytes(sizes[2])?);
+        let mut glyphs = Reader::new(streams.bytes(sizes[3])?);
+        let mut composites = Reader::new(streams.bytes(sizes[4])?);
+        let mut bboxes = Reader::new(streams.bytes(sizes[5])?);
+        let mut instructions = Reader::new(streams.bytes(sizes[6])?);
+        let overlap = if option_flags & 1 != 0 { Some(streams.bytes(num_glyphs.div_ceil(8))?) } else { None };
+        let bbox_bitmap = bboxes.bytes(num_glyphs.div_ceil(32) * 4)?;
+        let has_bit = |bitmap: &[u8], i: usize| bitmap[i / 8] & (0x80 >> (i % 8)) != 0;
+
+        let mut out = GlyfTables { glyf: Vec::new(), loca: Vec::new(), x_mins: vec![0; num_glyphs] };
+        let mut offsets = Vec::with_capacity(num_glyphs + 1);
+        for i in 0..num_glyphs {
+            offsets.push(out.glyf.len());
+            let contours = n_contours.i16()?;
+            let explicit_bbox = has_bit(bbox_bitmap, i);
+            let start = out.glyf.len();
+            match contours {
+                0 => {
+                    if explicit_bbox {
+                        return Err(WoffError::Invalid("empty glyph with a bounding box"));
+                    }
+                    continue;
+                }
+                -1 => {
+                    if !explicit_bbox {
+                        return Err(WoffError::Invalid("composite glyph without a bounding box"));
+                    }
+                    out.glyf.extend_from_slice(&(-1i16).to_be_bytes());
+                    out.glyf.extend_from_slice(bboxes.bytes(8)?);
+                    let has_instructions = copy_composite(&mut composites, &mut out.glyf)?;
+                    if has_instructions {
+                        let len = glyphs.u255()? as usize;
+                        out.glyf.extend_from_slice(&(len as u16).to_be_bytes());
+                        out.glyf.extend_from_slice(instructions.bytes(len)?);
+                    }
+                }
+                contours if contours > 0 => {
+                    let mut end_points = Vec::with_capacity(contours as usize);
+                    let mut total = 0usize;
+                    for _ in 0..contours {
+                        total += n_points.u255()? as usize;
+                        end_points.push(total.checked_sub(1).ok_or(WoffError::Invalid("contour without points"))?);
+                    }
+                    if total > u16::MAX as usize {
+                        return Err(WoffError::Invalid("glyph has too many points"));
+                    }
+                    let points = decode_points(flags.bytes(total)?, &mut glyphs)?;
+                    let bbox = if explicit_bbox {
+                        bboxes.bytes(8)?.to_vec()
+                    } else {
+                        let (x0, y0, x1, y1) = points.iter().fold((i32::MAX, i32::MAX, i32::MIN, i32::MIN), |b, p| {
+                            (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y))
+                        });
+                        [x0, y0, x1, y1].iter().flat_map(|&v| (v as i16).to_be_bytes()).collect()
+                    };
+                    out.glyf.extend_from_slice(&contours.to_be_bytes());
+                    out.glyf.extend_from_slice(&bbox);
+                    for &end in &end_points {
+                        out.glyf.extend_from_slice(&(end as u16).to_be_bytes());
+                    }
+                    let len = glyphs.u255()? as usize;
+                    out.glyf.extend_from_slice(&(len as u16).to_be_bytes());
+                    out.glyf.extend_from_slice(instructions.bytes(len)?);
+                    let overlaps = overlap.is_some_and(|bitmap| has_bit(bitmap, i));
+                    encode_points(&points, overlaps, &mut out.glyf);
+                }
+                _ => return Err(WoffError::Invalid("negative contour count other than -1")),
+            }
+            out.x_mins[i] = i16::from_be_bytes([out.glyf[start + 2], out.glyf[start + 3]]);
+            out.glyf.resize(out.glyf.len().next_multiple_of(4), 0);
+        }
+        offsets.push(out.glyf.len());
+
+        for offset in offsets {
+            if long_loca {
+                out.loca.extend_from_slice(&(offset as u32).to_be_bytes());
+            } else {
+                let half = u16::try_from(offset / 2).map_err(|_| WoffError::Invalid("glyf is too large for a short loca"))?;
+                out.loca.extend_from_slice(&half.to_be_bytes());
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Copies the component records of a composite glyph to `out`; tells whether the glyph has
+/// instructions.
+fn copy_composite(composites: &mut Reader, out: &mut Vec<u8>) -> Result<bool, WoffError> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAVE_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAVE_XY_SCALE: u16 = 0x0040;
+    const HAVE_2X2: u16 = 0x0080;
+    const HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    let mut has_instructions = false;
+    loop {
+        let flags = composites.u16()?;
+        has_instructions |= flags & HAVE_INSTRUCTIONS != 0;
+        let mut len = 2 + if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & HAVE_SCALE != 0 {
+            len += 2;
+        } else if flags & HAVE_XY_SCALE != 0 {
+            len += 4;
+        } else if flags & HAVE_2X2 != 0 {
+            len += 8;
+        }
+        out.extend_from_slice(&flags.to_be_bytes());
+        out.extend_from_slice(composites.bytes(len)?);
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(has_instructions);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct GlyphPoint {
+    x: i32,
+    y: i32,
+    on_curve: bool,
+}
+
+/// Absolute points of a simple glyph from its WOFF2 flags and triplet-encoded deltas.
+fn decode_points(flags: &[u8], glyphs: &mut Reader) -> Result<Vec<GlyphPoint>, WoffError> {
+    let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut points = Vec::with_capacity(flags.len());
+    for &flag in flags {
+        let on_curve = flag & 0x80 == 0;
+        let flag = flag & 0x7f;
+        let (dx, dy) = if flag < 10 {
+            let b = glyphs.u8()? as i32;
+            (0, with_sign(flag, (((flag & 14) as i32) << 7) + b))
+        } else if flag < 20 {
+            let b = glyphs.u8()? as i32;
+            (with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b), 0)
+        } else if flag < 84 {
+            let (b0, b1) = ((flag - 20) as i32, glyphs.u8()? as i32);
+            (with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)), with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)))
+        } else if flag < 120 {
+            let b0 = (flag - 84) as i32;
+            let (b1, b2) = (glyphs.u8()? as i32, glyphs.u8()? as i32);
+            (with_sign(flag, 1 + ((b0 / 12) << 8) + b1), with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2))
+        } else if flag < 124 {
+            let (b1, b2, b3) = (glyphs.u8()? as i32, glyphs.u8()? as i32, glyphs.u8()? as i32);
+            (with_sign(flag, (b1 << 4) + (b2 >> 4)), with_sign(flag >> 1, ((b2 & 0x0f) << 8) + b3))
+        } else {
+            let b: Vec<i32> = glyphs.bytes(4)?.iter().map(|&b| b as i32).collect();
+            (with_sign(flag, (b[0] << 8) + b[1]), with_sign(flag >> 1, (b[2] << 8) + b[3]))
+        };
+        x += dx;
+        y += dy;
+        points.push(GlyphPoint { x, y, on_curve });
+    }
+    Ok(points)
+}
+
+/// Appends the flags and coordinates of `points` in the TrueType simple glyph encoding.
+fn encode_points(points: &[GlyphPoint], overlaps: bool, out: &mut Vec<u8>) {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    let mut flags = Vec::with_capacity(points.len());
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    let (mut last_x, mut last_y) = (0, 0);
+    for (i, p) in points.iter().enumerate() {
+        let mut flag = if p.on_curve { ON_CURVE } else { 0 };
+        if overlaps && i == 0 {
+            flag |= OVERLAP_SIMPLE;
+        }
+        for (delta, coords, short, same) in [(p.x - last_x, &mut xs, X_SHORT, X_SAME_OR_POSITIVE), (p.y - last_y, &mut ys, Y_SHORT, Y_SAME_OR_POSITIVE)] {
+            if delta == 0 {
+                flag |= same;
+            } else if delta.abs() < 256 {
+                flag |= short | if delta > 0 { same } else { 0 };
+                coords.push(delta.unsigned_abs() as u8);
+            } else {
+                coords.extend_from_slice(&(delta as i16).to_be_bytes());
+            }
+        }
+        flags.push(flag);
+        last_x = p.x;
+        last_y = p.y;
+    }
+    out.extend(flags);
+    out.extend(xs);
+    out.extend(ys);
+}
+
+/// Rebuilds `hmtx` from its WOFF2 transform, taking omitted left side bearings from `x_mins`.
+fn decode_hmtx(data: &[u8], num_h_metrics: usize, x_mins: &[i16]) -> Result<Vec<u8>, WoffError> {
+    let mut r = Reader::new(data);
+    let flags = r.u8()?;
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(WoffError::Invalid("hhea metric count does not match the glyph count"));
+    }
+    let advances = (0..num_h_metrics).map(|_| r.u16()).collect::<Result<Vec<_>, _>>()?;
+    let mut lsbs = Vec::with_capacity(num_glyphs);
+    for (i, &x_min) in x_mins.iter().enumerate() {
+        let omitted = if i < num_h_metrics { flags & 1 != 0 } else { flags & 2 != 0 };
+        lsbs.push(if omitted { x_min } else { r.i16()? });
+    }
+    let mut out = Vec::with_capacity(num_h_metrics * 4 + (num_glyphs - num_h_metrics) * 2);
+    for (i, lsb) in lsbs.iter().enumerate() {
+        if let Some(advance) = advances.get(i) {
+            out.extend_from_slice(&advance.to_be_bytes());
+        }
+        out.extend_from_slice(&lsb.to_be_bytes());
+    }
+    Ok(out)
+}
+
+/// Reads all of `reader`, failing unless it yields exactly `len` bytes.
+fn read_exactly(reader: impl Read, len: usize) -> Result<Vec<u8>, WoffError> {
+    let mut out = Vec::new();
+    reader.take(len as u64 + 1).read_to_end(&mut out).map_err(|_| WoffError::Decompression)?;
+    if out.len() != len {
+        return Err(WoffError::Decompression);
+    }
+    Ok(out)
+}
+
+/// An SFNT file of `tables`: a table directory sorted by tag, then the 4-byte aligned data.
+fn build_sfnt(flavor: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let num_tables = tables.len() as u16;
+    let entry_selector = if num_tables == 0 { 0 } else { 15 - num_tables.leading_zeros() as u16 };
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&flavor.to_be_bytes());
+    for v in [num_tables, search_range, entry_selector, (num_tables * 16).saturating_sub(search_range)] {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in &tables {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&checksum(data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in &tables {
+        out.extend_from_slice(data);
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+    out
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Big-endian reader that fails with [`WoffError::Truncated`] past the end.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], WoffError> {
+        let end = self.pos.checked_add(len).ok_or(WoffError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(WoffError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), WoffError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, WoffError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, WoffError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, WoffError> {
+        self.u16().map(|v| v as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, WoffError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn tag(&mut self) -> Result<[u8; 4], WoffError> {
+        let b = self.bytes(4)?;
+        Ok([b[0], b[1], b[2], b[3]])
+    }
+
+    /// WOFF2 `UIntBase128`: 7 bits per byte, most significant first, at most 5 bytes.
+    fn base128(&mut self) -> Result<u32, WoffError> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                return Err(WoffError::Invalid("UIntBase128 with a leading zero"));
+            }
+            if value & 0xfe00_0000 != 0 {
+                return Err(WoffError::Invalid("UIntBase128 overflows"));
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(WoffError::Invalid("UIntBase128 longer than 5 bytes"))
+    }
+
+    /// WOFF2 `255UInt16`.
+    fn u255(&mut self) -> Result<u16, WoffError> {
+        Ok(match self.u8()? {
+            253 => self.u16()?,
+            254 => self.u8()? as u16 + 506,
+            255 => self.u8()? as u16 + 253,
+            code => code as u16,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::ops::Range;
+
+    use crate::fixtures::{self, DEJAVU_SANS};
+    use crate::font::Font;
+    use crate::ttfload::{load_font, load_font_from_bytes, FontLoadError};
+
+    /// Simple, composite and empty glyphs, with and without hinting instructions.
+    const SAMPLE: &str = "AaBgé%ﬁ ";
+
+    type Tables = Vec<([u8; 4], Vec<u8>)>;
+
+    /// Flavor and tables of the single-face sfnt `font` in directory order.
+    fn sfnt_tables(font: &[u8]) -> (u32, Tables) {
+        let mut r = Reader::new(font);
+        let flavor = r.u32().unwrap();
+        let num_tables = r.u16().unwrap();
+        r.skip(6).unwrap();
+        let tables = (0..num_tables).map(|_| {
+            let tag = r.tag().unwrap();
+            r.skip(4).unwrap();
+            let (offset, len) = (r.u32().unwrap() as usize, r.u32().unwrap() as usize);
+            (tag, font[offset..offset + len].to_vec())
+        }).collect();
+        (flavor, tables)
+    }
+
+    /// WOFF file of `font` with every table zlib-compressed where that makes it smaller.
+    fn woff1(font: &[u8]) -> Vec<u8> {
+        let (flavor, tables) = sfnt_tables(font);
+        let data_start = 44 + 20 * tables.len();
+        let (mut directory, mut body) = (Vec::new(), Vec::new());
+        for (tag, data) in &tables {
+            let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let stored = if compressed.len() < data.len() { compressed } else { data.clone() };
+            directory.extend_from_slice(tag);
+            for v in [data_start + body.len(), stored.len(), data.len()] {
+                directory.extend((v as u32).to_be_bytes());
+            }
+            directory.extend(checksum(data).to_be_bytes());
+            body.extend(stored);
+            body.resize(body.len().next_multiple_of(4), 0);
+        }
+        let mut out = WOFF_MAGIC.to_vec();
+        out.extend(flavor.to_be_bytes());
+        out.extend(((data_start + body.len()) as u32).to_be_bytes());
+        out.extend((tables.len() as u16).to_be_bytes());
+        out.extend([0; 2]);
+        out.extend((font.len() as u32).to_be_bytes());
+        // Version, metadata and private block fields.
+        out.extend([0; 24]);
+        out.extend(directory);
+        out.extend(body);
+        out
+    }
+
+    /// Little-endian bit writer for brotli headers.
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        used: u32,
+    }
+
+    impl Bits {
+        fn push(&mut self, value: u32, count: u32) {
+            for i in 0..count {
+                if self.used == 0 {
+                    self.bytes.push(0);
+                }
+                *self.bytes.last_mut().unwrap() |= (((value >> i) & 1) as u8) << self.used;
+                self.used = (self.used + 1) % 8;
+            }
+        }
+
+        fn align(&mut self) {
+            self.used = 0;
+        }
+    }
+
+    /// `data` as a brotli stream of uncompressed meta-blocks, which every decoder accepts.
+    fn brotli_stored(data: &[u8]) -> Vec<u8> {
+        let mut bits = Bits::default();
+        // A 16-bit window.
+        bits.push(0, 1);
+        for chunk in data.chunks(1 << 16) {
+            // Not last, 4 nibbles of length, uncompressed.
+            bits.push(0, 1);
+            bits.push(0, 2);
+            bits.push(chunk.len() as u32 - 1, 16);
+            bits.push(1, 1);
+            bits.align();
+            bits.bytes.extend_from_slice(chunk);
+        }
+        // Last and empty.
+        bits.push(0b11, 2);
+        bits.bytes
+    }
+
+    fn base128(mut value: u32, out: &mut Vec<u8>) {
+        let mut bytes = vec![(value & 0x7f) as u8];
+        value >>= 7;
+        while value > 0 {
+            bytes.push((value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        out.extend(bytes.iter().rev());
+    }
+
+    fn u255(value: u16, out: &mut Vec<u8>) {
+        match value {
+            0..=252 => out.push(value as u8),
+            253..=505 => out.extend([255, (value - 253) as u8]),
+            506..=761 => out.extend([254, (value - 506) as u8]),
+            _ => {
+                out.push(253);
+                out.extend(value.to_be_bytes());
+            }
+        }
+    }
+
+    /// Appends the WOFF2 triplet encoding of the point delta `(dx, dy)`, choosing the
+    /// smallest form like the reference encoder.
+    fn triplet(dx: i32, dy: i32, on_curve: bool, flags: &mut Vec<u8>, glyphs: &mut Vec<u8>) {
+        let (ax, ay) = (dx.unsigned_abs(), dy.unsigned_abs());
+        let (x_sign, y_sign) = ((dx >= 0) as u32, (dy >= 0) as u32);
+        let signs = x_sign + 2 * y_sign;
+        let flag = if dx == 0 && ay < 1280 {
+            glyphs.push(ay as u8);
+            ((ay >> 8) << 1) + y_sign
+        } else if dy == 0 && ax < 1280 {
+            glyphs.push(ax as u8);
+            10 + ((ax >> 8) << 1) + x_sign
+        } else if ax <= 64 && ay <= 64 {
+            glyphs.push((((ax - 1) & 0xf) << 4 | ((ay - 1) & 0xf)) as u8);
+            20 + ((ax - 1) & 0x30) + (((ay - 1) & 0x30) >> 2) + signs
+        } else if ax <= 768 && ay <= 768 {
+            glyphs.extend([(ax - 1) as u8, (ay - 1) as u8]);
+            84 + 12 * ((ax - 1) >> 8) + (((ay - 1) >> 8) << 2) + signs
+        } else if ax < 4096 && ay < 4096 {
+            glyphs.extend([(ax >> 4) as u8, ((ax & 0xf) << 4 | ay >> 8) as u8, ay as u8]);
+            120 + signs
+        } else {
+            glyphs.extend([(ax >> 8) as u8, ax as u8, (ay >> 8) as u8, ay as u8]);
+            124 + signs
+        };
+        flags.push(flag as u8 | if on_curve { 0 } else { 0x80 });
+    }
+
+    /// End points, instructions and absolute points of the simple glyph `glyph`.
+    fn simple_glyph(glyph: &[u8], contours: usize) -> (Vec<u16>, &[u8], Vec<GlyphPoint>) {
+        let mut r = Reader::new(&glyph[10..]);
+        let ends: Vec<u16> = (0..contours).map(|_| r.u16().unwrap()).collect();
+        let len = r.u16().unwrap() as usize;
+        let instructions = r.bytes(len).unwrap();
+        let count = *ends.last().unwrap() as usize + 1;
+        let mut flags = Vec::with_capacity(count);
+        while flags.len() < count {
+            let flag = r.u8().unwrap();
+            flags.push(flag);
+            if flag & 0x08 != 0 {
+                for _ in 0..r.u8().unwrap() {
+                    flags.push(flag);
+                }
+            }
+        }
+        let mut coordinates = |short: u8, same_or_positive: u8| {
+            let mut value = 0i32;
+            flags.iter().map(|&f| {
+                value += if f & short != 0 {
+                    let delta = r.u8().unwrap() as i32;
+                    if f & same_or_positive != 0 { delta } else { -delta }
+                } else if f & same_or_positive != 0 {
+                    0
+                } else {
+                    r.i16().unwrap() as i32
+                };
+                value
+            }).collect::<Vec<_>>()
+        };
+        let xs = coordinates(0x02, 0x10);
+        let ys = coordinates(0x04, 0x20);
+        let points = flags.iter().zip(xs.into_iter().zip(ys))
+            .map(|(&f, (x, y))| GlyphPoint { x, y, on_curve: f & 1 != 0 })
+            .collect();
+        (ends, instructions, points)
+    }
+
+    /// Byte ranges of each glyph in `glyf` according to `loca`.
+    fn glyph_ranges(loca: &[u8], long_loca: bool, num_glyphs: usize) -> Vec<Range<usize>> {
+        let offset = |i: usize| if long_loca {
+            u32::from_be_bytes(loca[4 * i..4 * i + 4].try_into().unwrap()) as usize
+        } else {
+            u16::from_be_bytes([loca[2 * i], loca[2 * i + 1]]) as usize * 2
+        };
+        (0..num_glyphs).map(|i| offset(i)..offset(i + 1)).collect()
+    }
+
+    /// The WOFF2 transformed form of `glyf` and `loca`, see the WOFF2 specification, 5.1.
+    fn transform_glyf(glyf: &[u8], ranges: &[Range<usize>], long_loca: bool) -> Vec<u8> {
+        // nContour, nPoints, flag, glyph, composite, bbox and instruction streams.
+        let mut streams: [Vec<u8>; 7] = Default::default();
+        let mut bitmap = vec![0u8; ranges.len().div_ceil(32) * 4];
+        for (i, range) in ranges.iter().enumerate() {
+            let glyph = &glyf[range.clone()];
+            if glyph.is_empty() {
+                streams[0].extend(0i16.to_be_bytes());
+                continue;
+            }
+            let contours = i16::from_be_bytes([glyph[0], glyph[1]]);
+            streams[0].extend(contours.to_be_bytes());
+            let bbox = &glyph[2..10];
+            if contours < 0 {
+                bitmap[i / 8] |= 0x80 >> (i % 8);
+                streams[5].extend_from_slice(bbox);
+                let mut r = Reader::new(&glyph[10..]);
+                let mut components = Vec::new();
+                if copy_composite(&mut r, &mut components).unwrap() {
+                    let len = r.u16().unwrap();
+                    u255(len, &mut streams[3]);
+                    streams[6].extend_from_slice(r.bytes(len as usize).unwrap());
+                }
+                streams[4].extend(components);
+                continue;
+            }
+            let (ends, instructions, points) = simple_glyph(glyph, contours as usize);
+            let mut start = 0;
+            for &end in &ends {
+                u255(end + 1 - start, &mut streams[1]);
+                start = end + 1;
+            }
+            let (mut x, mut y) = (0, 0);
+            for p in &points {
+                let (flags, glyphs) = streams.split_at_mut(3);
+                triplet(p.x - x, p.y - y, p.on_curve, &mut flags[2], &mut glyphs[0]);
+                (x, y) = (p.x, p.y);
+            }
+            u255(instructions.len() as u16, &mut streams[3]);
+            streams[6].extend_from_slice(instructions);
+            let (x0, y0, x1, y1) = points.iter().fold((i32::MAX, i32::MAX, i32::MIN, i32::MIN), |b, p| {
+                (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y))
+            });
+            let computed: Vec<u8> = [x0, y0, x1, y1].iter().flat_map(|&v| (v as i16).to_be_bytes()).collect();
+            if computed != bbox {
+                bitmap[i / 8] |= 0x80 >> (i % 8);
+                streams[5].extend_from_slice(bbox);
+            }
+        }
+        streams[5].splice(0..0, bitmap);
+
+        let mut out = Vec::new();
+        for v in [0, 0, ranges.len() as u16, long_loca as u16] {
+            out.extend(v.to_be_bytes());
+        }
+        for stream in &streams {
+            out.extend((stream.len() as u32).to_be_bytes());
+        }
+        out.extend(streams.concat());
+        out
+    }
+
+    /// The WOFF2 transformed form of `hmtx`, omitting the side bearings that equal the glyph
+    /// `x_mins`; `None` if no group of them can be omitted.
+    fn transform_hmtx(hmtx: &[u8], num_h_metrics: usize, x_mins: &[i16]) -> Option<Vec<u8>> {
+        let lsb = |i: usize| if i < num_h_metrics {
+            i16::from_be_bytes([hmtx[4 * i + 2], hmtx[4 * i + 3]])
+        } else {
+            let at = 4 * num_h_metrics + 2 * (i - num_h_metrics);
+            i16::from_be_bytes([hmtx[at], hmtx[at + 1]])
+        };
+        let omit_proportional = (0..num_h_metrics).all(|i| lsb(i) == x_mins[i]);
+        let omit_monospaced = (num_h_metrics..x_mins.len()).all(|i| lsb(i) == x_mins[i]);
+        let flags = omit_proportional as u8 | (omit_monospaced as u8) << 1;
+        if flags == 0 {
+            return None;
+        }
+        let mut out = vec![flags];
+        for i in 0..num_h_metrics {
+            out.extend_from_slice(&hmtx[4 * i..4 * i + 2]);
+        }
+        for i in 0..x_mins.len() {
+            let omitted = if i < num_h_metrics { omit_proportional } else { omit_monospaced };
+            if !omitted {
+                out.extend(lsb(i).to_be_bytes());
+            }
+        }
+        Some(out)
+    }
+
+    /// WOFF2 file of `font`. With `transform`, glyf, loca and hmtx are stored in their
+    /// transformed forms, as WOFF2 encoders do; otherwise every table is stored as is.
+    fn woff2(font: &[u8], transform: bool) -> Vec<u8> {
+        let (flavor, tables) = sfnt_tables(font);
+        let face = ttf_parser::Face::from_slice(font, 0).unwrap();
+        let table = |tag: &[u8; 4]| &tables.iter().find(|(t, _)| t == tag).unwrap().1;
+        let long_loca = face.tables().head.index_to_location_format == ttf_parser::head::IndexToLocationFormat::Long;
+        let ranges = glyph_ranges(table(b"loca"), long_loca, face.number_of_glyphs() as usize);
+        let glyf = table(b"glyf");
+        let x_mins: Vec<i16> = ranges.iter()
+            .map(|r| if r.is_empty() { 0 } else { i16::from_be_bytes([glyf[r.start + 2], glyf[r.start + 3]]) })
+            .collect();
+        let num_h_metrics = u16::from_be_bytes([table(b"hhea")[34], table(b"hhea")[35]]) as usize;
+
+        let (mut directory, mut stream) = (Vec::new(), Vec::new());
+        for (tag, data) in &tables {
+            let (version, stored) = match tag {
+                b"glyf" | b"loca" if !transform => (3, data.clone()),
+                b"glyf" => (0, transform_glyf(data, &ranges, long_loca)),
+                b"loca" => (0, Vec::new()),
+                b"hmtx" if transform => transform_hmtx(data, num_h_metrics, &x_mins).map_or((0, data.clone()), |t| (1, t)),
+                _ => (0, data.clone()),
+            };
+            let index = KNOWN_TAGS.iter().position(|known| *known == tag);
+            directory.push(index.map_or(63, |i| i as u8) | version << 6);
+            if index.is_none() {
+                directory.extend_from_slice(tag);
+            }
+            base128(data.len() as u32, &mut directory);
+            let transformed = if tag == b"glyf" || tag == b"loca" { version != 3 } else { version != 0 };
+            if transformed {
+                base128(stored.len() as u32, &mut directory);
+            }
+            stream.extend(stored);
+        }
+        let compressed = brotli_stored(&stream);
+
+        let mut out = WOFF2_MAGIC.to_vec();
+        out.extend(flavor.to_be_bytes());
+        out.extend(((48 + directory.len() + compressed.len()) as u32).to_be_bytes());
+        out.extend((tables.len() as u16).to_be_bytes());
+        out.extend([0; 2]);
+        out.extend((font.len() as u32).to_be_bytes());
+        out.extend((compressed.len() as u32).to_be_bytes());
+        // Version, metadata and private block fields.
+        out.extend([0; 24]);
+        out.extend(directory);
+        out.extend(compressed);
+        out
+    }
+
+    /// Tags of the tables a WOFF2 file stores transformed.
+    fn transformed_tables(woff2: &[u8]) -> Vec<[u8; 4]> {
+        let mut r = Reader::new(woff2);
+        r.skip(12).unwrap();
+        let num_tables = r.u16().unwrap();
+        r.skip(34).unwrap();
+        (0..num_tables).filter_map(|_| {
+            let flags = r.u8().unwrap();
+            let tag = if flags & 0x3f == 63 { r.tag().unwrap() } else { *KNOWN_TAGS[(flags & 0x3f) as usize] };
+            r.base128().unwrap();
+            let version = flags >> 6;
+            let transformed = if &tag == b"glyf" || &tag == b"loca" { version != 3 } else { version != 0 };
+            transformed.then(|| r.base128().map(|_| tag).unwrap())
+        }).collect()
+    }
+
+    fn json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    fn assert_same_glyphs(font: &Font, expected: &Font) {
+        for ch in SAMPLE.chars() {
+            assert_eq!(json(&font.glyph(ch)), json(&expected.glyph(ch)), "{}", ch);
+        }
+        assert_eq!(json(&font.notdef), json(&expected.notdef));
+        assert_eq!(font.kerning, expected.kerning);
+        assert_eq!(font.mark_anchors, expected.mark_anchors);
+        assert_eq!((font.ascender, font.descender, font.line_gap), (expected.ascender, expected.descender, expected.line_gap));
+        assert_eq!(font.family_name, expected.family_name);
+    }
+
+    #[test]
+    fn woff_tables_unpack_to_the_original_bytes() {
+        let ttf = fixtures::read("DejaVuSans.ttf");
+        assert!(is_woff(&woff1(&ttf)) && !is_woff(&ttf));
+        let (flavor, tables) = sfnt_tables(&ttf);
+        for container in [woff1(&ttf), woff2(&ttf, false)] {
+            assert_eq!(sfnt_tables(&to_sfnt(&container).unwrap()), (flavor, tables.clone()));
+        }
+    }
+
+    #[test]
+    fn woff_and_woff2_fonts_load_like_the_ttf() {
+        let ttf = fixtures::read("DejaVuSans.ttf");
+        let expected = load_font(DEJAVU_SANS, SAMPLE).unwrap();
+        let transformed = woff2(&ttf, true);
+        assert_eq!(transformed_tables(&transformed), [*b"glyf", *b"hmtx", *b"loca"]);
+        assert!(transformed.len() < woff2(&ttf, false).len());
+
+        for container in [woff1(&ttf), woff2(&ttf, false), transformed] {
+            let font = load_font_from_bytes(&container, SAMPLE).unwrap();
+            assert_same_glyphs(&font, &expected);
+        }
+    }
+
+    #[test]
+    fn corrupt_containers_are_typed_errors() {
+        let ttf = fixtures::read("DejaVuSans.ttf");
+        let woff = woff1(&ttf);
+        assert_eq!(to_sfnt(&woff[..30]), Err(WoffError::Truncated));
+        assert_eq!(to_sfnt(&woff[..woff.len() / 2]), Err(WoffError::Truncated));
+        assert_eq!(to_sfnt(b"OTTO\0\0\0\0"), Err(WoffError::Invalid("not a woff or woff2 file")));
+
+        // Garble the first compressed table.
+        let mut garbled = woff.clone();
+        let (offset, comp_length, orig_length) = (44 + 4, 44 + 8, 44 + 12);
+        let read = |data: &[u8], at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+        assert!(read(&woff, comp_length) < read(&woff, orig_length));
+        let start = read(&woff, offset);
+        garbled[start..start + 4].copy_from_slice(b"junk");
+        assert_eq!(to_sfnt(&garbled), Err(WoffError::Decompression));
+
+        let woff2 = woff2(&ttf, true);
+        assert_eq!(to_sfnt(&woff2[..woff2.len() - 100]), Err(WoffError::Truncated));
+        let mut ttc = woff2.clone();
+        ttc[4..8].copy_from_slice(b"ttcf");
+        assert_eq!(to_sfnt(&ttc), Err(WoffError::Invalid("woff2 font collections are not supported")));
+
+        assert!(matches!(load_font_from_bytes(&woff[..30], "a"), Err(FontLoadError::Woff(WoffError::Truncated))));
+    }
+}
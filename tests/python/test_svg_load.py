@@ -0,0 +1,54 @@
+"""Tests of the Python bindings.
+
+Build the module with
+`cargo rustc --lib --features extension-module --crate-type cdylib`, copy
+`target/debug/libsvg_load.so` to `svg_load.so` on `PYTHONPATH` and run `pytest tests/python`.
+"""
+
+import os
+
+import svg_load
+
+FIXTURES = os.path.join(os.path.dirname(__file__), "..", "fixtures")
+
+
+def fixture(name):
+    return os.path.join(FIXTURES, name)
+
+
+def test_load_svg_from_path_and_bytes():
+    from_path = svg_load.load_svg(fixture("rect.svg"))
+    with open(fixture("rect.svg"), "rb") as f:
+        from_bytes = svg_load.load_svg(f.read())
+
+    for scene in (from_path, from_bytes):
+        assert len(scene.paths) == 1
+        path = scene.paths[0]
+        assert path.triangle_count == 2
+        assert len(path.indices) == 6
+        assert len(path.vertices) % 2 == 0
+        expected = [0x33 / 255, 0x66 / 255, 0xCC / 255, 1.0]
+        assert all(abs(a - b) < 1e-6 for a, b in zip(path.color, expected))
+        assert path.gradient_color_stops is None
+
+
+def test_load_svg_reports_errors():
+    try:
+        svg_load.load_svg(b"not an svg")
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("expected ValueError")
+
+
+def test_load_font_glyph():
+    font = svg_load.load_font(fixture("DejaVuSans.ttf"), "Ha")
+    assert font.name
+    assert font.ascender > 0 > font.descender
+
+    glyph = font.glyph("H")
+    assert glyph.advance > 0
+    assert len(glyph.indices) % 3 == 0 and len(glyph.indices) > 0
+    x_min, y_min, x_max, y_max = glyph.bbox
+    assert x_min < x_max and y_min < y_max
+    assert font.glyph("z") is None